@@ -31,7 +31,8 @@ pub enum Commands {
 
     /// Get a document by path or docid.
     Get {
-        /// File path, virtual path (qmd://), or docid (#abc123).
+        /// File path, virtual path (qmd://), or docid (#abc123). Append
+        /// `#heading` to get only that section, e.g. `notes/plan.md#next-steps`.
         file: String,
 
         /// Starting line number.
@@ -77,7 +78,9 @@ pub enum Commands {
 
     /// BM25 full-text search.
     Search {
-        /// Search query.
+        /// Search query, optionally with inline filters such as
+        /// `collection:notes`, `path:2024/*`, `after:2025-01-01`, `before:2025-02-01`,
+        /// `tag:project` or a frontmatter field like `status:draft`.
         query: String,
 
         /// Restrict to a collection.
@@ -96,6 +99,10 @@ pub enum Commands {
         #[arg(long)]
         full: bool,
 
+        /// Explain how each score was computed.
+        #[arg(long)]
+        explain: bool,
+
         /// Add line numbers to output.
         #[arg(long)]
         line_numbers: bool,
@@ -107,7 +114,9 @@ pub enum Commands {
 
     /// Vector semantic search.
     Vsearch {
-        /// Search query.
+        /// Search query, optionally with inline filters such as
+        /// `collection:notes`, `path:2024/*`, `after:2025-01-01`, `before:2025-02-01`,
+        /// `tag:project` or a frontmatter field like `status:draft`.
         query: String,
 
         /// Restrict to a collection.
@@ -126,6 +135,10 @@ pub enum Commands {
         #[arg(long)]
         full: bool,
 
+        /// Explain how each score was computed.
+        #[arg(long)]
+        explain: bool,
+
         /// Add line numbers to output.
         #[arg(long)]
         line_numbers: bool,
@@ -137,6 +150,82 @@ pub enum Commands {
         /// Embedding model path.
         #[arg(long)]
         model: Option<String>,
+
+        /// Number of vector index lists to probe (higher = better recall, slower).
+        #[arg(long)]
+        probes: Option<usize>,
+
+        /// How chunk scores are combined into a document score.
+        #[arg(long, value_enum, default_value = "max")]
+        aggregate: CliChunkAggregation,
+    },
+
+    /// Find lines matching a regex or literal, like grep.
+    ///
+    /// Prints `collection/path:line: text`. Run `qmd db trigram` once to
+    /// index substrings and speed this up on large collections.
+    Grep {
+        /// Regular expression, or literal string with `--fixed-strings`.
+        pattern: String,
+
+        /// Restrict to a collection.
+        #[arg(short, long)]
+        collection: Option<String>,
+
+        /// Treat the pattern as a literal string.
+        #[arg(short = 'F', long)]
+        fixed_strings: bool,
+
+        /// Match case-insensitively.
+        #[arg(short, long)]
+        ignore_case: bool,
+
+        /// Maximum number of matching lines.
+        #[arg(short = 'n', long, default_value = "100")]
+        limit: usize,
+    },
+
+    /// List tags with their document counts, or the documents with a tag.
+    ///
+    /// Tags come from the frontmatter `tags` field and inline `#tags`; a tag
+    /// also lists documents with its nested tags, like `project/alpha`.
+    Tags {
+        /// Tag to list documents for.
+        tag: Option<String>,
+
+        /// Restrict to a collection.
+        #[arg(short, long)]
+        collection: Option<String>,
+    },
+
+    /// List the links of a document, or broken links with `--broken`.
+    ///
+    /// Follows `[text](file.md)` and `[[wiki]]` links. A document is given as
+    /// `collection/path`, `qmd://collection/path` or a `#docid`.
+    Links {
+        /// Document to list links of.
+        #[arg(required_unless_present = "broken")]
+        file: Option<String>,
+
+        /// List links that do not resolve to an indexed document.
+        #[arg(long)]
+        broken: bool,
+
+        /// Restrict `--broken` to a collection.
+        #[arg(short, long, requires = "broken")]
+        collection: Option<String>,
+    },
+
+    /// Show the heading outline of a document with line ranges.
+    Outline {
+        /// Document as `collection/path`, `qmd://collection/path` or a `#docid`.
+        file: String,
+    },
+
+    /// List the documents linking to a document.
+    Backlinks {
+        /// Document as `collection/path`, `qmd://collection/path` or a `#docid`.
+        file: String,
     },
 
     /// Generate embeddings for all documents.
@@ -145,6 +234,12 @@ pub enum Commands {
         #[arg(long)]
         force: bool,
 
+        /// How documents are split into chunks. Defaults to the chunker
+        /// the index was embedded with, or tokens for a new index; switching
+        /// the chunker of an embedded index requires `--force`.
+        #[arg(long, value_enum)]
+        chunker: Option<CliChunker>,
+
         /// Model path (GGUF file).
         #[arg(long)]
         model: Option<String>,
@@ -160,7 +255,9 @@ pub enum Commands {
 
     /// Hybrid search with query expansion, RRF fusion, and reranking.
     Qsearch {
-        /// Search query.
+        /// Search query, optionally with inline filters such as
+        /// `collection:notes`, `path:2024/*`, `after:2025-01-01`, `before:2025-02-01`,
+        /// `tag:project` or a frontmatter field like `status:draft`.
         query: String,
 
         /// Restrict to a collection.
@@ -175,6 +272,10 @@ pub enum Commands {
         #[arg(long)]
         full: bool,
 
+        /// Explain how each score was computed.
+        #[arg(long)]
+        explain: bool,
+
         /// Skip query expansion.
         #[arg(long)]
         no_expand: bool,
@@ -183,6 +284,15 @@ pub enum Commands {
         #[arg(long)]
         no_rerank: bool,
 
+        /// How rerank scores are combined with the retrieval rank.
+        #[arg(long, value_enum, default_value = "position")]
+        blend: CliRerankBlend,
+
+        /// Weight of the rerank score for `--blend weighted`, from 0.0 to
+        /// 1.0 [default: 0.5].
+        #[arg(long, value_parser = parse_weight)]
+        rerank_weight: Option<f64>,
+
         /// Output format.
         #[arg(long, value_enum, default_value = "cli")]
         format: CliOutputFormat,
@@ -210,6 +320,10 @@ pub enum Commands {
         #[arg(short = 'n', long, default_value = "10")]
         limit: usize,
 
+        /// Scoring mode.
+        #[arg(long, value_enum, default_value = "cross-encoder")]
+        mode: CliRerankMode,
+
         /// Output format.
         #[arg(long, value_enum, default_value = "cli")]
         format: CliOutputFormat,
@@ -217,7 +331,7 @@ pub enum Commands {
 
     /// Ask a question and get an answer from relevant documents.
     Ask {
-        /// Question to ask.
+        /// Question to ask, optionally with inline search filters.
         question: String,
 
         /// Restrict to a collection.
@@ -339,6 +453,20 @@ pub enum DbCommands {
 
     /// Clear LLM cache.
     ClearCache,
+
+    /// Build the trigram substring index used by `qmd grep`.
+    Trigram {
+        /// Drop the index instead.
+        #[arg(long)]
+        off: bool,
+    },
+
+    /// Convert stored vectors to another storage format.
+    Requantize {
+        /// Target format (conversions may only lose precision).
+        #[arg(value_enum)]
+        mode: CliQuantization,
+    },
 }
 
 /// CLI output format options (wraps qmd::OutputFormat for clap integration).
@@ -371,3 +499,120 @@ fn from(fmt: CliOutputFormat) -> Self {
         }
     }
 }
+
+/// Chunk score aggregation (wraps qmd::ChunkAggregation for clap integration).
+#[derive(ValueEnum, Clone, Copy, Debug, Default)]
+pub enum CliChunkAggregation {
+    /// Best-matching chunk.
+    #[default]
+    Max,
+    /// Mean over matching chunks.
+    Mean,
+    /// Average of max and mean.
+    MaxMean,
+}
+
+impl From<CliChunkAggregation> for qmd::ChunkAggregation {
+    fn from(agg: CliChunkAggregation) -> Self {
+        match agg {
+            CliChunkAggregation::Max => Self::Max,
+            CliChunkAggregation::Mean => Self::Mean,
+            CliChunkAggregation::MaxMean => Self::MaxMean,
+        }
+    }
+}
+
+/// Embedding chunker (wraps qmd::Chunker for clap integration).
+#[derive(ValueEnum, Clone, Copy, Debug, Default)]
+pub enum CliChunker {
+    /// Fixed token windows with overlap.
+    #[default]
+    Tokens,
+    /// Heading sections, keeping code blocks and tables whole.
+    Markdown,
+}
+
+impl From<CliChunker> for qmd::Chunker {
+    fn from(chunker: CliChunker) -> Self {
+        match chunker {
+            CliChunker::Tokens => Self::Tokens,
+            CliChunker::Markdown => Self::Markdown,
+        }
+    }
+}
+
+/// Vector storage format (wraps qmd::Quantization for clap integration).
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum CliQuantization {
+    /// Full-precision 32-bit floats.
+    F32,
+    /// 8-bit scalar quantization.
+    Int8,
+    /// 1-bit binary quantization.
+    Binary,
+}
+
+impl From<CliQuantization> for qmd::Quantization {
+    fn from(mode: CliQuantization) -> Self {
+        match mode {
+            CliQuantization::F32 => Self::F32,
+            CliQuantization::Int8 => Self::Int8,
+            CliQuantization::Binary => Self::Binary,
+        }
+    }
+}
+
+/// Rerank scoring mode (wraps qmd::RerankMode for clap integration).
+#[derive(ValueEnum, Clone, Copy, Debug, Default)]
+pub enum CliRerankMode {
+    /// Score query/document pairs from the reranker's yes/no logits.
+    #[default]
+    CrossEncoder,
+    /// Embedding similarity fallback for models that are not rerankers.
+    Embedding,
+}
+
+impl From<CliRerankMode> for qmd::RerankMode {
+    fn from(mode: CliRerankMode) -> Self {
+        match mode {
+            CliRerankMode::CrossEncoder => Self::CrossEncoder,
+            CliRerankMode::Embedding => Self::Embedding,
+        }
+    }
+}
+
+/// Rerank score blending (wraps qmd::RerankBlend for clap integration).
+#[derive(ValueEnum, Clone, Copy, Debug, Default)]
+pub enum CliRerankBlend {
+    /// Order by rerank score alone.
+    Replace,
+    /// Interpolate with `--rerank-weight`.
+    Weighted,
+    /// Trust the retrieval rank more near the top of the list.
+    #[default]
+    Position,
+}
+
+impl CliRerankBlend {
+    /// Convert to the library type, using `weight` for weighted blending.
+    ///
+    /// A weight is only accepted with `Weighted`, which defaults to 0.5.
+    pub fn to_blend(self, weight: Option<f64>) -> anyhow::Result<qmd::RerankBlend> {
+        match (self, weight) {
+            (Self::Weighted, w) => Ok(qmd::RerankBlend::Weighted(w.unwrap_or(0.5))),
+            (_, Some(_)) => anyhow::bail!("--rerank-weight requires --blend weighted"),
+            (Self::Replace, None) => Ok(qmd::RerankBlend::Replace),
+            (Self::Position, None) => Ok(qmd::RerankBlend::PositionAware),
+        }
+    }
+}
+
+/// Parse a blend weight, which must be between 0.0 and 1.0.
+fn parse_weight(value: &str) -> Result<f64, String> {
+    let weight: f64 = value.parse().map_err(|e| format!("{e}"))?;
+    if (0.0..=1.0).contains(&weight) {
+        Ok(weight)
+    } else {
+        Err(format!("{weight} is not between 0.0 and 1.0"))
+    }
+}
@@ -6,18 +6,19 @@
 use clap::Parser;
 use cli::{Cli, CollectionCommands, Commands, ContextCommands, DbCommands, ModelCommands};
 use colored::Colorize;
+use qmd::collections::NamedCollection;
 use qmd::{
-    OutputFormat, Store, add_collection as yaml_add_collection, add_context, add_line_numbers,
-    format_bytes, format_documents, format_ls_time, format_search_results, format_time_ago,
-    get_collection, is_docid, is_virtual_path, list_all_contexts,
+    FtsSearchOptions, IndexOptions, OutputFormat, SearchFilter, SearchResult, Store,
+    add_collection as yaml_add_collection, add_context, add_line_numbers, format_bytes,
+    format_documents, format_ls_time, format_search_results, format_search_results_explained,
+    format_time_ago, get_collection, is_virtual_path, list_all_contexts,
     list_collections as yaml_list_collections, match_files_by_glob, parse_virtual_path,
     remove_collection as yaml_remove_collection, remove_context,
-    rename_collection as yaml_rename_collection, set_global_context, should_exclude,
+    rename_collection as yaml_rename_collection, set_global_context,
 };
 use std::collections::HashSet;
 use std::fs;
 use std::path::Path;
-use walkdir::WalkDir;
 
 fn main() -> Result<()> {
     let cli = Cli::parse();
@@ -49,6 +50,7 @@ fn dispatch_command(cmd: Commands) -> Result<()> {
             limit,
             min_score,
             full,
+            explain,
             format,
             ..
         } => handle_search(
@@ -57,6 +59,7 @@ fn dispatch_command(cmd: Commands) -> Result<()> {
             limit,
             min_score,
             full,
+            explain,
             &format.into(),
         ),
         Commands::Vsearch {
@@ -65,8 +68,11 @@ fn dispatch_command(cmd: Commands) -> Result<()> {
             limit,
             min_score,
             full,
+            explain,
             format,
             model,
+            probes,
+            aggregate,
             ..
         } => handle_vsearch(
             &query,
@@ -74,10 +80,40 @@ fn dispatch_command(cmd: Commands) -> Result<()> {
             limit,
             min_score,
             full,
+            explain,
             &format.into(),
             model.as_deref(),
+            probes,
+            aggregate.into(),
         ),
-        Commands::Embed { force, model } => handle_embed(force, model.as_deref()),
+        Commands::Grep {
+            pattern,
+            collection,
+            fixed_strings,
+            ignore_case,
+            limit,
+        } => handle_grep(
+            &pattern,
+            collection.as_deref(),
+            &qmd::GrepOptions {
+                fixed_strings,
+                ignore_case,
+                limit,
+            },
+        ),
+        Commands::Tags { tag, collection } => handle_tags(tag.as_deref(), collection.as_deref()),
+        Commands::Links {
+            file,
+            broken,
+            collection,
+        } => handle_links(file.as_deref(), broken, collection.as_deref()),
+        Commands::Outline { file } => handle_outline(&file),
+        Commands::Backlinks { file } => handle_backlinks(&file),
+        Commands::Embed {
+            force,
+            chunker,
+            model,
+        } => handle_embed(force, chunker.map(Into::into), model.as_deref()),
         Commands::Models(c) => handle_models(c),
         Commands::Db(c) => handle_db(c),
         Commands::Qsearch {
@@ -85,16 +121,21 @@ fn dispatch_command(cmd: Commands) -> Result<()> {
             collection,
             limit,
             full,
+            explain,
             no_expand,
             no_rerank,
+            blend,
+            rerank_weight,
             format,
         } => handle_qsearch(
             &query,
             collection.as_deref(),
             limit,
             full,
+            explain,
             no_expand,
             no_rerank,
+            blend.to_blend(rerank_weight)?,
             &format.into(),
         ),
         Commands::Expand { query, lexical } => handle_expand(&query, lexical),
@@ -102,8 +143,9 @@ fn dispatch_command(cmd: Commands) -> Result<()> {
             query,
             files,
             limit,
+            mode,
             format,
-        } => handle_rerank(&query, &files, limit, &format.into()),
+        } => handle_rerank(&query, &files, limit, mode.into(), &format.into()),
         Commands::Ask {
             question,
             collection,
@@ -166,7 +208,9 @@ fn handle_collection(cmd: CollectionCommands) -> Result<()> {
             }
             yaml_add_collection(&coll_name, &abs_path_str, &mask)?;
             println!("Creating collection '{coll_name}'...");
-            index_files(&abs_path_str, &mask, &coll_name)?;
+            let collection = get_collection(&coll_name)?
+                .ok_or_else(|| anyhow::anyhow!("Collection '{coll_name}' was not saved"))?;
+            index_files(&Store::new()?, &collection)?;
             println!(
                 "{} Collection '{}' created successfully",
                 "✓".green(),
@@ -444,31 +488,38 @@ fn handle_get(
     } else {
         (file, None)
     };
-    let from_line = from_line.or(parsed_from_line);
-    let (collection, path) = if is_docid(input_path) {
-        store
-            .find_document_by_docid(input_path)?
-            .ok_or_else(|| anyhow::anyhow!("Document not found: {input_path}"))?
-    } else if is_virtual_path(input_path) {
-        parse_virtual_path(input_path)
-            .ok_or_else(|| anyhow::anyhow!("Invalid virtual path: {input_path}"))?
-    } else {
-        let parts: Vec<&str> = input_path.splitn(2, '/').collect();
-        if parts.len() == 2 {
-            (parts[0].to_string(), parts[1].to_string())
-        } else {
-            return Err(anyhow::anyhow!("Could not resolve path: {input_path}"));
+    // A docid starts with `#`; any later `#` names a section.
+    let (doc_ref, section) = match input_path.rsplit_once('#') {
+        Some((doc_path, name)) if !doc_path.is_empty() && !name.is_empty() => {
+            (doc_path, Some(name))
         }
+        _ => (input_path, None),
+    };
+    let (collection, path) = store
+        .locate_document(doc_ref)?
+        .ok_or_else(|| anyhow::anyhow!("Document not found: {doc_ref}"))?;
+    let (from_line, line_limit) = match section {
+        Some(name) => {
+            let heading = store
+                .find_section(&collection, &path, name)?
+                .ok_or_else(|| anyhow::anyhow!("Heading not found: #{name}"))?;
+            let section_lines = heading.end_line - heading.line + 1;
+            (
+                Some(heading.line),
+                Some(max_lines.map_or(section_lines, |n| n.min(section_lines))),
+            )
+        }
+        None => (from_line.or(parsed_from_line), max_lines),
     };
     let doc = store
         .get_document(&collection, &path)?
         .ok_or_else(|| anyhow::anyhow!("Document not found"))?;
     let mut body = doc.body.unwrap_or_default();
     let start_line = from_line.unwrap_or(1);
-    if from_line.is_some() || max_lines.is_some() {
+    if from_line.is_some() || line_limit.is_some() {
         let lines: Vec<&str> = body.lines().collect();
         let start = start_line.saturating_sub(1);
-        let end = max_lines.map_or(lines.len(), |n| (start + n).min(lines.len()));
+        let end = line_limit.map_or(lines.len(), |n| (start + n).min(lines.len()));
         body = lines[start..end].join("\n");
     }
     if line_numbers {
@@ -551,8 +602,9 @@ fn handle_status() -> Result<()> {
     let status = store.get_status()?;
     let contexts = list_all_contexts()?;
     println!("{}\n", "QMD Status".bold());
-    println!("Index: {db_path}");
-    println!("Size:  {}\n", format_bytes(index_size));
+    println!("Index:  {db_path}");
+    println!("Size:   {}", format_bytes(index_size));
+    println!("Schema: v{}\n", status.schema_version);
     println!("{}", "Documents".bold());
     println!("  Total:    {} files indexed", status.total_documents);
     if status.needs_embedding > 0 {
@@ -562,6 +614,25 @@ fn handle_status() -> Result<()> {
             format!("{} need embedding", status.needs_embedding)
         );
     }
+    let vector_count = store.get_vector_count()?;
+    if vector_count > 0 {
+        let lists = store.get_vector_index_lists()?;
+        let index = if lists > 0 {
+            format!("IVF, {lists} lists")
+        } else {
+            "exact".to_string()
+        };
+        println!(
+            "  Vectors:  {} chunks ({}, {}, {} chunker)",
+            vector_count,
+            store.get_vector_quantization()?.as_str(),
+            index,
+            store.get_vector_chunker()?.as_str()
+        );
+    }
+    if store.has_trigram_index()? {
+        println!("  Trigram:  enabled (qmd grep)");
+    }
     if status.collections.is_empty() {
         println!("\n{}", "No collections.".dimmed());
     } else {
@@ -649,26 +720,62 @@ fn handle_update(pull: bool) -> Result<()> {
                     .output();
             }
         }
-        index_files(&coll.pwd, &coll.glob_pattern, &coll.name)?;
+        if let Some(yaml_coll) = yaml_collections.iter().find(|c| c.name == coll.name) {
+            index_files(&store, yaml_coll)?;
+        }
         println!();
     }
     println!("{} All collections updated.", "✓".green());
     Ok(())
 }
 
+/// Print search results, with score explanations if requested.
+fn print_search_results(
+    results: &[SearchResult],
+    format: &OutputFormat,
+    full: bool,
+    explain: bool,
+) {
+    let output = if explain {
+        format_search_results_explained(results, format, full)
+    } else {
+        format_search_results(results, format, full)
+    };
+    println!("{output}");
+}
+
+/// Split inline filters out of a query and add the `--collection` flag.
+///
+/// Frontmatter fields present in the index are recognised as filters too.
+fn split_filters(
+    store: &Store,
+    query: &str,
+    collection: Option<&str>,
+) -> Result<(SearchFilter, String)> {
+    let (filter, text) = SearchFilter::parse_with_fields(query, &store.get_metadata_keys()?)?;
+    if text.is_empty() {
+        anyhow::bail!("Query has no search terms besides filters");
+    }
+    Ok((filter.with_collection(collection), text))
+}
+
 fn handle_search(
     query: &str,
     collection: Option<&str>,
     limit: usize,
     min_score: Option<f64>,
     full: bool,
+    explain: bool,
     format: &OutputFormat,
 ) -> Result<()> {
+    let fts = FtsSearchOptions::from_config()?;
     let store = Store::new()?;
-    let mut results = store.search_fts(query, limit, collection)?;
+    let (filter, query) = split_filters(&store, query, collection)?;
+    let mut results = store.search_fts_with(&query, limit, &filter, &fts)?;
     if let Some(min) = min_score {
         results.retain(|r| r.score >= min);
     }
+    store.add_snippets(&query, &mut results)?;
     if full {
         for result in &mut results {
             if result.doc.body.is_none() {
@@ -680,7 +787,7 @@ fn handle_search(
             }
         }
     }
-    println!("{}", format_search_results(&results, format, full));
+    print_search_results(&results, format, full, explain);
     Ok(())
 }
 
@@ -690,12 +797,16 @@ fn handle_vsearch(
     limit: usize,
     min_score: Option<f64>,
     full: bool,
+    explain: bool,
     format: &OutputFormat,
     model_path: Option<&str>,
+    probes: Option<usize>,
+    aggregation: qmd::ChunkAggregation,
 ) -> Result<()> {
-    use qmd::EmbeddingEngine;
+    use qmd::{EmbeddingEngine, VecSearchOptions};
     use std::path::PathBuf;
     let store = Store::new()?;
+    let (filter, query) = split_filters(&store, query, collection)?;
     store.check_and_warn_health();
     let mut engine = if let Some(path) = model_path {
         EmbeddingEngine::new(&PathBuf::from(path))?
@@ -706,11 +817,19 @@ fn handle_vsearch(
         std::process::exit(1);
     };
     println!("Generating query embedding...");
-    let query_result = engine.embed_query(query)?;
-    let mut results = store.search_vec(&query_result.embedding, limit, collection)?;
+    let query_result = engine.embed_query(&query)?;
+    let mut options = VecSearchOptions {
+        aggregation,
+        ..VecSearchOptions::default()
+    };
+    if let Some(probes) = probes {
+        options.probes = probes;
+    }
+    let mut results = store.search_vec_with(&query_result.embedding, limit, &filter, &options)?;
     if let Some(min) = min_score {
         results.retain(|r| r.score >= min);
     }
+    store.add_snippets(&query, &mut results)?;
     if results.is_empty() {
         println!("No results found.");
         return Ok(());
@@ -726,28 +845,162 @@ fn handle_vsearch(
             }
         }
     }
-    println!("{}", format_search_results(&results, format, full));
+    print_search_results(&results, format, full, explain);
+    Ok(())
+}
+
+fn handle_grep(pattern: &str, collection: Option<&str>, options: &qmd::GrepOptions) -> Result<()> {
+    let store = Store::new()?;
+    let matches = store.grep(pattern, &SearchFilter::collection(collection), options)?;
+    if matches.is_empty() {
+        println!("No matches found.");
+        return Ok(());
+    }
+    for m in &matches {
+        println!("{m}");
+    }
+    Ok(())
+}
+
+fn handle_tags(tag: Option<&str>, collection: Option<&str>) -> Result<()> {
+    let store = Store::new()?;
+    if let Some(name) = tag {
+        let docs = store.get_tagged_documents(name, collection)?;
+        if docs.is_empty() {
+            println!("No documents tagged '{name}'.");
+            return Ok(());
+        }
+        for doc in docs {
+            println!(
+                "{}{}  {}",
+                format!("qmd://{}/", doc.collection_name).dimmed(),
+                doc.path.cyan(),
+                doc.title
+            );
+        }
+        return Ok(());
+    }
+
+    let tags = store.list_tags(collection)?;
+    if tags.is_empty() {
+        println!("No tags found.");
+        return Ok(());
+    }
+    let mut current: Option<&str> = None;
+    for count in &tags {
+        if current != Some(count.collection.as_str()) {
+            if current.is_some() {
+                println!();
+            }
+            println!("{}", count.collection.cyan().bold());
+            current = Some(&count.collection);
+        }
+        println!(
+            "  #{}  {}",
+            count.tag,
+            format!("({})", count.count).dimmed()
+        );
+    }
     Ok(())
 }
 
-fn handle_embed(force: bool, model_path: Option<&str>) -> Result<()> {
-    use qmd::{
-        CHUNK_OVERLAP_TOKENS, CHUNK_SIZE_TOKENS, Cursor, EmbeddingEngine, Progress,
-        chunk_document_by_tokens, format_doc_for_embedding, format_eta, render_progress_bar,
+fn handle_links(file: Option<&str>, broken: bool, collection: Option<&str>) -> Result<()> {
+    let store = Store::new()?;
+    let links = match file {
+        Some(name) => {
+            let (coll, path) = store
+                .locate_document(name)?
+                .ok_or_else(|| anyhow::anyhow!("Document not found: {name}"))?;
+            let mut links = store.get_links(&coll, &path)?;
+            if broken {
+                links.retain(|link| link.resolved.is_none());
+            }
+            links
+        }
+        None => store.get_broken_links(collection)?,
     };
-    use std::io::Write;
-    use std::path::PathBuf;
-    use std::time::Instant;
+    if links.is_empty() {
+        println!(
+            "{}",
+            if broken {
+                "No broken links."
+            } else {
+                "No links found."
+            }
+        );
+        return Ok(());
+    }
+    for link in &links {
+        let source = format!("{}/{}:{}", link.collection, link.path, link.line);
+        let target = match &link.resolved {
+            Some((coll, path)) => format!("qmd://{coll}/{path}").cyan(),
+            None => format!("{} (broken)", link.target).red(),
+        };
+        println!(
+            "{}  {}  {} {}",
+            source.dimmed(),
+            link.text,
+            "->".dimmed(),
+            target
+        );
+    }
+    Ok(())
+}
+
+fn handle_outline(file: &str) -> Result<()> {
     let store = Store::new()?;
-    if force {
-        let cleared = store.clear_embeddings()?;
-        println!("Cleared {cleared} existing embeddings");
+    let (coll, path) = store
+        .locate_document(file)?
+        .ok_or_else(|| anyhow::anyhow!("Document not found: {file}"))?;
+    let headings = store.get_outline(&coll, &path)?;
+    if headings.is_empty() {
+        println!("No headings found.");
+        return Ok(());
     }
-    let pending = store.get_hashes_needing_embedding()?;
-    if pending.is_empty() {
-        println!("{} All documents already have embeddings.", "✓".green());
+    for heading in &headings {
+        println!(
+            "{}{} {}  {}",
+            "  ".repeat(heading.level - 1),
+            "#".repeat(heading.level).dimmed(),
+            heading.text,
+            format!("#{} L{}-{}", heading.slug, heading.line, heading.end_line).dimmed()
+        );
+    }
+    Ok(())
+}
+
+fn handle_backlinks(file: &str) -> Result<()> {
+    let store = Store::new()?;
+    let (coll, path) = store
+        .locate_document(file)?
+        .ok_or_else(|| anyhow::anyhow!("Document not found: {file}"))?;
+    let links = store.get_backlinks(&coll, &path)?;
+    if links.is_empty() {
+        println!("No backlinks found.");
         return Ok(());
     }
+    for link in &links {
+        println!(
+            "{}{}{}  {}",
+            format!("qmd://{}/", link.collection).dimmed(),
+            link.path.cyan(),
+            format!(":{}", link.line).dimmed(),
+            link.text
+        );
+    }
+    Ok(())
+}
+
+fn handle_embed(
+    force: bool,
+    chunker: Option<qmd::Chunker>,
+    model_path: Option<&str>,
+) -> Result<()> {
+    use qmd::{Cursor, EmbedOptions, EmbeddingEngine, Progress, format_eta, render_progress_bar};
+    use std::io::Write;
+    use std::ops::ControlFlow;
+    use std::path::PathBuf;
+    let store = Store::new()?;
     let mut engine = if let Some(path) = model_path {
         EmbeddingEngine::new(&PathBuf::from(path))?
     } else if let Ok(e) = EmbeddingEngine::load_default() {
@@ -756,132 +1009,58 @@ fn handle_embed(force: bool, model_path: Option<&str>) -> Result<()> {
         eprintln!("{} Embedding model not found.", "Error:".red());
         std::process::exit(1);
     };
-    eprintln!("Chunking {} documents...", pending.len());
-    #[allow(dead_code)]
-    struct ChunkItem {
-        hash: String,
-        title: String,
-        text: String,
-        seq: usize,
-        pos: usize,
-        bytes: usize,
-        display_name: String,
-    }
-    let mut all_chunks: Vec<ChunkItem> = Vec::new();
-    for (hash, path, content) in &pending {
-        if content.is_empty() {
-            continue;
-        }
-        let title = Store::extract_title(content);
-        match chunk_document_by_tokens(&engine, content, CHUNK_SIZE_TOKENS, CHUNK_OVERLAP_TOKENS) {
-            Ok(chunks) => {
-                for (seq, chunk) in chunks.into_iter().enumerate() {
-                    all_chunks.push(ChunkItem {
-                        hash: hash.clone(),
-                        title: title.clone(),
-                        text: chunk.text,
-                        seq,
-                        pos: chunk.pos,
-                        bytes: chunk.bytes,
-                        display_name: path.clone(),
-                    });
-                }
-            }
-            Err(_) => {
-                all_chunks.push(ChunkItem {
-                    hash: hash.clone(),
-                    title: title.clone(),
-                    text: content.clone(),
-                    seq: 0,
-                    pos: 0,
-                    bytes: content.len(),
-                    display_name: path.clone(),
-                });
-            }
-        }
-    }
-    if all_chunks.is_empty() {
-        println!("{} No non-empty documents to embed.", "✓".green());
-        return Ok(());
-    }
-    let total_bytes: usize = all_chunks.iter().map(|c| c.bytes).sum();
-    let total_chunks = all_chunks.len();
-    let total_docs = pending.len();
-    println!(
-        "{} {} {}",
-        "Embedding".bold(),
-        format!("{total_docs} documents").bold(),
-        format!("({total_chunks} chunks, {})", format_bytes(total_bytes)).dimmed()
-    );
+    let options = EmbedOptions {
+        force,
+        chunker: match chunker {
+            Some(c) => c,
+            None => store.get_vector_chunker()?,
+        },
+    };
     let progress = Progress::new();
-    progress.indeterminate();
-    let first_chunk = &all_chunks[0];
-    let first_text = format_doc_for_embedding(&first_chunk.text, Some(&first_chunk.title));
-    let first_result = engine.embed(&first_text)?;
-    let dims = first_result.embedding.len();
-    store.ensure_vector_table(dims)?;
-    Cursor::hide();
-    let now = chrono::Utc::now().to_rfc3339();
-    let start_time = Instant::now();
-    let mut chunks_embedded = 0usize;
-    let mut errors = 0usize;
-    let mut bytes_processed = 0usize;
-    store.insert_embedding(
-        &first_chunk.hash,
-        first_chunk.seq,
-        first_chunk.pos,
-        &first_result.embedding,
-        &first_result.model,
-        &now,
-    )?;
-    chunks_embedded += 1;
-    bytes_processed += first_chunk.bytes;
-    for chunk in all_chunks.iter().skip(1) {
-        let formatted = format_doc_for_embedding(&chunk.text, Some(&chunk.title));
-        match engine.embed(&formatted) {
-            Ok(result) => {
-                store.insert_embedding(
-                    &chunk.hash,
-                    chunk.seq,
-                    chunk.pos,
-                    &result.embedding,
-                    &result.model,
-                    &now,
-                )?;
-                chunks_embedded += 1;
-            }
-            Err(e) => {
-                errors += 1;
-                eprintln!("\n{} Error embedding: {}", "⚠".yellow(), e);
-            }
+    let report = store.embed_pending(&mut engine, &options, |p| {
+        if p.chunks_done == 0 {
+            println!(
+                "{} {} {}",
+                "Embedding".bold(),
+                format!("{} documents", p.total_docs).bold(),
+                format!(
+                    "({} chunks, {})",
+                    p.total_chunks,
+                    format_bytes(p.total_bytes)
+                )
+                .dimmed()
+            );
+            progress.indeterminate();
+            Cursor::hide();
+            return ControlFlow::Continue(());
         }
-        bytes_processed += chunk.bytes;
-        let percent = (bytes_processed as f64 / total_bytes as f64) * 100.0;
+        let percent = p.percent();
         progress.set(percent);
-        let elapsed = start_time.elapsed().as_secs_f64();
-        let bytes_per_sec = bytes_processed as f64 / elapsed;
-        let remaining_bytes = total_bytes.saturating_sub(bytes_processed);
-        let eta_sec = remaining_bytes as f64 / bytes_per_sec;
-        let bar = render_progress_bar(percent, 20);
-        let eta = if elapsed > 2.0 {
-            format_eta(eta_sec)
-        } else {
-            "...".to_string()
+        let eta = match p.eta_secs() {
+            Some(secs) if p.elapsed.as_secs_f64() > 2.0 => format_eta(secs),
+            _ => "...".to_string(),
         };
         eprint!(
             "\r{} {:3.0}% {}/{} {} ETA {}   ",
-            bar.cyan(),
+            render_progress_bar(percent, 20).cyan(),
             percent,
-            chunks_embedded,
-            total_chunks,
-            format!("{}/s", format_bytes(bytes_per_sec as usize)).dimmed(),
+            p.chunks_done,
+            p.total_chunks,
+            format!("{}/s", format_bytes(p.bytes_per_sec() as usize)).dimmed(),
             eta.dimmed()
         );
         std::io::stderr().flush().ok();
-    }
+        ControlFlow::Continue(())
+    })?;
     progress.clear();
     Cursor::show();
-    let total_time_sec = start_time.elapsed().as_secs_f64();
+    if force {
+        println!("Cleared {} existing embeddings", report.cleared);
+    }
+    if report.chunks == 0 && report.failures.is_empty() {
+        println!("{} All documents already have embeddings.", "✓".green());
+        return Ok(());
+    }
     println!(
         "\r{} {}                                    ",
         render_progress_bar(100.0, 20).green(),
@@ -890,12 +1069,18 @@ struct ChunkItem {
     println!(
         "\n{} Embedded {} chunks from {} documents in {}",
         "✓".green(),
-        chunks_embedded.to_string().bold(),
-        total_docs.to_string().bold(),
-        format_eta(total_time_sec).bold()
+        report.chunks.to_string().bold(),
+        report.documents.to_string().bold(),
+        format_eta(report.elapsed.as_secs_f64()).bold()
     );
-    if errors > 0 {
-        println!("{} {} chunks failed", "⚠".yellow(), errors);
+    if !report.failures.is_empty() {
+        println!("{} {} chunks failed", "⚠".yellow(), report.failures.len());
+        for (path, reason) in &report.failures {
+            eprintln!("  {} {}: {}", "⚠".yellow(), path, reason);
+        }
+    }
+    if let Some(lists) = report.index_lists {
+        println!("{} Built vector index ({} lists)", "✓".green(), lists);
     }
     Ok(())
 }
@@ -988,6 +1173,33 @@ fn handle_db(cmd: DbCommands) -> Result<()> {
             let cleared = store.clear_cache()?;
             println!("{} Cleared {} cached entries", "✓".green(), cleared);
         }
+        DbCommands::Trigram { off } => {
+            if off {
+                store.set_trigram_index(false)?;
+                store.vacuum()?;
+                println!("{} Trigram index dropped", "✓".green());
+            } else {
+                let indexed = store.set_trigram_index(true)?;
+                println!(
+                    "{} Trigram index built ({} documents)",
+                    "✓".green(),
+                    indexed
+                );
+            }
+        }
+        DbCommands::Requantize { mode } => {
+            let mode: qmd::Quantization = mode.into();
+            let converted = store.requantize(mode)?;
+            println!(
+                "{} Vectors stored as {} ({} converted)",
+                "✓".green(),
+                mode.as_str(),
+                converted
+            );
+            if converted > 0 {
+                store.vacuum()?;
+            }
+        }
     }
     Ok(())
 }
@@ -997,131 +1209,45 @@ fn handle_qsearch(
     collection: Option<&str>,
     limit: usize,
     full: bool,
+    explain: bool,
     no_expand: bool,
     no_rerank: bool,
+    blend: qmd::RerankBlend,
     format: &OutputFormat,
 ) -> Result<()> {
-    use qmd::{EmbeddingEngine, GenerationEngine, RerankDocument, RerankEngine};
+    use qmd::{HybridEngines, HybridOptions, HybridSearcher};
     let store = Store::new()?;
+    let (filter, query) = split_filters(&store, query, collection)?;
     store.check_and_warn_health();
-    let queries = if no_expand || !GenerationEngine::is_available() {
-        vec![qmd::Queryable::lex(query), qmd::Queryable::vec(query)]
-    } else {
-        println!("Expanding query...");
-        match GenerationEngine::load_default() {
-            Ok(engine) => match engine.expand_query(query, true) {
-                Ok(q) => q,
-                Err(_) => qmd::expand_query_simple(query),
-            },
-            Err(_) => qmd::expand_query_simple(query),
-        }
+    let options = HybridOptions {
+        limit,
+        expand: !no_expand,
+        rerank: !no_rerank,
+        blend,
+        filter,
+        fts: FtsSearchOptions::from_config()?,
+        include_body: full,
+        ..HybridOptions::default()
     };
-    let mut fts_results: Vec<(String, String, String, String)> = Vec::new();
-    let mut vec_results: Vec<(String, String, String, String)> = Vec::new();
-    for q in &queries {
-        match q.query_type {
-            qmd::QueryType::Lex => {
-                if let Ok(results) = store.search_fts(&q.text, limit * 2, collection) {
-                    for r in results {
-                        fts_results.push((
-                            r.doc.filepath.clone(),
-                            r.doc.display_path.clone(),
-                            r.doc.title.clone(),
-                            r.doc.body.clone().unwrap_or_default(),
-                        ));
-                    }
-                }
-            }
-            qmd::QueryType::Vec | qmd::QueryType::Hyde => {
-                if let Ok(mut engine) = EmbeddingEngine::load_default() {
-                    if let Ok(query_result) = engine.embed_query(&q.text) {
-                        if let Ok(results) =
-                            store.search_vec(&query_result.embedding, limit * 2, collection)
-                        {
-                            for r in results {
-                                let body = store
-                                    .get_document(&r.doc.collection_name, &r.doc.path)
-                                    .ok()
-                                    .flatten()
-                                    .and_then(|d| d.body)
-                                    .unwrap_or_default();
-                                vec_results.push((
-                                    r.doc.filepath.clone(),
-                                    r.doc.display_path.clone(),
-                                    r.doc.title.clone(),
-                                    body,
-                                ));
-                            }
-                        }
-                    }
-                }
-            }
-        }
+    let mut engines = HybridEngines::load(&options);
+    if engines.generator.is_some() {
+        println!("Expanding query...");
     }
-    let mut rrf_results = qmd::hybrid_search_rrf(fts_results, vec_results, 60);
-    if !no_rerank && RerankEngine::is_available() && !rrf_results.is_empty() {
-        println!("Reranking {} results...", rrf_results.len().min(limit * 2));
-        if let Ok(mut reranker) = RerankEngine::load_default() {
-            let docs: Vec<RerankDocument> = rrf_results
-                .iter()
-                .take(limit * 2)
-                .map(|r| RerankDocument {
-                    file: r.file.clone(),
-                    text: r.body.clone(),
-                    title: Some(r.title.clone()),
-                })
-                .collect();
-            if let Ok(reranked) = reranker.rerank(query, &docs) {
-                let mut reordered = Vec::new();
-                for rr in reranked.results {
-                    if let Some(orig) = rrf_results.iter().find(|r| r.file == rr.file) {
-                        reordered.push(orig.clone());
-                    }
-                }
-                rrf_results = reordered;
-            }
-        }
+    if engines.reranker.is_some() {
+        println!(
+            "Reranking up to {} results...",
+            options.candidates.max(limit)
+        );
     }
-    rrf_results.truncate(limit);
-    if rrf_results.is_empty() {
+    let mut search_results = HybridSearcher::new(&store)
+        .with_engines(&mut engines)
+        .search(&query, &options)?;
+    store.add_snippets(&query, &mut search_results)?;
+    if search_results.is_empty() {
         println!("{}", "No results found.".dimmed());
         return Ok(());
     }
-    let search_results: Vec<qmd::SearchResult> = rrf_results
-        .iter()
-        .map(|r| {
-            let parts: Vec<&str> = r
-                .file
-                .strip_prefix("qmd://")
-                .unwrap_or(&r.file)
-                .splitn(2, '/')
-                .collect();
-            let (collection_name, path) = if parts.len() == 2 {
-                (parts[0].to_string(), parts[1].to_string())
-            } else {
-                (String::new(), r.file.clone())
-            };
-            qmd::SearchResult {
-                doc: qmd::DocumentResult {
-                    filepath: r.file.clone(),
-                    display_path: r.display_path.clone(),
-                    title: r.title.clone(),
-                    context: None,
-                    hash: String::new(),
-                    docid: String::new(),
-                    collection_name,
-                    path,
-                    modified_at: String::new(),
-                    body_length: r.body.len(),
-                    body: if full { Some(r.body.clone()) } else { None },
-                },
-                score: r.score,
-                source: qmd::SearchSource::Fts,
-                chunk_pos: None,
-            }
-        })
-        .collect();
-    println!("{}", format_search_results(&search_results, format, full));
+    print_search_results(&search_results, format, full, explain);
     Ok(())
 }
 
@@ -1152,7 +1278,13 @@ fn handle_expand(query: &str, include_lexical: bool) -> Result<()> {
     Ok(())
 }
 
-fn handle_rerank(query: &str, files: &str, limit: usize, format: &OutputFormat) -> Result<()> {
+fn handle_rerank(
+    query: &str,
+    files: &str,
+    limit: usize,
+    mode: qmd::RerankMode,
+    format: &OutputFormat,
+) -> Result<()> {
     use qmd::{RerankDocument, RerankEngine};
     let store = Store::new()?;
     let file_list: Vec<&str> = files
@@ -1189,10 +1321,12 @@ fn handle_rerank(query: &str, files: &str, limit: usize, format: &OutputFormat)
         std::process::exit(1);
     }
     println!("Reranking {} documents...", docs.len());
-    let mut engine = RerankEngine::load_default().map_err(|e| {
-        eprintln!("{} Could not load rerank model: {}", "Error:".red(), e);
-        std::process::exit(1);
-    })?;
+    let mut engine = RerankEngine::load_default()
+        .map_err(|e| {
+            eprintln!("{} Could not load rerank model: {}", "Error:".red(), e);
+            std::process::exit(1);
+        })?
+        .with_mode(mode);
     let result = engine.rerank(query, &docs)?;
     match format {
         OutputFormat::Json => {
@@ -1225,22 +1359,30 @@ fn handle_ask(
     limit: usize,
     max_tokens: usize,
 ) -> Result<()> {
-    use qmd::{EmbeddingEngine, GenerationEngine};
+    use qmd::{EmbeddingEngine, GenerationEngine, VecSearchOptions};
+    let fts = FtsSearchOptions::from_config()?;
     let store = Store::new()?;
+    let (filter, question) = split_filters(&store, question, collection)?;
+    let question = question.as_str();
     println!("{}", "Searching for relevant documents...".dimmed());
     let context_docs = if let Ok(mut engine) = EmbeddingEngine::load_default() {
         if let Ok(query_result) = engine.embed_query(question) {
             store
-                .search_vec(&query_result.embedding, limit, collection)
+                .search_vec_with(
+                    &query_result.embedding,
+                    limit,
+                    &filter,
+                    &VecSearchOptions::default(),
+                )
                 .unwrap_or_default()
         } else {
             store
-                .search_fts(question, limit, collection)
+                .search_fts_with(question, limit, &filter, &fts)
                 .unwrap_or_default()
         }
     } else {
         store
-            .search_fts(question, limit, collection)
+            .search_fts_with(question, limit, &filter, &fts)
             .unwrap_or_default()
     };
     if context_docs.is_empty() {
@@ -1305,75 +1447,15 @@ fn handle_index(name: Option<&str>) -> Result<()> {
     Ok(())
 }
 
-fn index_files(pwd: &str, glob_pattern: &str, collection_name: &str) -> Result<()> {
-    let store = Store::new()?;
-    let now = chrono::Utc::now().to_rfc3339();
-    let glob_matcher = glob::Pattern::new(glob_pattern)?;
-    let mut files = Vec::new();
-    for entry in WalkDir::new(pwd)
-        .follow_links(true)
-        .into_iter()
-        .filter_map(std::result::Result::ok)
-    {
-        let path = entry.path();
-        if !path.is_file() {
-            continue;
-        }
-        if should_exclude(path) {
-            continue;
-        }
-        let rel_path = path.strip_prefix(pwd).unwrap_or(path);
-        let rel_path_str = rel_path.to_string_lossy();
-        if glob_matcher.matches(&rel_path_str) {
-            files.push((path.to_path_buf(), rel_path_str.to_string()));
-        }
-    }
-    if files.is_empty() {
+fn index_files(store: &Store, collection: &NamedCollection) -> Result<()> {
+    let report = store.index_collection(collection, &IndexOptions::default())?;
+    if report.files.is_empty() {
         println!("  No files found matching pattern.");
         return Ok(());
     }
-    let mut indexed = 0;
-    let mut updated = 0;
-    let mut unchanged = 0;
-    let mut seen_paths = HashSet::new();
-    for (abs_path, rel_path) in &files {
-        let normalized_path = Store::handelize(rel_path);
-        seen_paths.insert(normalized_path.clone());
-        let content = match fs::read_to_string(abs_path) {
-            Ok(c) => c,
-            Err(_) => continue,
-        };
-        let hash = Store::hash_content(&content);
-        let title = Store::extract_title(&content);
-        if let Some((doc_id, existing_hash, existing_title)) =
-            store.find_active_document(collection_name, &normalized_path)?
-        {
-            if existing_hash == hash {
-                if existing_title != title {
-                    store.update_document_title(doc_id, &title, &now)?;
-                }
-                unchanged += 1;
-            } else {
-                store.insert_content(&hash, &content, &now)?;
-                store.update_document(doc_id, &title, &hash, &now)?;
-                updated += 1;
-            }
-        } else {
-            store.insert_content(&hash, &content, &now)?;
-            store.insert_document(collection_name, &normalized_path, &title, &hash, &now, &now)?;
-            indexed += 1;
-        }
-    }
-    let existing_paths = store.get_active_document_paths(collection_name)?;
-    let mut deactivated = 0;
-    for path in existing_paths {
-        if !seen_paths.contains(&path) {
-            store.deactivate_document(collection_name, &path)?;
-            deactivated += 1;
-        }
+    for (path, reason) in report.failures() {
+        eprintln!("  {} {}: {}", "⚠".yellow(), path, reason);
     }
-    println!(
-        "  {indexed} indexed, {updated} updated, {unchanged} unchanged, {deactivated} removed"
-    );
+    println!("  {}", report.summary());
     Ok(())
 }
@@ -10,9 +10,14 @@
 //! ## Usage
 //!
 //! ```bash
+//! # Single client over stdio
 //! qmd-mcp
+//!
+//! # Shared server over streamable HTTP
+//! qmd-mcp --http 127.0.0.1:8765 --http-path /mcp
 //! ```
 
 pub mod server;
+pub mod state;
 
 pub use server::QmdMcpServer;
@@ -1,8 +1,14 @@
-//! QMD MCP Server - Entry point with stdio transport.
+//! QMD MCP Server - Entry point with stdio or streamable HTTP transport.
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use clap::Parser;
 use rmcp::ServiceExt;
+use rmcp::transport::streamable_http_server::{
+    StreamableHttpServerConfig, StreamableHttpService, session::local::LocalSessionManager,
+};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
 use tracing_subscriber::{EnvFilter, fmt, prelude::*};
 
 use qmd_mcp::QmdMcpServer;
@@ -15,6 +21,18 @@ struct Args {
     /// Enable verbose logging.
     #[arg(short, long)]
     verbose: bool,
+
+    /// Unload models after this many seconds without a tool call.
+    #[arg(long, default_value = "300")]
+    idle_timeout: u64,
+
+    /// Serve streamable HTTP on this address (e.g. 127.0.0.1:8765) instead of stdio.
+    #[arg(long, value_name = "ADDR")]
+    http: Option<SocketAddr>,
+
+    /// URL path of the MCP endpoint when serving HTTP; `/` serves it at the root.
+    #[arg(long, default_value = "/mcp", requires = "http")]
+    http_path: String,
 }
 
 #[tokio::main]
@@ -34,8 +52,16 @@ async fn main() -> Result<()> {
         .init();
 
     // Create QMD MCP server
-    let server = QmdMcpServer::new();
+    let server = QmdMcpServer::with_idle_timeout(Duration::from_secs(args.idle_timeout));
 
+    match args.http {
+        Some(addr) => serve_http(server, addr, &args.http_path).await,
+        None => serve_stdio(server).await,
+    }
+}
+
+/// Serve a single client over stdio.
+async fn serve_stdio(server: QmdMcpServer) -> Result<()> {
     tracing::info!("Starting QMD MCP server with stdio transport");
 
     // Serve using stdio transport
@@ -46,3 +72,43 @@ async fn main() -> Result<()> {
 
     Ok(())
 }
+
+/// Serve any number of clients over streamable HTTP until Ctrl-C.
+///
+/// Every session gets its own handler, but all handlers share the same store
+/// and loaded models.
+async fn serve_http(server: QmdMcpServer, addr: SocketAddr, path: &str) -> Result<()> {
+    let endpoint = format!("/{}", path.trim_matches('/'));
+
+    let config = StreamableHttpServerConfig::default();
+    let cancellation = config.cancellation_token.clone();
+    let service = StreamableHttpService::new(
+        move || Ok(server.clone()),
+        Arc::new(LocalSessionManager::default()),
+        config,
+    );
+    // axum cannot nest a service at the root, so serve it as the fallback.
+    let router = if endpoint == "/" {
+        axum::Router::new().fallback_service(service)
+    } else {
+        axum::Router::new().nest_service(&endpoint, service)
+    };
+
+    let listener = tokio::net::TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("Failed to bind {addr}"))?;
+    let local_addr = listener.local_addr()?;
+    tracing::info!("Starting QMD MCP server on http://{local_addr}{endpoint}");
+
+    axum::serve(listener, router)
+        .with_graceful_shutdown(async move {
+            if let Err(e) = tokio::signal::ctrl_c().await {
+                tracing::warn!("Failed to listen for Ctrl-C: {e}");
+            }
+            tracing::info!("Shutting down");
+            cancellation.cancel();
+        })
+        .await?;
+
+    Ok(())
+}
@@ -2,43 +2,78 @@
 //!
 //! Uses `spawn_blocking` to run synchronous rusqlite operations in a
 //! dedicated thread pool, following the Rust community best practice.
+//! The store and models live in a shared [`ServerState`] and are reused
+//! across tool calls.
+//!
+//! Indexed documents are also exposed as MCP resources under their
+//! `qmd://collection/path` virtual paths.
+//!
+//! The search tools return their hits both as text and as structured content
+//! described by a declared output schema ([`SearchResults`]).
 
 use rmcp::{
-    ServerHandler,
-    handler::server::{tool::ToolRouter, wrapper::Parameters},
+    RoleServer, ServerHandler,
+    handler::server::{common::schema_for_type, tool::ToolRouter, wrapper::Parameters},
     model::{
-        CallToolResult, Content, Implementation, InitializeResult, ProtocolVersion,
+        AnnotateAble, CallToolResult, Content, Implementation, InitializeResult,
+        ListResourceTemplatesResult, ListResourcesResult, PaginatedRequestParams,
+        ProgressNotificationParam, ProgressToken, ProtocolVersion, RawResource,
+        RawResourceTemplate, ReadResourceRequestParams, ReadResourceResult, ResourceContents,
         ServerCapabilities,
     },
     schemars::JsonSchema,
+    service::{Peer, RequestContext},
     tool, tool_handler, tool_router,
 };
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::ops::ControlFlow;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::state::{DEFAULT_IDLE_TIMEOUT, ServerState};
 
 /// Type alias for ServerInfo (same as InitializeResult).
 type ServerInfo = InitializeResult;
 
 /// QMD MCP Server that provides search and document retrieval tools.
-#[derive(Clone, Default, Debug)]
+#[derive(Clone, Debug)]
 pub struct QmdMcpServer {
     /// Tool router for handling tool calls.
     tool_router: ToolRouter<Self>,
+    /// Store and models shared across tool calls.
+    state: Arc<ServerState>,
 }
 
 impl QmdMcpServer {
     /// Create a new QMD MCP server instance.
     #[must_use]
     pub fn new() -> Self {
+        Self::with_idle_timeout(DEFAULT_IDLE_TIMEOUT)
+    }
+
+    /// Create a server that unloads models after `idle_timeout` of inactivity.
+    #[must_use]
+    pub fn with_idle_timeout(idle_timeout: Duration) -> Self {
         Self {
             tool_router: Self::tool_router(),
+            state: ServerState::new(idle_timeout),
         }
     }
 }
 
+impl Default for QmdMcpServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// Parameters for search tool.
 #[derive(Debug, Clone, Deserialize, JsonSchema)]
 pub struct SearchParams {
-    /// Search query - keywords or phrases to find.
+    /// Search query - keywords or phrases to find. Supports inline filters:
+    /// collection:name, path:glob, after:YYYY-MM-DD, before:YYYY-MM-DD,
+    /// tag:name, or any frontmatter field such as status:draft.
     pub query: String,
     /// Maximum number of results (default: 10).
     #[serde(default = "default_limit")]
@@ -48,12 +83,17 @@ pub struct SearchParams {
     pub min_score: f64,
     /// Filter to a specific collection by name.
     pub collection: Option<String>,
+    /// Include a breakdown of how each score was computed (default: false).
+    #[serde(default)]
+    pub explain: bool,
 }
 
 /// Parameters for vsearch tool.
 #[derive(Debug, Clone, Deserialize, JsonSchema)]
 pub struct VsearchParams {
-    /// Natural language query - describe what you're looking for.
+    /// Natural language query - describe what you're looking for. Supports
+    /// inline filters: collection:name, path:glob, after:YYYY-MM-DD, before:YYYY-MM-DD,
+    /// tag:name, or any frontmatter field such as status:draft.
     pub query: String,
     /// Maximum number of results (default: 10).
     #[serde(default = "default_limit")]
@@ -63,24 +103,33 @@ pub struct VsearchParams {
     pub min_score: f64,
     /// Filter to a specific collection by name.
     pub collection: Option<String>,
+    /// Include a breakdown of how each score was computed (default: false).
+    #[serde(default)]
+    pub explain: bool,
 }
 
 /// Parameters for query tool (hybrid search).
 #[derive(Debug, Clone, Deserialize, JsonSchema)]
 pub struct QueryParams {
-    /// Natural language query - describe what you're looking for.
+    /// Natural language query - describe what you're looking for. Supports
+    /// inline filters: collection:name, path:glob, after:YYYY-MM-DD, before:YYYY-MM-DD,
+    /// tag:name, or any frontmatter field such as status:draft.
     pub query: String,
     /// Maximum number of results (default: 10).
     #[serde(default = "default_limit")]
     pub limit: usize,
     /// Filter to a specific collection by name.
     pub collection: Option<String>,
+    /// Include a breakdown of how each score was computed (default: false).
+    #[serde(default)]
+    pub explain: bool,
 }
 
 /// Parameters for get tool.
 #[derive(Debug, Clone, Deserialize, JsonSchema)]
 pub struct GetParams {
     /// File path or docid from search results (e.g., 'notes/meeting.md', '#abc123').
+    /// Append '#heading' to get only that section (e.g., 'notes/meeting.md#action-items').
     pub file: String,
     /// Start from this line number (1-indexed).
     pub from_line: Option<usize>,
@@ -112,10 +161,68 @@ pub struct LsParams {
     pub prefix: Option<String>,
 }
 
+/// Parameters for grep tool.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct GrepParams {
+    /// Regular expression to match against each line, or a literal string
+    /// with fixed_strings (e.g., 'fn parse_', 'v1\.2\.\d+').
+    pub pattern: String,
+    /// Filter to a specific collection by name.
+    pub collection: Option<String>,
+    /// Treat the pattern as a literal string (default: false).
+    #[serde(default)]
+    pub fixed_strings: bool,
+    /// Match case-insensitively (default: false).
+    #[serde(default)]
+    pub ignore_case: bool,
+    /// Maximum number of matching lines (default: 100).
+    #[serde(default = "default_grep_limit")]
+    pub limit: usize,
+}
+
+/// Parameters for tags tool.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct TagsParams {
+    /// Tag to list documents for (e.g., 'project' also matches 'project/alpha').
+    /// If empty, lists all tags with their document counts.
+    pub tag: Option<String>,
+    /// Filter to a specific collection by name.
+    pub collection: Option<String>,
+}
+
+/// Parameters for links tool.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct LinksParams {
+    /// File path or docid from search results (e.g., 'notes/meeting.md', '#abc123').
+    /// Required unless broken is set.
+    pub file: Option<String>,
+    /// Only list links that do not resolve to an indexed document (default: false).
+    #[serde(default)]
+    pub broken: bool,
+    /// With broken and no file, filter to a specific collection by name.
+    pub collection: Option<String>,
+}
+
+/// Parameters for outline tool.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct OutlineParams {
+    /// File path or docid from search results (e.g., 'notes/meeting.md', '#abc123').
+    pub file: String,
+}
+
+/// Parameters for backlinks tool.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct BacklinksParams {
+    /// File path or docid from search results (e.g., 'notes/meeting.md', '#abc123').
+    pub file: String,
+}
+
 /// Parameters for ask tool (RAG-based Q&A).
 #[derive(Debug, Clone, Deserialize, JsonSchema)]
 pub struct AskParams {
-    /// Natural language question to answer based on indexed documents.
+    /// Natural language question to answer based on indexed documents. Supports
+    /// inline filters: collection:name, path:glob, after:YYYY-MM-DD, before:YYYY-MM-DD,
+    /// tag:name, or any frontmatter field such as status:draft.
     pub question: String,
     /// Number of context documents to use (default: 5).
     #[serde(default = "default_context_limit")]
@@ -142,7 +249,9 @@ pub struct RerankParams {
 /// Parameters for qsearch tool (advanced hybrid search).
 #[derive(Debug, Clone, Deserialize, JsonSchema)]
 pub struct QsearchParams {
-    /// Search query - natural language question or keywords.
+    /// Search query - natural language question or keywords. Supports inline
+    /// filters: collection:name, path:glob, after:YYYY-MM-DD, before:YYYY-MM-DD,
+    /// tag:name, or any frontmatter field such as status:draft.
     pub query: String,
     /// Maximum number of results (default: 10).
     #[serde(default = "default_limit")]
@@ -155,6 +264,9 @@ pub struct QsearchParams {
     /// Skip reranking (default: false).
     #[serde(default)]
     pub no_rerank: bool,
+    /// Include a breakdown of how each score was computed (default: false).
+    #[serde(default)]
+    pub explain: bool,
 }
 
 /// Parameters for collection_add tool.
@@ -262,6 +374,9 @@ fn default_model_all() -> String {
 fn default_limit() -> usize {
     10
 }
+fn default_grep_limit() -> usize {
+    100
+}
 fn default_true() -> bool {
     true
 }
@@ -281,14 +396,320 @@ fn default_rerank_limit() -> usize {
     10
 }
 
-/// Search result item for JSON output.
-#[derive(Debug, Serialize)]
-struct SearchResultItem {
-    docid: String,
-    file: String,
-    title: String,
-    score: f64,
-    context: Option<String>,
+/// Maximum snippet length in structured search results.
+const SNIPPET_MAX_CHARS: usize = 300;
+
+/// Structured content of the search tools.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct SearchResults {
+    /// Matching documents, best first.
+    pub results: Vec<SearchResultItem>,
+}
+
+/// A single search hit.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct SearchResultItem {
+    /// Short document id, e.g. `#abc123`.
+    pub docid: String,
+    /// Virtual path, e.g. `qmd://collection/path.md`.
+    pub uri: String,
+    /// Display path, e.g. `collection/path.md`.
+    pub file: String,
+    /// Document title.
+    pub title: String,
+    /// Relevance score; higher is better.
+    pub score: f64,
+    /// Which retrieval method produced the score.
+    pub source: ScoreSource,
+    /// Folder context description if configured.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context: Option<String>,
+    /// Frontmatter fields; lists stay lists.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub meta: BTreeMap<String, serde_json::Value>,
+    /// Most relevant excerpt of the document.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snippet: Option<Snippet>,
+    /// Windows around the query's keyword matches, best lines first in
+    /// document order.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub matches: Vec<MatchSnippet>,
+    /// Best-matching chunk, for vector and hybrid results.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chunk: Option<ChunkPosition>,
+    /// How the score was computed, when requested with `explain`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub explain: Option<ScoreExplanation>,
+}
+
+/// Breakdown of a search score.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ScoreExplanation {
+    /// Raw BM25 score of a keyword search result.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bm25: Option<f64>,
+    /// Vector similarity of a semantic search result.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub similarity: Option<f64>,
+    /// RRF contribution of each query variant's result list.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub lists: Vec<ListScore>,
+    /// Position bonus added to the RRF score.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub position_bonus: Option<f64>,
+    /// Fused RRF score, including the position bonus.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rrf_score: Option<f64>,
+    /// Rank (1-indexed) in the fused list before reranking.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fused_rank: Option<usize>,
+    /// Reranker relevance score.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rerank_score: Option<f64>,
+    /// Line where the passage that decided the rerank score starts.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub passage_line: Option<usize>,
+    /// Final score after blending.
+    pub final_score: f64,
+    /// The same breakdown as human-readable lines.
+    pub lines: Vec<String>,
+}
+
+/// RRF contribution of one query variant.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ListScore {
+    /// Query type: `lex`, `vec` or `hyde`.
+    #[serde(rename = "type")]
+    pub query_type: String,
+    /// Query variant text.
+    pub query: String,
+    /// Rank (1-indexed) of the document in the list.
+    pub rank: usize,
+    /// Raw score in the list (BM25 or similarity).
+    pub score: f64,
+    /// RRF weight of the list.
+    pub weight: f64,
+    /// Amount added to the RRF score.
+    pub contribution: f64,
+}
+
+impl ScoreExplanation {
+    /// Explain the score of a search result.
+    fn from_result(result: &qmd::SearchResult) -> Self {
+        let provenance = result.provenance.as_ref();
+        Self {
+            bm25: (provenance.is_none() && result.source == qmd::SearchSource::Fts)
+                .then_some(result.score),
+            similarity: (provenance.is_none() && result.source == qmd::SearchSource::Vec)
+                .then_some(result.score),
+            lists: provenance
+                .map(|p| {
+                    p.lists
+                        .iter()
+                        .map(|list| ListScore {
+                            query_type: list.query_type.as_str().to_string(),
+                            query: list.query.clone(),
+                            rank: list.rank + 1,
+                            score: list.score,
+                            weight: list.weight,
+                            contribution: list.contribution,
+                        })
+                        .collect()
+                })
+                .unwrap_or_default(),
+            position_bonus: provenance.map(|p| p.position_bonus),
+            rrf_score: provenance.map(|p| p.rrf_score),
+            fused_rank: provenance.and_then(|p| p.fused_rank).map(|rank| rank + 1),
+            rerank_score: provenance.and_then(|p| p.rerank_score),
+            passage_line: result.passage.as_ref().map(|p| p.line),
+            final_score: result.score,
+            lines: qmd::explain_score(result),
+        }
+    }
+}
+
+/// Retrieval method behind a score.
+#[derive(Debug, Clone, Copy, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ScoreSource {
+    /// BM25 full-text search.
+    Fts,
+    /// Vector similarity search.
+    Vec,
+    /// Fused lexical and vector search.
+    Hybrid,
+}
+
+impl From<qmd::SearchSource> for ScoreSource {
+    fn from(source: qmd::SearchSource) -> Self {
+        match source {
+            qmd::SearchSource::Fts => Self::Fts,
+            qmd::SearchSource::Vec => Self::Vec,
+            qmd::SearchSource::Hybrid => Self::Hybrid,
+        }
+    }
+}
+
+/// Excerpt of a document.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct Snippet {
+    /// Line number (1-indexed) where the excerpt starts.
+    pub line: usize,
+    /// Excerpt text.
+    pub text: String,
+    /// Nearest heading enclosing the excerpt.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub heading: Option<String>,
+    /// The heading's section, for 'get' (e.g., 'notes/plan.md#goals').
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub section: Option<String>,
+}
+
+/// Window of a document around keyword matches.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct MatchSnippet {
+    /// Line number (1-indexed) of the window.
+    pub line: usize,
+    /// Window text.
+    pub text: String,
+    /// Byte ranges `[start, end)` of the matches within `text`.
+    pub highlights: Vec<[usize; 2]>,
+}
+
+impl From<qmd::Snippet> for MatchSnippet {
+    fn from(snippet: qmd::Snippet) -> Self {
+        Self {
+            highlights: snippet
+                .highlights
+                .iter()
+                .map(|h| [h.start, h.end])
+                .collect(),
+            line: snippet.line,
+            text: snippet.text,
+        }
+    }
+}
+
+impl MatchSnippet {
+    /// Window text with the matches marked in bold.
+    fn marked(&self) -> String {
+        let mut out = String::with_capacity(self.text.len() + self.highlights.len() * 4);
+        let mut pos = 0;
+        for &[start, end] in &self.highlights {
+            out.push_str(&self.text[pos..start]);
+            out.push_str("**");
+            out.push_str(&self.text[start..end]);
+            out.push_str("**");
+            pos = end;
+        }
+        out.push_str(&self.text[pos..]);
+        out
+    }
+}
+
+/// Position of a chunk within its document.
+#[derive(Debug, Clone, Copy, Serialize, JsonSchema)]
+pub struct ChunkPosition {
+    /// Chunk sequence number (0-indexed).
+    pub seq: usize,
+    /// Byte offset of the chunk in the document.
+    pub pos: usize,
+}
+
+impl SearchResultItem {
+    /// Build an item from a search result, loading the body for the snippet.
+    fn from_result(
+        store: &qmd::Store,
+        result: qmd::SearchResult,
+        query: &str,
+        explain: bool,
+    ) -> Self {
+        let explanation = explain.then(|| ScoreExplanation::from_result(&result));
+        let body = result
+            .doc
+            .body
+            .or_else(|| store.get_content(&result.doc.hash).ok().flatten());
+        // Prefer the passage the reranker chose over the vector chunk.
+        let pos = result.passage.as_ref().map(|p| p.pos).or(result.chunk_pos);
+        let snippet = body.map(|text| {
+            let extracted = qmd::extract_snippet(&text, query, SNIPPET_MAX_CHARS, pos);
+            let heading = store
+                .get_enclosing_heading(
+                    &result.doc.collection_name,
+                    &result.doc.path,
+                    extracted.line,
+                )
+                .ok()
+                .flatten();
+            Snippet {
+                line: extracted.line,
+                text: extracted.snippet,
+                section: heading
+                    .as_ref()
+                    .map(|h| format!("{}#{}", result.doc.display_path, h.slug)),
+                heading: heading.map(|h| h.text),
+            }
+        });
+        let chunk = result
+            .chunk_seq
+            .zip(result.chunk_pos)
+            .map(|(seq, pos)| ChunkPosition { seq, pos });
+
+        Self {
+            docid: format!("#{}", result.doc.docid),
+            uri: result.doc.filepath,
+            file: result.doc.display_path,
+            title: result.doc.title,
+            score: result.score,
+            source: result.source.into(),
+            context: result.doc.context,
+            meta: result
+                .doc
+                .meta
+                .into_iter()
+                .map(|(key, value)| (key, serde_json::json!(value)))
+                .collect(),
+            snippet,
+            matches: result
+                .snippets
+                .into_iter()
+                .map(MatchSnippet::from)
+                .collect(),
+            chunk,
+            explain: explanation,
+        }
+    }
+
+    /// Match windows to append to the item's text summary line.
+    fn matches_suffix(&self) -> String {
+        let mut suffix = String::new();
+        for m in &self.matches {
+            suffix.push_str("\n    L");
+            suffix.push_str(&m.line.to_string());
+            suffix.push_str(": ");
+            suffix.push_str(&m.marked());
+        }
+        suffix
+    }
+
+    /// Explanation lines to append to the item's text summary line.
+    fn explain_suffix(&self) -> String {
+        self.explain
+            .as_ref()
+            .map(|e| format!("\n    {}", e.lines.join("\n    ")))
+            .unwrap_or_default()
+    }
+}
+
+/// Build a search tool result with a text summary and structured content.
+fn search_tool_result(
+    results: Vec<SearchResultItem>,
+    summary: String,
+) -> Result<CallToolResult, rmcp::ErrorData> {
+    let structured = serde_json::to_value(SearchResults { results }).map_err(to_mcp_error)?;
+    let mut result = CallToolResult::success(vec![Content::text(summary)]);
+    result.structured_content = Some(structured);
+    Ok(result)
 }
 
 /// Status result for JSON output.
@@ -298,6 +719,7 @@ struct StatusResult {
     needs_embedding: usize,
     has_vector_index: bool,
     collections: Vec<CollectionStatus>,
+    schema_version: u32,
 }
 
 /// Collection status for JSON output.
@@ -308,11 +730,138 @@ struct CollectionStatus {
     documents: usize,
 }
 
+/// Number of resources returned per `resources/list` page.
+const RESOURCE_PAGE_SIZE: usize = 200;
+
+/// MIME type of indexed documents.
+const MARKDOWN_MIME: &str = "text/markdown";
+
+/// Minimum time between two progress notifications.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Reports progress to the client from a blocking task and polls for
+/// cancellation.
+///
+/// Notifications are only sent when the client supplied a progress token and
+/// are throttled to [`PROGRESS_INTERVAL`].
+struct ProgressReporter {
+    /// Runtime used to send notifications from the blocking thread.
+    runtime: tokio::runtime::Handle,
+    /// Client connection.
+    peer: Peer<RoleServer>,
+    /// Progress token from the request, if any.
+    token: Option<ProgressToken>,
+    /// Whether the client cancelled the request.
+    cancelled: Box<dyn Fn() -> bool + Send>,
+    /// When the last notification was sent.
+    last_sent: Option<Instant>,
+}
+
+impl ProgressReporter {
+    /// Create a reporter for a request. Must be called on the runtime.
+    fn new(context: &RequestContext<RoleServer>) -> Self {
+        let ct = context.ct.clone();
+        Self {
+            runtime: tokio::runtime::Handle::current(),
+            peer: context.peer.clone(),
+            token: context.meta.get_progress_token(),
+            cancelled: Box::new(move || ct.is_cancelled()),
+            last_sent: None,
+        }
+    }
+
+    /// Report progress, returning [`ControlFlow::Break`] once the client has
+    /// cancelled. `force` bypasses throttling.
+    fn report(
+        &mut self,
+        progress: f64,
+        total: Option<f64>,
+        message: impl FnOnce() -> String,
+        force: bool,
+    ) -> ControlFlow<()> {
+        if (self.cancelled)() {
+            return ControlFlow::Break(());
+        }
+        let Some(token) = self.token.clone() else {
+            return ControlFlow::Continue(());
+        };
+        if !force
+            && self
+                .last_sent
+                .is_some_and(|t| t.elapsed() < PROGRESS_INTERVAL)
+        {
+            return ControlFlow::Continue(());
+        }
+        self.last_sent = Some(Instant::now());
+        let param = ProgressNotificationParam {
+            progress_token: token,
+            progress,
+            total,
+            message: Some(message()),
+        };
+        if let Err(e) = self.runtime.block_on(self.peer.notify_progress(param)) {
+            tracing::debug!("Failed to send progress notification: {e}");
+        }
+        ControlFlow::Continue(())
+    }
+}
+
 /// Convert qmd error to MCP error.
 fn to_mcp_error(e: impl std::fmt::Display) -> rmcp::ErrorData {
     rmcp::ErrorData::internal_error(e.to_string(), None)
 }
 
+/// Split inline filters out of a query and add the `collection` parameter.
+fn split_filters(
+    store: &qmd::Store,
+    query: &str,
+    collection: Option<&str>,
+) -> Result<(qmd::SearchFilter, String), qmd::QmdError> {
+    let (filter, text) = qmd::SearchFilter::parse_with_fields(query, &store.get_metadata_keys()?)?;
+    if text.is_empty() {
+        return Err(qmd::QmdError::InvalidQuery(
+            "query has no search terms besides filters".to_string(),
+        ));
+    }
+    Ok((filter.with_collection(collection), text))
+}
+
+/// Run a hybrid query on the blocking pool.
+///
+/// Inline filters are split out of `query` and the BM25 weights read from
+/// the config there too, since both touch the store or the disk. Any
+/// `filter` and `fts` in `options` are replaced.
+async fn run_hybrid(
+    state: Arc<ServerState>,
+    query: String,
+    collection: Option<String>,
+    options: qmd::HybridOptions,
+    explain: bool,
+) -> Result<Vec<SearchResultItem>, rmcp::ErrorData> {
+    tokio::task::spawn_blocking(move || -> Result<Vec<SearchResultItem>, qmd::QmdError> {
+        let store = state.model_reader()?;
+        let (filter, text) = split_filters(&store, &query, collection.as_deref())?;
+        let search_options = qmd::HybridOptions {
+            filter,
+            fts: qmd::FtsSearchOptions::from_config()?,
+            ..options
+        };
+        let mut engines = state.engines();
+        engines.ensure(&search_options);
+        let mut results = qmd::HybridSearcher::new(&store)
+            .with_engines(&mut engines)
+            .search(&text, &search_options)?;
+        store.add_snippets(&text, &mut results)?;
+        Ok(results
+            .into_iter()
+            .map(|r| SearchResultItem::from_result(&store, r, &text, explain))
+            .collect())
+    })
+    .await
+    .map_err(to_mcp_error)?
+    .map_err(to_mcp_error)
+}
+
 /// Add line numbers to text.
 fn add_line_numbers(text: &str, start: usize) -> String {
     text.lines()
@@ -326,7 +875,10 @@ fn add_line_numbers(text: &str, start: usize) -> String {
 impl QmdMcpServer {
     /// Fast keyword-based full-text search using BM25.
     /// Best for finding documents with specific words or phrases.
-    #[tool(name = "search")]
+    #[tool(
+        name = "search",
+        output_schema = schema_for_type::<SearchResults>()
+    )]
     async fn search(
         &self,
         params: Parameters<SearchParams>,
@@ -334,21 +886,19 @@ async fn search(
         let p = params.0;
 
         // Run synchronous database operation in blocking thread pool
+        let state = Arc::clone(&self.state);
         let result =
             tokio::task::spawn_blocking(move || -> Result<Vec<SearchResultItem>, qmd::QmdError> {
-                let store = qmd::Store::new()?;
-                let results = store.search_fts(&p.query, p.limit, p.collection.as_deref())?;
+                let store = state.reader()?;
+                let (filter, query) = split_filters(&store, &p.query, p.collection.as_deref())?;
+                let fts = qmd::FtsSearchOptions::from_config()?;
+                let mut results = store.search_fts_with(&query, p.limit, &filter, &fts)?;
+                store.add_snippets(&query, &mut results)?;
 
                 Ok(results
                     .into_iter()
                     .filter(|r| r.score >= p.min_score)
-                    .map(|r| SearchResultItem {
-                        docid: format!("#{}", r.doc.docid),
-                        file: r.doc.display_path,
-                        title: r.doc.title,
-                        score: (r.score * 100.0).round() / 100.0,
-                        context: r.doc.context,
-                    })
+                    .map(|r| SearchResultItem::from_result(&store, r, &query, p.explain))
                     .collect())
             })
             .await
@@ -362,55 +912,63 @@ async fn search(
                 .iter()
                 .map(|r| {
                     format!(
-                        "{} {}% {} - {}",
+                        "{} {:.0}% {} - {}{}{}",
                         r.docid,
-                        (r.score * 100.0) as i32,
+                        r.score * 100.0,
                         r.file,
-                        r.title
+                        r.title,
+                        r.matches_suffix(),
+                        r.explain_suffix()
                     )
                 })
                 .collect::<Vec<_>>()
                 .join("\n")
         };
 
-        Ok(CallToolResult::success(vec![Content::text(summary)]))
+        search_tool_result(result, summary)
     }
 
-    /// Retrieve the full content of a document by its file path or docid (#abc123).
+    /// Retrieve the full content of a document by its file path or docid (#abc123),
+    /// or one section of it with 'file#heading'.
     #[tool(name = "get")]
     async fn get(&self, params: Parameters<GetParams>) -> Result<CallToolResult, rmcp::ErrorData> {
         let p = params.0;
         let file_for_err = p.file.clone();
 
+        let state = Arc::clone(&self.state);
         let result = tokio::task::spawn_blocking(
             move || -> Result<Option<(String, String, Option<String>)>, qmd::QmdError> {
-                let store = qmd::Store::new()?;
+                let store = state.reader()?;
 
-                // Check if it's a docid
-                let (collection, path) = if p.file.starts_with('#') {
-                    match store.find_document_by_docid(&p.file)? {
-                        Some(cp) => cp,
-                        None => return Ok(None),
-                    }
-                } else {
-                    // Parse collection/path format
-                    let parts: Vec<&str> = p.file.splitn(2, '/').collect();
-                    if parts.len() == 2 {
-                        (parts[0].to_string(), parts[1].to_string())
-                    } else {
-                        return Ok(None);
+                // A docid starts with '#'; any later '#' names a section.
+                let (file, fragment) = match p.file.rsplit_once('#') {
+                    Some((doc_path, section)) if !doc_path.is_empty() && !section.is_empty() => {
+                        (doc_path, Some(section))
                     }
+                    _ => (p.file.as_str(), None),
+                };
+                let Some((collection, path)) = store.locate_document(file)? else {
+                    return Ok(None);
                 };
+                let (mut from_line, mut max_lines) = (p.from_line, p.max_lines);
+                if let Some(name) = fragment {
+                    let Some(heading) = store.find_section(&collection, &path, name)? else {
+                        return Ok(None);
+                    };
+                    let section_lines = heading.end_line - heading.line + 1;
+                    from_line = Some(heading.line);
+                    max_lines = Some(max_lines.map_or(section_lines, |n| n.min(section_lines)));
+                }
 
                 match store.get_document(&collection, &path)? {
                     Some(doc) => {
                         let mut body = doc.body.unwrap_or_default();
 
                         // Apply line range
-                        if let Some(from) = p.from_line {
+                        if let Some(from) = from_line {
                             let lines: Vec<&str> = body.lines().collect();
                             let start = from.saturating_sub(1);
-                            let end = p.max_lines.map(|m| start + m).unwrap_or(lines.len());
+                            let end = max_lines.map(|m| start + m).unwrap_or(lines.len());
                             body = lines
                                 .get(start..end.min(lines.len()))
                                 .map(|s| s.join("\n"))
@@ -419,7 +977,7 @@ async fn get(&self, params: Parameters<GetParams>) -> Result<CallToolResult, rmc
 
                         // Add line numbers
                         if p.line_numbers {
-                            body = add_line_numbers(&body, p.from_line.unwrap_or(1));
+                            body = add_line_numbers(&body, from_line.unwrap_or(1));
                         }
 
                         Ok(Some((doc.title, body, doc.context)))
@@ -451,8 +1009,9 @@ async fn get(&self, params: Parameters<GetParams>) -> Result<CallToolResult, rmc
     /// Show the status of the QMD index: collections, document counts, and health information.
     #[tool(name = "status")]
     async fn status(&self) -> Result<CallToolResult, rmcp::ErrorData> {
-        let result = tokio::task::spawn_blocking(|| -> Result<StatusResult, qmd::QmdError> {
-            let store = qmd::Store::new()?;
+        let state = Arc::clone(&self.state);
+        let result = tokio::task::spawn_blocking(move || -> Result<StatusResult, qmd::QmdError> {
+            let store = state.reader()?;
             let status = store.get_status()?;
 
             Ok(StatusResult {
@@ -468,6 +1027,7 @@ async fn status(&self) -> Result<CallToolResult, rmcp::ErrorData> {
                         documents: c.active_count,
                     })
                     .collect(),
+                schema_version: status.schema_version,
             })
         })
         .await
@@ -483,6 +1043,7 @@ async fn status(&self) -> Result<CallToolResult, rmcp::ErrorData> {
                 if result.has_vector_index { "yes" } else { "no" }
             ),
             format!("  Collections: {}", result.collections.len()),
+            format!("  Schema version: {}", result.schema_version),
         ];
         for col in &result.collections {
             lines.push(format!("    - {} ({} docs)", col.name, col.documents));
@@ -496,38 +1057,47 @@ async fn status(&self) -> Result<CallToolResult, rmcp::ErrorData> {
     /// Semantic similarity search using vector embeddings.
     /// Finds conceptually related content even without exact keyword matches.
     /// Requires embeddings to be generated first (run 'qmd embed').
-    #[tool(name = "vsearch")]
+    #[tool(
+        name = "vsearch",
+        output_schema = schema_for_type::<SearchResults>()
+    )]
     async fn vsearch(
         &self,
         params: Parameters<VsearchParams>,
     ) -> Result<CallToolResult, rmcp::ErrorData> {
         let p = params.0;
 
+        let state = Arc::clone(&self.state);
         let result =
             tokio::task::spawn_blocking(move || -> Result<Vec<SearchResultItem>, String> {
-                let store = qmd::Store::new().map_err(|e| e.to_string())?;
+                let store = state.model_reader().map_err(|e| e.to_string())?;
+                let (filter, query) = split_filters(&store, &p.query, p.collection.as_deref())
+                    .map_err(|e| e.to_string())?;
 
                 // Load embedding engine
-                let mut engine = qmd::EmbeddingEngine::load_default().map_err(|e| e.to_string())?;
+                let mut engines = state.engines();
+                let engine = engines.embedder().map_err(|e| e.to_string())?;
 
                 // Embed query
-                let query_emb = engine.embed_query(&p.query).map_err(|e| e.to_string())?;
+                let query_emb = engine.embed_query(&query).map_err(|e| e.to_string())?;
 
                 // Vector search
-                let results = store
-                    .search_vec(&query_emb.embedding, p.limit, p.collection.as_deref())
+                let mut results = store
+                    .search_vec_with(
+                        &query_emb.embedding,
+                        p.limit,
+                        &filter,
+                        &qmd::VecSearchOptions::default(),
+                    )
+                    .map_err(|e| e.to_string())?;
+                store
+                    .add_snippets(&query, &mut results)
                     .map_err(|e| e.to_string())?;
 
                 Ok(results
                     .into_iter()
                     .filter(|r| r.score >= p.min_score)
-                    .map(|r| SearchResultItem {
-                        docid: format!("#{}", r.doc.docid),
-                        file: r.doc.display_path,
-                        title: r.doc.title,
-                        score: (r.score * 100.0).round() / 100.0,
-                        context: r.doc.context,
-                    })
+                    .map(|r| SearchResultItem::from_result(&store, r, &query, p.explain))
                     .collect())
             })
             .await
@@ -541,128 +1111,70 @@ async fn vsearch(
                 .iter()
                 .map(|r| {
                     format!(
-                        "{} {:.0}% {} - {}",
+                        "{} {:.0}% {} - {}{}{}",
                         r.docid,
                         r.score * 100.0,
                         r.file,
-                        r.title
+                        r.title,
+                        r.matches_suffix(),
+                        r.explain_suffix()
                     )
                 })
                 .collect::<Vec<_>>()
                 .join("\n")
         };
 
-        Ok(CallToolResult::success(vec![Content::text(summary)]))
+        search_tool_result(result, summary)
     }
 
     /// Hybrid search combining BM25 + vector search with RRF fusion.
     /// Best quality results but requires embeddings.
-    #[tool(name = "query")]
+    #[tool(
+        name = "query",
+        output_schema = schema_for_type::<SearchResults>()
+    )]
     async fn query(
         &self,
         params: Parameters<QueryParams>,
     ) -> Result<CallToolResult, rmcp::ErrorData> {
         let p = params.0;
 
-        let result =
-            tokio::task::spawn_blocking(move || -> Result<Vec<SearchResultItem>, String> {
-                let store = qmd::Store::new().map_err(|e| e.to_string())?;
-
-                // FTS search
-                let fts_results = store
-                    .search_fts(&p.query, p.limit * 2, p.collection.as_deref())
-                    .map_err(|e| e.to_string())?;
-
-                let fts_tuples: Vec<(String, String, String, String)> = fts_results
-                    .iter()
-                    .map(|r| {
-                        (
-                            r.doc.display_path.clone(),
-                            r.doc.display_path.clone(),
-                            r.doc.title.clone(),
-                            String::new(),
-                        )
-                    })
-                    .collect();
-
-                // Try vector search (may fail if no embeddings)
-                let vec_tuples: Vec<(String, String, String, String)> =
-                    match qmd::EmbeddingEngine::load_default() {
-                        Ok(mut engine) => match engine.embed_query(&p.query) {
-                            Ok(query_emb) => {
-                                match store.search_vec(
-                                    &query_emb.embedding,
-                                    p.limit * 2,
-                                    p.collection.as_deref(),
-                                ) {
-                                    Ok(vec_results) => vec_results
-                                        .iter()
-                                        .map(|r| {
-                                            (
-                                                r.doc.display_path.clone(),
-                                                r.doc.display_path.clone(),
-                                                r.doc.title.clone(),
-                                                String::new(),
-                                            )
-                                        })
-                                        .collect(),
-                                    Err(_) => Vec::new(),
-                                }
-                            }
-                            Err(_) => Vec::new(),
-                        },
-                        Err(_) => Vec::new(),
-                    };
-
-                // RRF fusion
-                let rrf_results = qmd::hybrid_search_rrf(fts_tuples, vec_tuples, 60);
-
-                // Convert to SearchResultItem
-                let items: Vec<SearchResultItem> = rrf_results
-                    .into_iter()
-                    .take(p.limit)
-                    .map(|r| SearchResultItem {
-                        docid: String::new(), // Will be filled below
-                        file: r.display_path,
-                        title: r.title,
-                        score: r.score,
-                        context: None,
-                    })
-                    .collect();
-
-                // Enrich with docids
-                let enriched: Vec<SearchResultItem> = items
-                    .into_iter()
-                    .filter_map(|mut item| {
-                        let parts: Vec<&str> = item.file.splitn(2, '/').collect();
-                        if parts.len() == 2 {
-                            if let Ok(Some(doc)) = store.get_document(parts[0], parts[1]) {
-                                item.docid = format!("#{}", doc.docid);
-                                item.context = doc.context;
-                                return Some(item);
-                            }
-                        }
-                        None
-                    })
-                    .collect();
-
-                Ok(enriched)
-            })
-            .await
-            .map_err(|e| to_mcp_error(e))?
-            .map_err(|e| to_mcp_error(e))?;
+        let options = qmd::HybridOptions {
+            limit: p.limit,
+            expand: false,
+            rerank: false,
+            ..qmd::HybridOptions::default()
+        };
+        let result = run_hybrid(
+            Arc::clone(&self.state),
+            p.query,
+            p.collection,
+            options,
+            p.explain,
+        )
+        .await?;
 
         let summary = if result.is_empty() {
             "No results found".to_string()
         } else {
             result
                 .iter()
-                .map(|r| format!("{} {:.2} {} - {}", r.docid, r.score, r.file, r.title))
+                .map(|r| {
+                    format!(
+                        "{} {:.2} {} - {}{}{}",
+                        r.docid,
+                        r.score,
+                        r.file,
+                        r.title,
+                        r.matches_suffix(),
+                        r.explain_suffix()
+                    )
+                })
                 .collect::<Vec<_>>()
                 .join("\n")
         };
 
-        Ok(CallToolResult::success(vec![Content::text(summary)]))
+        search_tool_result(result, summary)
     }
 
     /// Retrieve multiple documents by comma-separated file paths or docids.
@@ -673,8 +1185,9 @@ async fn multi_get(
     ) -> Result<CallToolResult, rmcp::ErrorData> {
         let p = params.0;
 
+        let state = Arc::clone(&self.state);
         let result = tokio::task::spawn_blocking(move || -> Result<String, qmd::QmdError> {
-            let store = qmd::Store::new()?;
+            let store = state.reader()?;
             let files: Vec<&str> = p.files.split(',').map(str::trim).collect();
             let mut output = Vec::new();
 
@@ -744,8 +1257,9 @@ async fn multi_get(
     async fn ls(&self, params: Parameters<LsParams>) -> Result<CallToolResult, rmcp::ErrorData> {
         let p = params.0;
 
+        let state = Arc::clone(&self.state);
         let result = tokio::task::spawn_blocking(move || -> Result<String, qmd::QmdError> {
-            let store = qmd::Store::new()?;
+            let store = state.reader()?;
 
             match p.collection {
                 Some(coll) => {
@@ -789,29 +1303,246 @@ async fn ls(&self, params: Parameters<LsParams>) -> Result<CallToolResult, rmcp:
         Ok(CallToolResult::success(vec![Content::text(result)]))
     }
 
+    /// Find lines matching a regex or literal string, like grep.
+    /// Best for identifiers, partial words, version strings and code fragments.
+    /// Returns one `collection/path:line: text` line per match.
+    #[tool(name = "grep")]
+    async fn grep(
+        &self,
+        params: Parameters<GrepParams>,
+    ) -> Result<CallToolResult, rmcp::ErrorData> {
+        let p = params.0;
+
+        let state = Arc::clone(&self.state);
+        let result = tokio::task::spawn_blocking(move || -> Result<String, qmd::QmdError> {
+            let store = state.reader()?;
+            let options = qmd::GrepOptions {
+                fixed_strings: p.fixed_strings,
+                ignore_case: p.ignore_case,
+                limit: p.limit,
+            };
+            let matches = store.grep(
+                &p.pattern,
+                &qmd::SearchFilter::collection(p.collection.as_deref()),
+                &options,
+            )?;
+            if matches.is_empty() {
+                return Ok("No matches found".to_string());
+            }
+            Ok(matches
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join("\n"))
+        })
+        .await
+        .map_err(|e| to_mcp_error(e))?
+        .map_err(to_mcp_error)?;
+
+        Ok(CallToolResult::success(vec![Content::text(result)]))
+    }
+
+    /// List tags from frontmatter and inline #tags with their document counts
+    /// per collection, or the documents with a given tag.
+    #[tool(name = "tags")]
+    async fn tags(
+        &self,
+        params: Parameters<TagsParams>,
+    ) -> Result<CallToolResult, rmcp::ErrorData> {
+        let p = params.0;
+
+        let state = Arc::clone(&self.state);
+        let result = tokio::task::spawn_blocking(move || -> Result<String, qmd::QmdError> {
+            let store = state.reader()?;
+
+            if let Some(tag) = p.tag {
+                let docs = store.get_tagged_documents(&tag, p.collection.as_deref())?;
+                if docs.is_empty() {
+                    return Ok(format!("No documents tagged '{tag}'"));
+                }
+                return Ok(docs
+                    .iter()
+                    .map(|d| format!("{} - {}", d.display_path, d.title))
+                    .collect::<Vec<_>>()
+                    .join("\n"));
+            }
+
+            let tags = store.list_tags(p.collection.as_deref())?;
+            if tags.is_empty() {
+                return Ok("No tags found".to_string());
+            }
+            Ok(tags
+                .iter()
+                .map(|t| format!("{}: #{} ({})", t.collection, t.tag, t.count))
+                .collect::<Vec<_>>()
+                .join("\n"))
+        })
+        .await
+        .map_err(|e| to_mcp_error(e))?
+        .map_err(to_mcp_error)?;
+
+        Ok(CallToolResult::success(vec![Content::text(result)]))
+    }
+
+    /// List the links of a document to other notes, from [text](file.md) and [[wiki]] links.
+    /// Returns `source:line: target -> collection/path` per link; pass the linked path to
+    /// 'get' to follow it. With broken, lists links that do not resolve instead.
+    #[tool(name = "links")]
+    async fn links(
+        &self,
+        params: Parameters<LinksParams>,
+    ) -> Result<CallToolResult, rmcp::ErrorData> {
+        let p = params.0;
+
+        let state = Arc::clone(&self.state);
+        let result = tokio::task::spawn_blocking(move || -> Result<String, qmd::QmdError> {
+            let store = state.reader()?;
+
+            let links = match &p.file {
+                Some(file) => {
+                    let Some((collection, path)) = store.locate_document(file)? else {
+                        return Ok(format!("Document not found: {file}"));
+                    };
+                    let mut links = store.get_links(&collection, &path)?;
+                    if p.broken {
+                        links.retain(|link| link.resolved.is_none());
+                    }
+                    links
+                }
+                None if p.broken => store.get_broken_links(p.collection.as_deref())?,
+                None => {
+                    return Err(qmd::QmdError::InvalidQuery(
+                        "file is required unless broken is set".to_string(),
+                    ));
+                }
+            };
+            if links.is_empty() {
+                return Ok(if p.broken {
+                    "No broken links".to_string()
+                } else {
+                    "No links found".to_string()
+                });
+            }
+            Ok(links
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join("\n"))
+        })
+        .await
+        .map_err(|e| to_mcp_error(e))?
+        .map_err(to_mcp_error)?;
+
+        Ok(CallToolResult::success(vec![Content::text(result)]))
+    }
+
+    /// Show the heading outline of a document: one line per heading with its level,
+    /// '#slug' and line range. Pass 'file#slug' to 'get' to read just that section.
+    #[tool(name = "outline")]
+    async fn outline(
+        &self,
+        params: Parameters<OutlineParams>,
+    ) -> Result<CallToolResult, rmcp::ErrorData> {
+        let p = params.0;
+
+        let state = Arc::clone(&self.state);
+        let result = tokio::task::spawn_blocking(move || -> Result<String, qmd::QmdError> {
+            let store = state.reader()?;
+            let Some((collection, path)) = store.locate_document(&p.file)? else {
+                return Ok(format!("Document not found: {}", p.file));
+            };
+            let headings = store.get_outline(&collection, &path)?;
+            if headings.is_empty() {
+                return Ok("No headings found".to_string());
+            }
+            Ok(headings
+                .iter()
+                .map(|h| {
+                    format!(
+                        "{}{} {} (#{}, lines {}-{})",
+                        "  ".repeat(h.level - 1),
+                        "#".repeat(h.level),
+                        h.text,
+                        h.slug,
+                        h.line,
+                        h.end_line
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n"))
+        })
+        .await
+        .map_err(|e| to_mcp_error(e))?
+        .map_err(to_mcp_error)?;
+
+        Ok(CallToolResult::success(vec![Content::text(result)]))
+    }
+
+    /// List the documents linking to a document, one `source:line: target -> document`
+    /// line per link. Pass a source path to 'get' to read the linking note.
+    #[tool(name = "backlinks")]
+    async fn backlinks(
+        &self,
+        params: Parameters<BacklinksParams>,
+    ) -> Result<CallToolResult, rmcp::ErrorData> {
+        let p = params.0;
+
+        let state = Arc::clone(&self.state);
+        let result = tokio::task::spawn_blocking(move || -> Result<String, qmd::QmdError> {
+            let store = state.reader()?;
+            let Some((collection, path)) = store.locate_document(&p.file)? else {
+                return Ok(format!("Document not found: {}", p.file));
+            };
+            let links = store.get_backlinks(&collection, &path)?;
+            if links.is_empty() {
+                return Ok("No backlinks found".to_string());
+            }
+            Ok(links
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join("\n"))
+        })
+        .await
+        .map_err(|e| to_mcp_error(e))?
+        .map_err(to_mcp_error)?;
+
+        Ok(CallToolResult::success(vec![Content::text(result)]))
+    }
+
     /// Ask a question and get an AI-generated answer based on relevant documents (RAG).
     /// Searches for context documents and generates a response using the LLM.
     #[tool(name = "ask")]
     async fn ask(&self, params: Parameters<AskParams>) -> Result<CallToolResult, rmcp::ErrorData> {
         let p = params.0;
 
+        let state = Arc::clone(&self.state);
         let result = tokio::task::spawn_blocking(move || -> Result<String, String> {
-            let store = qmd::Store::new().map_err(|e| e.to_string())?;
+            let fts = qmd::FtsSearchOptions::from_config().map_err(|e| e.to_string())?;
+            let store = state.model_reader().map_err(|e| e.to_string())?;
+            let (filter, question) = split_filters(&store, &p.question, p.collection.as_deref())
+                .map_err(|e| e.to_string())?;
 
             // Search for relevant documents using vector search if available, fallback to FTS
-            let context_docs = if let Ok(mut engine) = qmd::EmbeddingEngine::load_default() {
-                if let Ok(query_result) = engine.embed_query(&p.question) {
+            let mut engines = state.engines();
+            let context_docs = if let Ok(engine) = engines.embedder() {
+                if let Ok(query_result) = engine.embed_query(&question) {
                     store
-                        .search_vec(&query_result.embedding, p.limit, p.collection.as_deref())
+                        .search_vec_with(
+                            &query_result.embedding,
+                            p.limit,
+                            &filter,
+                            &qmd::VecSearchOptions::default(),
+                        )
                         .unwrap_or_default()
                 } else {
                     store
-                        .search_fts(&p.question, p.limit, p.collection.as_deref())
+                        .search_fts_with(&question, p.limit, &filter, &fts)
                         .unwrap_or_default()
                 }
             } else {
                 store
-                    .search_fts(&p.question, p.limit, p.collection.as_deref())
+                    .search_fts_with(&question, p.limit, &filter, &fts)
                     .unwrap_or_default()
             };
 
@@ -841,15 +1572,15 @@ async fn ask(&self, params: Parameters<AskParams>) -> Result<CallToolResult, rmc
             }
 
             // Generate answer using LLM
-            let gen_engine = qmd::GenerationEngine::load_default()
+            let gen_engine = engines
+                .generator()
                 .map_err(|e| format!("Could not load generation model: {e}"))?;
 
             let prompt = format!(
                 "Based on the following documents, answer the question concisely.\n\n\
                  Documents:\n{context}\n\n\
-                 Question: {}\n\n\
-                 Answer:",
-                p.question
+                 Question: {question}\n\n\
+                 Answer:"
             );
 
             let gen_result = gen_engine
@@ -880,8 +1611,9 @@ async fn rerank(
     ) -> Result<CallToolResult, rmcp::ErrorData> {
         let p = params.0;
 
+        let state = Arc::clone(&self.state);
         let result = tokio::task::spawn_blocking(move || -> Result<String, String> {
-            let store = qmd::Store::new().map_err(|e| e.to_string())?;
+            let store = state.model_reader().map_err(|e| e.to_string())?;
 
             // Parse file list
             let file_list: Vec<&str> = p
@@ -938,8 +1670,10 @@ async fn rerank(
             }
 
             // Rerank using cross-encoder
-            let mut engine =
-                qmd::RerankEngine::load_default().map_err(|e| format!("Rerank model: {e}"))?;
+            let mut engines = state.engines();
+            let engine = engines
+                .reranker()
+                .map_err(|e| format!("Rerank model: {e}"))?;
 
             let rerank_result = engine.rerank(&p.query, &docs).map_err(|e| e.to_string())?;
 
@@ -960,151 +1694,52 @@ async fn rerank(
 
     /// Advanced hybrid search with query expansion, RRF fusion, and optional reranking.
     /// Best quality results combining multiple search strategies.
-    #[tool(name = "qsearch")]
+    #[tool(
+        name = "qsearch",
+        output_schema = schema_for_type::<SearchResults>()
+    )]
     async fn qsearch(
         &self,
         params: Parameters<QsearchParams>,
     ) -> Result<CallToolResult, rmcp::ErrorData> {
         let p = params.0;
 
-        let result =
-            tokio::task::spawn_blocking(move || -> Result<Vec<SearchResultItem>, String> {
-                let store = qmd::Store::new().map_err(|e| e.to_string())?;
-
-                // Expand query if enabled
-                let queries = if p.no_expand || !qmd::GenerationEngine::is_available() {
-                    vec![qmd::Queryable::lex(&p.query), qmd::Queryable::vec(&p.query)]
-                } else {
-                    match qmd::GenerationEngine::load_default() {
-                        Ok(engine) => match engine.expand_query(&p.query, true) {
-                            Ok(q) => q,
-                            Err(_) => qmd::expand_query_simple(&p.query),
-                        },
-                        Err(_) => qmd::expand_query_simple(&p.query),
-                    }
-                };
-
-                // Collect results from different search strategies
-                let mut fts_results: Vec<(String, String, String, String)> = Vec::new();
-                let mut vec_results: Vec<(String, String, String, String)> = Vec::new();
-
-                for q in &queries {
-                    match q.query_type {
-                        qmd::QueryType::Lex => {
-                            if let Ok(results) =
-                                store.search_fts(&q.text, p.limit * 2, p.collection.as_deref())
-                            {
-                                for r in results {
-                                    fts_results.push((
-                                        r.doc.filepath.clone(),
-                                        r.doc.display_path.clone(),
-                                        r.doc.title.clone(),
-                                        r.doc.body.clone().unwrap_or_default(),
-                                    ));
-                                }
-                            }
-                        }
-                        qmd::QueryType::Vec | qmd::QueryType::Hyde => {
-                            if let Ok(mut engine) = qmd::EmbeddingEngine::load_default() {
-                                if let Ok(query_result) = engine.embed_query(&q.text) {
-                                    if let Ok(results) = store.search_vec(
-                                        &query_result.embedding,
-                                        p.limit * 2,
-                                        p.collection.as_deref(),
-                                    ) {
-                                        for r in results {
-                                            let body = store
-                                                .get_document(&r.doc.collection_name, &r.doc.path)
-                                                .ok()
-                                                .flatten()
-                                                .and_then(|d| d.body)
-                                                .unwrap_or_default();
-                                            vec_results.push((
-                                                r.doc.filepath.clone(),
-                                                r.doc.display_path.clone(),
-                                                r.doc.title.clone(),
-                                                body,
-                                            ));
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-
-                // RRF fusion
-                let mut rrf_results = qmd::hybrid_search_rrf(fts_results, vec_results, 60);
-
-                // Optional reranking
-                if !p.no_rerank && qmd::RerankEngine::is_available() && !rrf_results.is_empty() {
-                    if let Ok(mut reranker) = qmd::RerankEngine::load_default() {
-                        let docs: Vec<qmd::RerankDocument> = rrf_results
-                            .iter()
-                            .take(p.limit * 2)
-                            .map(|r| qmd::RerankDocument {
-                                file: r.file.clone(),
-                                text: r.body.clone(),
-                                title: Some(r.title.clone()),
-                            })
-                            .collect();
-
-                        if let Ok(reranked) = reranker.rerank(&p.query, &docs) {
-                            let mut reordered = Vec::new();
-                            for rr in reranked.results {
-                                if let Some(orig) = rrf_results.iter().find(|r| r.file == rr.file) {
-                                    reordered.push(orig.clone());
-                                }
-                            }
-                            rrf_results = reordered;
-                        }
-                    }
-                }
-
-                rrf_results.truncate(p.limit);
-
-                // Convert to SearchResultItem and enrich with docids
-                let items: Vec<SearchResultItem> = rrf_results
-                    .into_iter()
-                    .filter_map(|r| {
-                        let parts: Vec<&str> = r
-                            .file
-                            .strip_prefix("qmd://")
-                            .unwrap_or(&r.file)
-                            .splitn(2, '/')
-                            .collect();
-                        if parts.len() == 2 {
-                            if let Ok(Some(doc)) = store.get_document(parts[0], parts[1]) {
-                                return Some(SearchResultItem {
-                                    docid: format!("#{}", doc.docid),
-                                    file: r.display_path,
-                                    title: r.title,
-                                    score: r.score,
-                                    context: doc.context,
-                                });
-                            }
-                        }
-                        None
-                    })
-                    .collect();
-
-                Ok(items)
-            })
-            .await
-            .map_err(|e| to_mcp_error(e))?
-            .map_err(|e| to_mcp_error(e))?;
+        let options = qmd::HybridOptions {
+            limit: p.limit,
+            expand: !p.no_expand,
+            rerank: !p.no_rerank,
+            ..qmd::HybridOptions::default()
+        };
+        let result = run_hybrid(
+            Arc::clone(&self.state),
+            p.query,
+            p.collection,
+            options,
+            p.explain,
+        )
+        .await?;
 
         let summary = if result.is_empty() {
             "No results found".to_string()
         } else {
             result
                 .iter()
-                .map(|r| format!("{} {:.2} {} - {}", r.docid, r.score, r.file, r.title))
+                .map(|r| {
+                    format!(
+                        "{} {:.2} {} - {}{}{}",
+                        r.docid,
+                        r.score,
+                        r.file,
+                        r.title,
+                        r.matches_suffix(),
+                        r.explain_suffix()
+                    )
+                })
                 .collect::<Vec<_>>()
                 .join("\n")
         };
 
-        Ok(CallToolResult::success(vec![Content::text(summary)]))
+        search_tool_result(result, summary)
     }
 
     /// Add a new collection to index markdown files from a directory.
@@ -1115,6 +1750,7 @@ async fn collection_add(
     ) -> Result<CallToolResult, rmcp::ErrorData> {
         let p = params.0;
 
+        let state = Arc::clone(&self.state);
         let result = tokio::task::spawn_blocking(move || -> Result<String, String> {
             use std::path::Path;
 
@@ -1143,47 +1779,14 @@ async fn collection_add(
             qmd::add_collection(&coll_name, &p.path, &p.pattern).map_err(|e| e.to_string())?;
 
             // Index files
-            let store = qmd::Store::new().map_err(|e| e.to_string())?;
-            let now = chrono::Utc::now().to_rfc3339();
-            let glob_matcher =
-                glob::Pattern::new(&p.pattern).map_err(|e| format!("Invalid pattern: {}", e))?;
-
-            let mut indexed = 0;
-            for entry in walkdir::WalkDir::new(&p.path)
-                .follow_links(true)
-                .into_iter()
-                .filter_map(|e| e.ok())
-            {
-                let entry_path = entry.path();
-                if !entry_path.is_file() {
-                    continue;
-                }
-                if qmd::should_exclude(entry_path) {
-                    continue;
-                }
-
-                let rel_path = entry_path
-                    .strip_prefix(&p.path)
-                    .unwrap_or(entry_path)
-                    .to_string_lossy();
-
-                if !glob_matcher.matches(&rel_path) {
-                    continue;
-                }
-
-                let Ok(content) = std::fs::read_to_string(entry_path) else {
-                    continue;
-                };
-
-                let normalized_path = qmd::Store::handelize(&rel_path);
-                let hash = qmd::Store::hash_content(&content);
-                let title = qmd::Store::extract_title(&content);
-
-                let _ = store.insert_content(&hash, &content, &now);
-                let _ =
-                    store.insert_document(&coll_name, &normalized_path, &title, &hash, &now, &now);
-                indexed += 1;
-            }
+            let store = state.writer().map_err(|e| e.to_string())?;
+            let collection = qmd::get_collection(&coll_name)
+                .map_err(|e| e.to_string())?
+                .ok_or_else(|| format!("Collection '{}' was not saved", coll_name))?;
+            let report = store
+                .index_collection(&collection, &qmd::IndexOptions::default())
+                .map_err(|e| e.to_string())?;
+            let indexed = report.new_count();
 
             Ok(format!(
                 "Collection '{}' created with {} files indexed",
@@ -1232,6 +1835,7 @@ async fn collection_remove(
     ) -> Result<CallToolResult, rmcp::ErrorData> {
         let p = params.0;
 
+        let state = Arc::clone(&self.state);
         let result = tokio::task::spawn_blocking(move || -> Result<String, String> {
             if qmd::get_collection(&p.name)
                 .map_err(|e| e.to_string())?
@@ -1240,7 +1844,7 @@ async fn collection_remove(
                 return Err(format!("Collection not found: {}", p.name));
             }
 
-            let store = qmd::Store::new().map_err(|e| e.to_string())?;
+            let store = state.writer().map_err(|e| e.to_string())?;
             let (deleted_docs, cleaned) = store
                 .remove_collection_documents(&p.name)
                 .map_err(|e| e.to_string())?;
@@ -1267,6 +1871,7 @@ async fn collection_rename(
     ) -> Result<CallToolResult, rmcp::ErrorData> {
         let p = params.0;
 
+        let state = Arc::clone(&self.state);
         let result = tokio::task::spawn_blocking(move || -> Result<String, String> {
             if qmd::get_collection(&p.old_name)
                 .map_err(|e| e.to_string())?
@@ -1282,7 +1887,7 @@ async fn collection_rename(
                 return Err(format!("Collection already exists: {}", p.new_name));
             }
 
-            let store = qmd::Store::new().map_err(|e| e.to_string())?;
+            let store = state.writer().map_err(|e| e.to_string())?;
             store
                 .rename_collection_documents(&p.old_name, &p.new_name)
                 .map_err(|e| e.to_string())?;
@@ -1409,11 +2014,14 @@ async fn context_remove(
     async fn update(
         &self,
         params: Parameters<UpdateParams>,
+        context: RequestContext<RoleServer>,
     ) -> Result<CallToolResult, rmcp::ErrorData> {
         let p = params.0;
 
+        let state = Arc::clone(&self.state);
+        let mut reporter = ProgressReporter::new(&context);
         let result = tokio::task::spawn_blocking(move || -> Result<String, String> {
-            let store = qmd::Store::new().map_err(|e| e.to_string())?;
+            let store = state.writer().map_err(|e| e.to_string())?;
             let _ = store.clear_cache();
 
             let collections = qmd::list_collections().map_err(|e| e.to_string())?;
@@ -1423,6 +2031,7 @@ async fn update(
             }
 
             let mut results = Vec::new();
+            let mut processed = 0usize;
 
             for coll in collections {
                 if let Some(ref target) = p.collection {
@@ -1431,81 +2040,31 @@ async fn update(
                     }
                 }
 
-                let now = chrono::Utc::now().to_rfc3339();
-                let glob_matcher = match glob::Pattern::new(&coll.pattern) {
-                    Ok(m) => m,
-                    Err(_) => continue,
-                };
-
-                let mut indexed = 0;
-                let mut updated = 0;
-                let mut seen_paths = std::collections::HashSet::new();
-
-                for entry in walkdir::WalkDir::new(&coll.path)
-                    .follow_links(true)
-                    .into_iter()
-                    .filter_map(|e| e.ok())
-                {
-                    let entry_path = entry.path();
-                    if !entry_path.is_file() || qmd::should_exclude(entry_path) {
-                        continue;
-                    }
-
-                    let rel_path = entry_path
-                        .strip_prefix(&coll.path)
-                        .unwrap_or(entry_path)
-                        .to_string_lossy();
-
-                    if !glob_matcher.matches(&rel_path) {
-                        continue;
-                    }
-
-                    let Ok(content) = std::fs::read_to_string(entry_path) else {
-                        continue;
-                    };
-
-                    let normalized_path = qmd::Store::handelize(&rel_path);
-                    seen_paths.insert(normalized_path.clone());
-                    let hash = qmd::Store::hash_content(&content);
-                    let title = qmd::Store::extract_title(&content);
-
-                    if let Ok(Some((doc_id, existing_hash, _))) =
-                        store.find_active_document(&coll.name, &normalized_path)
-                    {
-                        if existing_hash != hash {
-                            let _ = store.insert_content(&hash, &content, &now);
-                            let _ = store.update_document(doc_id, &title, &hash, &now);
-                            updated += 1;
-                        }
-                    } else {
-                        let _ = store.insert_content(&hash, &content, &now);
-                        let _ = store.insert_document(
-                            &coll.name,
-                            &normalized_path,
-                            &title,
-                            &hash,
-                            &now,
-                            &now,
-                        );
-                        indexed += 1;
-                    }
-                }
-
-                // Deactivate removed files
-                let mut deactivated = 0;
-                if let Ok(existing) = store.get_active_document_paths(&coll.name) {
-                    for path in existing {
-                        if !seen_paths.contains(&path) {
-                            let _ = store.deactivate_document(&coll.name, &path);
-                            deactivated += 1;
+                let indexed =
+                    store.index_collection_with(&coll, &qmd::IndexOptions::default(), |progress| {
+                        processed += 1;
+                        reporter.report(
+                            processed as f64,
+                            None,
+                            || {
+                                format!(
+                                    "{}: {}/{} {}",
+                                    coll.name, progress.processed, progress.total, progress.path
+                                )
+                            },
+                            progress.processed == progress.total,
+                        )
+                    });
+                match indexed {
+                    Err(qmd::QmdError::Cancelled) => return Err("Update cancelled".to_string()),
+                    Ok(report) => {
+                        results.push(format!("{}: {}", coll.name, report.summary()));
+                        for (path, reason) in report.failures() {
+                            results.push(format!("  failed {}: {}", path, reason));
                         }
                     }
+                    Err(e) => results.push(format!("{}: error: {}", coll.name, e)),
                 }
-
-                results.push(format!(
-                    "{}: {} new, {} updated, {} removed",
-                    coll.name, indexed, updated, deactivated
-                ));
             }
 
             if results.is_empty() {
@@ -1522,73 +2081,62 @@ async fn update(
     }
 
     /// Generate embeddings for documents that need them.
+    /// Sends progress notifications when the client supplies a progress token.
     #[tool(name = "embed")]
     async fn embed(
         &self,
         params: Parameters<EmbedParams>,
+        context: RequestContext<RoleServer>,
     ) -> Result<CallToolResult, rmcp::ErrorData> {
         let p = params.0;
 
+        let state = Arc::clone(&self.state);
+        let mut reporter = ProgressReporter::new(&context);
         let result = tokio::task::spawn_blocking(move || -> Result<String, String> {
-            let store = qmd::Store::new().map_err(|e| e.to_string())?;
-
-            if p.force {
-                let cleared = store.clear_embeddings().map_err(|e| e.to_string())?;
-                if cleared > 0 {
-                    // Cleared embeddings message will be in output
-                }
-            }
-
-            let pending = store
-                .get_hashes_needing_embedding()
+            let store = state.writer().map_err(|e| e.to_string())?;
+            let mut engines = state.engines();
+            let engine = engines.embedder().map_err(|e| format!("Model: {}", e))?;
+
+            // Keep chunking the way the index was embedded.
+            let options = qmd::EmbedOptions {
+                force: p.force,
+                chunker: store.get_vector_chunker().map_err(|e| e.to_string())?,
+            };
+            let report = store
+                .embed_pending(engine, &options, |progress| {
+                    reporter.report(
+                        progress.bytes_done as f64,
+                        Some(progress.total_bytes as f64),
+                        || {
+                            format!(
+                                "{}/{} chunks, {}/{} documents",
+                                progress.chunks_done,
+                                progress.total_chunks,
+                                progress.docs_done,
+                                progress.total_docs
+                            )
+                        },
+                        progress.chunks_done == progress.total_chunks,
+                    )
+                })
                 .map_err(|e| e.to_string())?;
 
-            if pending.is_empty() {
+            if report.chunks == 0 && report.failures.is_empty() {
                 return Ok("All documents already have embeddings.".to_string());
             }
 
-            let mut engine =
-                qmd::EmbeddingEngine::load_default().map_err(|e| format!("Model: {}", e))?;
-
-            let now = chrono::Utc::now().to_rfc3339();
-            let mut embedded = 0;
-            let mut errors = 0;
-
-            // Get dimensions from first embedding
-            let first_content = &pending[0].2;
-            let first_title = qmd::Store::extract_title(first_content);
-            let first_text = qmd::format_doc_for_embedding(first_content, Some(&first_title));
-            let first_result = engine.embed(&first_text).map_err(|e| e.to_string())?;
-            let dims = first_result.embedding.len();
-
-            store.ensure_vector_table(dims).map_err(|e| e.to_string())?;
-
-            for (hash, _path, content) in &pending {
-                if content.is_empty() {
-                    continue;
-                }
-
-                let title = qmd::Store::extract_title(content);
-                let formatted = qmd::format_doc_for_embedding(content, Some(&title));
-
-                match engine.embed(&formatted) {
-                    Ok(result) => {
-                        if store
-                            .insert_embedding(hash, 0, 0, &result.embedding, &result.model, &now)
-                            .is_ok()
-                        {
-                            embedded += 1;
-                        }
-                    }
-                    Err(_) => {
-                        errors += 1;
-                    }
-                }
+            let mut msg = format!(
+                "Embedded {} chunks from {} documents",
+                report.chunks, report.documents
+            );
+            if report.cleared > 0 {
+                msg.push_str(&format!(" (cleared {} existing)", report.cleared));
             }
-
-            let mut msg = format!("Embedded {} documents", embedded);
-            if errors > 0 {
-                msg.push_str(&format!(", {} errors", errors));
+            if !report.failures.is_empty() {
+                msg.push_str(&format!(", {} errors", report.failures.len()));
+            }
+            if report.index_lists.is_some() {
+                msg.push_str(", rebuilt vector index");
             }
 
             Ok(msg)
@@ -1717,8 +2265,9 @@ async fn models_pull(
     /// Clean up the database: remove orphaned entries and vacuum.
     #[tool(name = "db_cleanup")]
     async fn db_cleanup(&self) -> Result<CallToolResult, rmcp::ErrorData> {
-        let result = tokio::task::spawn_blocking(|| -> Result<String, String> {
-            let store = qmd::Store::new().map_err(|e| e.to_string())?;
+        let state = Arc::clone(&self.state);
+        let result = tokio::task::spawn_blocking(move || -> Result<String, String> {
+            let store = state.writer().map_err(|e| e.to_string())?;
 
             let cache_cleared = store.clear_cache().map_err(|e| e.to_string())?;
             let inactive = store
@@ -1747,8 +2296,9 @@ async fn db_cleanup(&self) -> Result<CallToolResult, rmcp::ErrorData> {
     /// Vacuum the database to reclaim space.
     #[tool(name = "db_vacuum")]
     async fn db_vacuum(&self) -> Result<CallToolResult, rmcp::ErrorData> {
-        let result = tokio::task::spawn_blocking(|| -> Result<String, String> {
-            let store = qmd::Store::new().map_err(|e| e.to_string())?;
+        let state = Arc::clone(&self.state);
+        let result = tokio::task::spawn_blocking(move || -> Result<String, String> {
+            let store = state.writer().map_err(|e| e.to_string())?;
             store.vacuum().map_err(|e| e.to_string())?;
             Ok("Database vacuumed successfully".to_string())
         })
@@ -1767,9 +2317,10 @@ async fn expand(
     ) -> Result<CallToolResult, rmcp::ErrorData> {
         let p = params.0;
 
+        let state = Arc::clone(&self.state);
         let result = tokio::task::spawn_blocking(move || -> Result<String, String> {
             let queries = if qmd::GenerationEngine::is_available() {
-                match qmd::GenerationEngine::load_default() {
+                match state.engines().generator() {
                     Ok(engine) => match engine.expand_query(&p.query, p.lexical) {
                         Ok(q) => q,
                         Err(_) => qmd::expand_query_simple(&p.query),
@@ -1817,7 +2368,10 @@ impl ServerHandler for QmdMcpServer {
     fn get_info(&self) -> ServerInfo {
         ServerInfo {
             protocol_version: ProtocolVersion::LATEST,
-            capabilities: ServerCapabilities::builder().enable_tools().build(),
+            capabilities: ServerCapabilities::builder()
+                .enable_tools()
+                .enable_resources()
+                .build(),
             server_info: Implementation {
                 name: "qmd".into(),
                 version: env!("CARGO_PKG_VERSION").into(),
@@ -1827,12 +2381,124 @@ fn get_info(&self) -> ServerInfo {
             },
             instructions: Some(
                 "QMD - Quick Markdown Search. A local search engine for markdown knowledge bases. \
-                 Search: 'search' (BM25), 'vsearch' (semantic), 'query'/'qsearch' (hybrid), 'expand'. \
+                 Search: 'search' (BM25), 'vsearch' (semantic), 'query'/'qsearch' (hybrid), 'grep' (regex/substring), 'expand'. \
                  AI: 'ask' (RAG Q&A), 'rerank' (cross-encoder). \
-                 Docs: 'get'/'multi_get', 'ls', 'status'. \
-                 Admin: 'collection_*', 'context_*', 'update', 'embed', 'models_*', 'db_*'."
+                 Docs: 'get'/'multi_get', 'ls', 'tags', 'outline', 'links'/'backlinks', 'status'. \
+                 Admin: 'collection_*', 'context_*', 'update', 'embed', 'models_*', 'db_*'. \
+                 Resources: every document is readable as qmd://{collection}/{path}."
                     .into(),
             ),
         }
     }
+
+    async fn list_resources(
+        &self,
+        request: Option<PaginatedRequestParams>,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListResourcesResult, rmcp::ErrorData> {
+        // The cursor is the virtual path of the last resource on the
+        // previous page.
+        let after = match request.and_then(|r| r.cursor) {
+            Some(cursor) => Some(qmd::parse_virtual_path(&cursor).ok_or_else(|| {
+                rmcp::ErrorData::invalid_params(format!("Invalid cursor: {cursor}"), None)
+            })?),
+            None => None,
+        };
+
+        let state = Arc::clone(&self.state);
+        let mut files = tokio::task::spawn_blocking(move || -> Result<_, qmd::QmdError> {
+            let start = after.as_ref().map(|(c, p)| (c.as_str(), p.as_str()));
+            state
+                .reader()?
+                .list_files_page(start, RESOURCE_PAGE_SIZE + 1)
+        })
+        .await
+        .map_err(to_mcp_error)?
+        .map_err(to_mcp_error)?;
+
+        let more = files.len() > RESOURCE_PAGE_SIZE;
+        files.truncate(RESOURCE_PAGE_SIZE);
+        let next_cursor = files
+            .last()
+            .filter(|_| more)
+            .map(|(collection, path, _, _)| qmd::store::build_virtual_path(collection, path));
+
+        let resources = files
+            .into_iter()
+            .map(|(collection, path, title, size)| {
+                let mut resource = RawResource::new(
+                    qmd::store::build_virtual_path(&collection, &path),
+                    format!("{collection}/{path}"),
+                );
+                resource.title = (!title.is_empty()).then_some(title);
+                resource.mime_type = Some(MARKDOWN_MIME.to_string());
+                resource.size = u32::try_from(size).ok();
+                resource.no_annotation()
+            })
+            .collect();
+
+        Ok(ListResourcesResult {
+            meta: None,
+            next_cursor,
+            resources,
+        })
+    }
+
+    async fn list_resource_templates(
+        &self,
+        _request: Option<PaginatedRequestParams>,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListResourceTemplatesResult, rmcp::ErrorData> {
+        let template = RawResourceTemplate {
+            uri_template: "qmd://{collection}/{path}".to_string(),
+            name: "document".to_string(),
+            title: Some("Indexed document".to_string()),
+            description: Some("A markdown document by collection and path".to_string()),
+            mime_type: Some(MARKDOWN_MIME.to_string()),
+            icons: None,
+        };
+        Ok(ListResourceTemplatesResult::with_all_items(vec![
+            template.no_annotation(),
+        ]))
+    }
+
+    async fn read_resource(
+        &self,
+        request: ReadResourceRequestParams,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ReadResourceResult, rmcp::ErrorData> {
+        let uri = request.uri;
+        let Some((collection, path)) = qmd::parse_virtual_path(&uri) else {
+            return Err(rmcp::ErrorData::invalid_params(
+                format!("Not a qmd:// URI: {uri}"),
+                None,
+            ));
+        };
+
+        let state = Arc::clone(&self.state);
+        let body = tokio::task::spawn_blocking(move || -> Result<_, qmd::QmdError> {
+            let store = state.reader()?;
+            Ok(store
+                .get_document(&collection, &path)?
+                .and_then(|doc| doc.body))
+        })
+        .await
+        .map_err(to_mcp_error)?
+        .map_err(to_mcp_error)?;
+
+        let Some(text) = body else {
+            return Err(rmcp::ErrorData::resource_not_found(
+                format!("Document not found: {uri}"),
+                None,
+            ));
+        };
+        Ok(ReadResourceResult {
+            contents: vec![ResourceContents::TextResourceContents {
+                uri,
+                mime_type: Some(MARKDOWN_MIME.to_string()),
+                text,
+                meta: None,
+            }],
+        })
+    }
 }
@@ -0,0 +1,233 @@
+//! Shared server state: the document store and lazily loaded models.
+//!
+//! The store is opened on first use and kept for the lifetime of the server,
+//! with three connections: one for tools that modify the index, one for tools
+//! that only read it and one for read tools that also run a model. The
+//! database is in WAL mode, so reads keep running while `update` or `embed`
+//! hold the write connection for their whole run. Tools that run a model
+//! already take turns on the models, so giving them their own connection
+//! keeps a slow hybrid query from holding up plain reads.
+//!
+//! Models are loaded on the first tool call that needs them and reused by
+//! later calls; a background reaper unloads them after a period of
+//! inactivity to free memory. `embed` holds the models for its whole run,
+//! so other tools that run a model wait until it finishes.
+//!
+//! Lock order is always store before engines. No call holds two store
+//! connections.
+
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock, PoisonError, Weak};
+use std::time::{Duration, Instant};
+
+use qmd::{EmbeddingEngine, GenerationEngine, HybridEngines, RerankEngine, Store};
+
+/// Default time a model may sit unused before it is unloaded.
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_mins(5);
+
+/// Loaded models and when they were last used.
+#[derive(Debug)]
+struct EngineSlots {
+    /// The loaded models.
+    engines: HybridEngines,
+    /// Last time any model was handed out.
+    last_used: Instant,
+}
+
+/// State shared by every tool call.
+#[derive(Debug)]
+pub struct ServerState {
+    /// Write connection to the document store, opened on first use.
+    writer: OnceLock<Mutex<Store>>,
+    /// Read connection to the document store, opened on first use.
+    reader: OnceLock<Mutex<Store>>,
+    /// Read connection for tools that run models, opened on first use.
+    model_reader: OnceLock<Mutex<Store>>,
+    /// Lazily loaded models.
+    engines: Mutex<EngineSlots>,
+    /// Idle time after which models are unloaded.
+    idle_timeout: Duration,
+}
+
+/// Exclusive access to the loaded models.
+///
+/// Refreshes the idle timer when dropped, so a long-running call keeps its
+/// models loaded.
+#[derive(Debug)]
+pub struct EngineGuard<'a>(MutexGuard<'a, EngineSlots>);
+
+impl EngineGuard<'_> {
+    /// Get the embedding model, loading it if needed.
+    pub fn embedder(&mut self) -> anyhow::Result<&mut EmbeddingEngine> {
+        let slot = &mut self.0.engines.embedder;
+        let engine = slot.take().map_or_else(
+            || {
+                tracing::info!("Loading embedding model");
+                EmbeddingEngine::load_default()
+            },
+            Ok,
+        )?;
+        Ok(slot.insert(engine))
+    }
+
+    /// Get the generation model, loading it if needed.
+    pub fn generator(&mut self) -> anyhow::Result<&mut GenerationEngine> {
+        let slot = &mut self.0.engines.generator;
+        let engine = slot.take().map_or_else(
+            || {
+                tracing::info!("Loading generation model");
+                GenerationEngine::load_default()
+            },
+            Ok,
+        )?;
+        Ok(slot.insert(engine))
+    }
+
+    /// Get the rerank model, loading it if needed.
+    pub fn reranker(&mut self) -> anyhow::Result<&mut RerankEngine> {
+        let slot = &mut self.0.engines.reranker;
+        let engine = slot.take().map_or_else(
+            || {
+                tracing::info!("Loading rerank model");
+                RerankEngine::load_default()
+            },
+            Ok,
+        )?;
+        Ok(slot.insert(engine))
+    }
+}
+
+impl Deref for EngineGuard<'_> {
+    type Target = HybridEngines;
+
+    fn deref(&self) -> &HybridEngines {
+        &self.0.engines
+    }
+}
+
+impl DerefMut for EngineGuard<'_> {
+    fn deref_mut(&mut self) -> &mut HybridEngines {
+        &mut self.0.engines
+    }
+}
+
+impl Drop for EngineGuard<'_> {
+    fn drop(&mut self) {
+        self.0.last_used = Instant::now();
+    }
+}
+
+impl ServerState {
+    /// Create the state and start the idle reaper.
+    ///
+    /// The reaper thread holds a weak reference and exits once the state is
+    /// dropped.
+    #[must_use]
+    pub fn new(idle_timeout: Duration) -> Arc<Self> {
+        let state = Arc::new(Self {
+            writer: OnceLock::new(),
+            reader: OnceLock::new(),
+            model_reader: OnceLock::new(),
+            engines: Mutex::new(EngineSlots {
+                engines: HybridEngines::default(),
+                last_used: Instant::now(),
+            }),
+            idle_timeout,
+        });
+        spawn_reaper(Arc::downgrade(&state), idle_timeout);
+        state
+    }
+
+    /// Lock the write connection, opening it on first use.
+    ///
+    /// For tools that modify the index; everything else uses
+    /// [`ServerState::reader`] or [`ServerState::model_reader`].
+    pub fn writer(&self) -> qmd::Result<MutexGuard<'_, Store>> {
+        lock_store(&self.writer)
+    }
+
+    /// Lock the read connection, opening it on first use.
+    pub fn reader(&self) -> qmd::Result<MutexGuard<'_, Store>> {
+        lock_store(&self.reader)
+    }
+
+    /// Lock the read connection for tools that run models, opening it on
+    /// first use.
+    pub fn model_reader(&self) -> qmd::Result<MutexGuard<'_, Store>> {
+        lock_store(&self.model_reader)
+    }
+
+    /// Lock the models. Models are loaded on demand through the guard.
+    pub fn engines(&self) -> EngineGuard<'_> {
+        let mut guard = self.engines.lock().unwrap_or_else(PoisonError::into_inner);
+        guard.last_used = Instant::now();
+        EngineGuard(guard)
+    }
+
+    /// Unload all models if they have been idle for longer than the timeout.
+    ///
+    /// Returns whether anything was unloaded. Models in use are never
+    /// unloaded because the caller holds the lock.
+    pub fn unload_idle(&self) -> bool {
+        let Ok(mut slots) = self.engines.try_lock() else {
+            return false;
+        };
+        if slots.engines.is_empty() || slots.last_used.elapsed() < self.idle_timeout {
+            return false;
+        }
+        slots.engines = HybridEngines::default();
+        tracing::info!("Unloaded idle models");
+        true
+    }
+}
+
+/// Lock a store connection, opening it on first use.
+fn lock_store(cell: &OnceLock<Mutex<Store>>) -> qmd::Result<MutexGuard<'_, Store>> {
+    let store = cell.get().map_or_else(
+        || Store::new().map(|opened| cell.get_or_init(|| Mutex::new(opened))),
+        Ok,
+    )?;
+    Ok(store.lock().unwrap_or_else(PoisonError::into_inner))
+}
+
+/// Periodically unload idle models until the state is dropped.
+fn spawn_reaper(state: Weak<ServerState>, idle_timeout: Duration) {
+    let interval = (idle_timeout / 4).max(Duration::from_secs(1));
+    let spawned = std::thread::Builder::new()
+        .name("qmd-mcp-reaper".to_string())
+        .spawn(move || {
+            loop {
+                std::thread::sleep(interval);
+                let Some(strong) = state.upgrade() else {
+                    break;
+                };
+                strong.unload_idle();
+            }
+        });
+    if let Err(e) = spawned {
+        tracing::warn!("Failed to start idle model reaper: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_engine_guard_refreshes_idle_timer() {
+        let timeout = Duration::from_millis(50);
+        let state = ServerState::new(timeout);
+
+        let guard = state.engines();
+        // A model in use is never unloaded.
+        assert!(!state.unload_idle());
+        std::thread::sleep(timeout * 2);
+        drop(guard);
+        assert!(state.engines.lock().unwrap().last_used.elapsed() < timeout);
+
+        // Nothing is loaded, so there is nothing to unload once idle.
+        std::thread::sleep(timeout * 2);
+        assert!(state.engines.lock().unwrap().last_used.elapsed() >= timeout);
+        assert!(!state.unload_idle());
+    }
+}
@@ -0,0 +1,399 @@
+//! Markdown-aware chunking.
+//!
+//! The size-based chunkers in [`crate::llm`] cut wherever the size limit
+//! falls, which can split a fenced code block or a table in two and loses
+//! which section a chunk came from. The Markdown chunker splits a document
+//! into sections at headings and packs whole sections into a chunk while they
+//! fit the budget. A section too large for one chunk is split between its
+//! blocks (paragraphs, lists, fenced code, tables); a block is only split
+//! between lines when it alone exceeds the budget. Chunks do not overlap and
+//! together cover the whole document.
+//!
+//! Each chunk carries the heading breadcrumb of the sections it covers, such
+//! as `["Guide", "Install"]`, which the embedding pass prefixes to the chunk
+//! text so every chunk is embedded with its context. The budget covers the
+//! chunk together with that prefix.
+
+use crate::frontmatter::split_frontmatter;
+use crate::llm::EmbeddingEngine;
+use crate::outline::atx_heading;
+use anyhow::Result;
+use std::convert::Infallible;
+use std::ops::Range;
+
+/// How documents are split into chunks for embedding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Chunker {
+    /// Fixed token budget with overlap, breaking at paragraphs; see
+    /// [`chunk_document_by_tokens`](crate::llm::chunk_document_by_tokens).
+    #[default]
+    Tokens,
+    /// Markdown structure: headings, code blocks and tables; see
+    /// [`chunk_markdown_by_tokens`].
+    Markdown,
+}
+
+impl Chunker {
+    /// Name stored in the database.
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Tokens => "tokens",
+            Self::Markdown => "markdown",
+        }
+    }
+
+    /// Parse a stored or user-supplied name.
+    #[must_use]
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "tokens" => Some(Self::Tokens),
+            "markdown" => Some(Self::Markdown),
+            _ => None,
+        }
+    }
+}
+
+/// A chunk of a Markdown document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MarkdownChunk {
+    /// The text content of the chunk.
+    pub text: String,
+    /// Byte position in the original document.
+    pub pos: usize,
+    /// Size of the text in the unit of the budget: tokens, or bytes for
+    /// [`chunk_markdown`]. The breadcrumb prefix is not included.
+    pub size: usize,
+    /// Headings enclosing every section in the chunk, outermost first.
+    pub breadcrumb: Vec<String>,
+}
+
+/// Prefix chunk text with its heading breadcrumb, as `Guide > Install`
+/// followed by a blank line. Text without a breadcrumb is returned as is.
+#[must_use]
+pub fn prefix_breadcrumb(text: &str, breadcrumb: &[String]) -> String {
+    if breadcrumb.is_empty() {
+        return text.to_string();
+    }
+    format!("{}\n\n{text}", breadcrumb.join(" > "))
+}
+
+/// Chunk a document by Markdown structure, measuring size in bytes.
+///
+/// Every chunk fits `max_bytes` once prefixed with its breadcrumb by
+/// [`prefix_breadcrumb`], unless a single line is larger.
+#[must_use]
+pub fn chunk_markdown(content: &str, max_bytes: usize) -> Vec<MarkdownChunk> {
+    let Ok(chunks) = pack(content, max_bytes, &mut |text| {
+        Ok::<_, Infallible>(text.len())
+    });
+    chunks
+}
+
+/// Chunk a document by Markdown structure, measuring size in tokens of the
+/// embedding model.
+///
+/// Every chunk fits `max_tokens` once prefixed with its breadcrumb by
+/// [`prefix_breadcrumb`], unless a single line is larger.
+///
+/// # Errors
+/// Returns an error if tokenization fails.
+pub fn chunk_markdown_by_tokens(
+    engine: &EmbeddingEngine,
+    content: &str,
+    max_tokens: usize,
+) -> Result<Vec<MarkdownChunk>> {
+    pack(content, max_tokens, &mut |text| engine.count_tokens(text))
+}
+
+/// A run of lines kept together when possible, with any trailing blank
+/// lines.
+#[derive(Debug)]
+struct Block {
+    /// Byte range in the document.
+    range: Range<usize>,
+    /// Level and text if the block is a heading.
+    heading: Option<(usize, String)>,
+}
+
+/// Split a document into blocks: frontmatter, headings, fenced code blocks,
+/// tables and other runs of non-blank lines.
+fn blocks(content: &str) -> Vec<Block> {
+    let mut blocks: Vec<Block> = Vec::new();
+    let mut pos = content.len() - split_frontmatter(content).1.len();
+    if pos > 0 {
+        blocks.push(Block {
+            range: 0..pos,
+            heading: None,
+        });
+    }
+
+    let mut fence: Option<&str> = None;
+    // Whether the next line continues the last block, and if it is a table.
+    let mut open: Option<bool> = None;
+    for line in content[pos..].split_inclusive('\n') {
+        let start = pos;
+        pos += line.len();
+        let trimmed = line.trim();
+
+        // `Some(heading)` starts a new block, `None` extends the last one.
+        let new_block = if let Some(marker) = fence {
+            if trimmed.starts_with(marker) {
+                fence = None;
+            }
+            None
+        } else if trimmed.is_empty() {
+            open = None;
+            blocks.is_empty().then_some(None)
+        } else if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            fence = Some(&trimmed[..3]);
+            open = None;
+            Some(None)
+        } else if let Some((level, text)) = atx_heading(line.trim_end()) {
+            open = None;
+            Some(Some((level, text.to_string())))
+        } else {
+            let table = trimmed.starts_with('|');
+            (open != Some(table)).then(|| {
+                open = Some(table);
+                None
+            })
+        };
+
+        match (new_block, blocks.last_mut()) {
+            (None, Some(last)) => last.range.end = pos,
+            (heading, _) => blocks.push(Block {
+                range: start..pos,
+                heading: heading.flatten(),
+            }),
+        }
+    }
+    blocks
+}
+
+/// A heading's section up to the next heading, as a range of blocks.
+struct Section {
+    /// Indexes of the section's blocks.
+    blocks: Range<usize>,
+    /// Headings enclosing the section, including its own.
+    path: Vec<String>,
+}
+
+/// Group blocks into sections, one per heading plus any leading content.
+fn sections(blocks: &[Block]) -> Vec<Section> {
+    let mut sections: Vec<Section> = Vec::new();
+    let mut stack: Vec<(usize, String)> = Vec::new();
+    for (i, block) in blocks.iter().enumerate() {
+        match &block.heading {
+            Some((level, text)) => {
+                while stack.last().is_some_and(|(l, _)| l >= level) {
+                    stack.pop();
+                }
+                stack.push((*level, text.clone()));
+                sections.push(Section {
+                    blocks: i..i + 1,
+                    path: stack.iter().map(|(_, t)| t.clone()).collect(),
+                });
+            }
+            None => match sections.last_mut() {
+                Some(section) => section.blocks.end = i + 1,
+                None => sections.push(Section {
+                    blocks: i..i + 1,
+                    path: Vec::new(),
+                }),
+            },
+        }
+    }
+    sections
+}
+
+/// Accumulates consecutive ranges of a document into chunks.
+struct Packer<'a> {
+    content: &'a str,
+    chunks: Vec<MarkdownChunk>,
+    range: Option<Range<usize>>,
+    size: usize,
+    breadcrumb: Vec<String>,
+    /// Size of the breadcrumb prefix the current chunk started with; adding
+    /// sections only shortens the breadcrumb.
+    prefix: usize,
+}
+
+impl Packer<'_> {
+    /// Whether `size` more of a section whose breadcrumb prefix measures
+    /// `prefix` fits in the current chunk.
+    const fn fits(&self, size: usize, prefix: usize, max: usize) -> bool {
+        let overhead = if self.range.is_some() {
+            self.prefix
+        } else {
+            prefix
+        };
+        self.size + size + overhead <= max
+    }
+
+    /// Append `span`, which must follow the current chunk.
+    fn add(&mut self, span: Range<usize>, size: usize, path: &[String], prefix: usize) {
+        if let Some(range) = &mut self.range {
+            range.end = span.end;
+            let common = self
+                .breadcrumb
+                .iter()
+                .zip(path)
+                .take_while(|(a, b)| a == b)
+                .count();
+            self.breadcrumb.truncate(common);
+        } else {
+            self.range = Some(span);
+            self.breadcrumb = path.to_vec();
+            self.prefix = prefix;
+        }
+        self.size += size;
+    }
+
+    /// Finish the current chunk, if any.
+    fn flush(&mut self) {
+        if let Some(range) = self.range.take() {
+            self.chunks.push(MarkdownChunk {
+                text: self.content[range.clone()].to_string(),
+                pos: range.start,
+                size: self.size,
+                breadcrumb: std::mem::take(&mut self.breadcrumb),
+            });
+        }
+        self.size = 0;
+    }
+}
+
+/// Pack sections, then blocks, then lines into chunks of at most `max`
+/// as measured by `measure`, counting the breadcrumb prefix; a single line
+/// larger than `max` becomes its own chunk. The pieces of a split section
+/// are not merged with the next section.
+fn pack<E>(
+    content: &str,
+    max: usize,
+    measure: &mut impl FnMut(&str) -> std::result::Result<usize, E>,
+) -> std::result::Result<Vec<MarkdownChunk>, E> {
+    let blocks = blocks(content);
+    let mut packer = Packer {
+        content,
+        chunks: Vec::new(),
+        range: None,
+        size: 0,
+        breadcrumb: Vec::new(),
+        prefix: 0,
+    };
+
+    for section in sections(&blocks) {
+        let span =
+            blocks[section.blocks.start].range.start..blocks[section.blocks.end - 1].range.end;
+        let section_size = measure(&content[span.clone()])?;
+        let prefix = if section.path.is_empty() {
+            0
+        } else {
+            measure(&prefix_breadcrumb("", &section.path))?
+        };
+        if !packer.fits(section_size, prefix, max) {
+            packer.flush();
+        }
+        if packer.fits(section_size, prefix, max) {
+            packer.add(span, section_size, &section.path, prefix);
+            continue;
+        }
+
+        for block in &blocks[section.blocks] {
+            let block_size = measure(&content[block.range.clone()])?;
+            if !packer.fits(block_size, prefix, max) {
+                packer.flush();
+            }
+            if packer.fits(block_size, prefix, max) {
+                packer.add(block.range.clone(), block_size, &section.path, prefix);
+                continue;
+            }
+
+            let mut start = block.range.start;
+            for line in content[block.range.clone()].split_inclusive('\n') {
+                let size = measure(line)?;
+                if !packer.fits(size, prefix, max) {
+                    packer.flush();
+                }
+                packer.add(start..start + line.len(), size, &section.path, prefix);
+                start += line.len();
+            }
+        }
+        // The next section starts a chunk of its own at its heading.
+        packer.flush();
+    }
+    packer.flush();
+    Ok(packer.chunks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(chunks: &[MarkdownChunk]) -> Vec<(String, String)> {
+        chunks
+            .iter()
+            .map(|c| {
+                let first = c.text.lines().next().unwrap_or_default().to_string();
+                (c.breadcrumb.join(" > "), first)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_chunk_markdown_keeps_structure() {
+        let code = format!("```rust\n{}```\n", "let x = 1;\n".repeat(8));
+        let table = format!("| a | b |\n|---|---|\n{}", "| 1 | 2 |\n".repeat(6));
+        let content = format!(
+            "---\ntitle: Guide\n---\n# Guide\nIntro.\n\n## Install\n{code}\n{table}\n\
+             ## Usage\nRun it.\n### Flags\nSee flags.\n"
+        );
+        let chunks = chunk_markdown(&content, 140);
+
+        let joined: String = chunks.iter().map(|c| c.text.as_str()).collect();
+        assert_eq!(joined, content);
+        for chunk in &chunks {
+            assert_eq!(
+                &content[chunk.pos..chunk.pos + chunk.text.len()],
+                chunk.text
+            );
+            assert!(chunk.size <= 140);
+            assert!(prefix_breadcrumb(&chunk.text, &chunk.breadcrumb).len() <= 140);
+        }
+        assert_eq!(
+            summary(&chunks),
+            [
+                (String::new(), "---".into()),
+                ("Guide > Install".into(), "## Install".into()),
+                ("Guide > Install".into(), "| a | b |".into()),
+                ("Guide > Usage".into(), "## Usage".into()),
+            ]
+        );
+        assert!(chunks[1].text.ends_with("```\n\n"));
+    }
+
+    #[test]
+    fn test_chunk_markdown_splits_oversized_blocks() {
+        let content = format!("# Log\n{}", "entry line\n".repeat(10));
+        let chunks = chunk_markdown(&content, 40);
+        assert!(chunks.len() > 2);
+        assert!(chunks.iter().all(|c| c.breadcrumb == ["Log"]));
+        assert!(
+            chunks
+                .iter()
+                .all(|c| prefix_breadcrumb(&c.text, &c.breadcrumb).len() <= 40)
+        );
+        let joined: String = chunks.iter().map(|c| c.text.as_str()).collect();
+        assert_eq!(joined, content);
+
+        assert_eq!(
+            chunk_markdown("no headings", 5)[0].breadcrumb,
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            prefix_breadcrumb("text", &["Guide".into(), "Install".into()]),
+            "Guide > Install\n\ntext"
+        );
+    }
+}
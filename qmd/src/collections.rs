@@ -5,6 +5,7 @@
 
 use crate::config::{get_config_dir, get_config_path};
 use crate::error::{QmdError, Result};
+use crate::query::Bm25Weights;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 use std::fs;
@@ -27,6 +28,9 @@ pub struct Collection {
     /// Optional bash command to run during qmd update.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub update: Option<String>,
+    /// Optional BM25 column weights overriding the global ones.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bm25: Option<Bm25Weights>,
 }
 
 /// The complete configuration file structure.
@@ -35,6 +39,9 @@ pub struct CollectionConfig {
     /// Context applied to all collections.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub global_context: Option<String>,
+    /// BM25 column weights for all collections.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bm25: Option<Bm25Weights>,
     /// Collection name -> config.
     #[serde(default)]
     pub collections: BTreeMap<String, Collection>,
@@ -151,7 +158,9 @@ pub fn list_collections() -> Result<Vec<NamedCollection>> {
 pub fn add_collection(name: &str, path: &str, pattern: &str) -> Result<()> {
     let mut config = load_config()?;
 
-    let existing_context = config.collections.get(name).and_then(|c| c.context.clone());
+    let existing = config.collections.get(name);
+    let existing_context = existing.and_then(|c| c.context.clone());
+    let existing_bm25 = existing.and_then(|c| c.bm25);
 
     config.collections.insert(
         name.to_string(),
@@ -160,6 +169,7 @@ pub fn add_collection(name: &str, path: &str, pattern: &str) -> Result<()> {
             pattern: pattern.to_string(),
             context: existing_context,
             update: None,
+            bm25: existing_bm25,
         },
     );
 
@@ -0,0 +1,320 @@
+//! Embedding pass.
+//!
+//! Chunks every document that has no embeddings yet, embeds the chunks and
+//! stores the vectors, reporting progress after each chunk. This is the single
+//! implementation behind `qmd embed` and the MCP `embed` tool.
+//!
+//! Each document's chunks are written in one transaction once all of them are
+//! embedded, so a cancelled pass never leaves a half-embedded document behind
+//! and the next pass resumes where it stopped.
+//!
+//! Documents are chunked by token budget, or by Markdown structure with
+//! [`Chunker::Markdown`], in which case each chunk is embedded with its
+//! heading breadcrumb prepended. The chunker is recorded with the vectors;
+//! switching to another one requires [`EmbedOptions::force`] so an index
+//! never mixes chunks of both kinds.
+
+use crate::chunker::{Chunker, chunk_markdown_by_tokens, prefix_breadcrumb};
+use crate::error::{QmdError, Result};
+use crate::llm::{
+    CHUNK_OVERLAP_TOKENS, CHUNK_SIZE_TOKENS, EmbeddingEngine, chunk_document_by_tokens,
+    format_doc_for_embedding,
+};
+use crate::store::Store;
+use std::ops::ControlFlow;
+use std::time::{Duration, Instant};
+
+/// Options for an embedding pass.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EmbedOptions {
+    /// Clear all existing embeddings and re-embed every document.
+    pub force: bool,
+    /// How documents are split into chunks.
+    pub chunker: Chunker,
+}
+
+/// Progress of an embedding pass, reported after every chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EmbedProgress {
+    /// Documents fully processed.
+    pub docs_done: usize,
+    /// Documents to embed.
+    pub total_docs: usize,
+    /// Chunks processed, including failed ones.
+    pub chunks_done: usize,
+    /// Chunks to embed.
+    pub total_chunks: usize,
+    /// Bytes of chunk text processed.
+    pub bytes_done: usize,
+    /// Bytes of chunk text to embed.
+    pub total_bytes: usize,
+    /// Time spent embedding so far.
+    pub elapsed: Duration,
+}
+
+impl EmbedProgress {
+    /// Completion percentage by bytes, 0-100.
+    #[must_use]
+    pub fn percent(&self) -> f64 {
+        if self.total_bytes == 0 {
+            return 100.0;
+        }
+        (self.bytes_done as f64 / self.total_bytes as f64) * 100.0
+    }
+
+    /// Throughput in bytes per second.
+    #[must_use]
+    pub fn bytes_per_sec(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs > 0.0 {
+            self.bytes_done as f64 / secs
+        } else {
+            0.0
+        }
+    }
+
+    /// Estimated seconds remaining, once throughput is known.
+    #[must_use]
+    pub fn eta_secs(&self) -> Option<f64> {
+        let rate = self.bytes_per_sec();
+        (rate > 0.0).then(|| self.total_bytes.saturating_sub(self.bytes_done) as f64 / rate)
+    }
+}
+
+/// Result of an embedding pass.
+#[derive(Debug, Clone, Default)]
+pub struct EmbedReport {
+    /// Embeddings cleared before the pass (with [`EmbedOptions::force`]).
+    pub cleared: usize,
+    /// Documents embedded.
+    pub documents: usize,
+    /// Chunks embedded.
+    pub chunks: usize,
+    /// Chunks that failed to embed, as `(document path, reason)`.
+    pub failures: Vec<(String, String)>,
+    /// Number of IVF lists if the vector index was (re)built afterwards.
+    pub index_lists: Option<usize>,
+    /// Time spent embedding.
+    pub elapsed: Duration,
+}
+
+/// A chunk waiting to be embedded.
+struct PendingChunk {
+    /// Chunk text.
+    text: String,
+    /// Byte offset in the document.
+    pos: usize,
+    /// Byte size of the chunk.
+    bytes: usize,
+    /// Enclosing headings, empty unless chunked by Markdown structure.
+    breadcrumb: Vec<String>,
+}
+
+/// A document waiting to be embedded.
+struct PendingDocument {
+    /// Content hash.
+    hash: String,
+    /// Document path, for error reporting.
+    path: String,
+    /// Document title.
+    title: String,
+    /// Chunks in order.
+    chunks: Vec<PendingChunk>,
+}
+
+/// Split a document into chunks, falling back to a single chunk when
+/// tokenization fails.
+///
+/// Markdown chunks are budgeted for the title prefix added by
+/// [`format_doc_for_embedding`] as well as their breadcrumb.
+fn chunk_for_embedding(
+    engine: &EmbeddingEngine,
+    content: &str,
+    title: &str,
+    chunker: Chunker,
+) -> Vec<PendingChunk> {
+    let chunks = match chunker {
+        Chunker::Tokens => {
+            chunk_document_by_tokens(engine, content, CHUNK_SIZE_TOKENS, CHUNK_OVERLAP_TOKENS).map(
+                |chunks| {
+                    chunks
+                        .into_iter()
+                        .map(|c| PendingChunk {
+                            text: c.text,
+                            pos: c.pos,
+                            bytes: c.bytes,
+                            breadcrumb: Vec::new(),
+                        })
+                        .collect()
+                },
+            )
+        }
+        Chunker::Markdown => engine
+            .count_tokens(&format_doc_for_embedding("", Some(title)))
+            .and_then(|prefix| {
+                let budget = CHUNK_SIZE_TOKENS.saturating_sub(prefix);
+                chunk_markdown_by_tokens(engine, content, budget)
+            })
+            .map(|chunks| {
+                chunks
+                    .into_iter()
+                    .map(|c| PendingChunk {
+                        bytes: c.text.len(),
+                        text: c.text,
+                        pos: c.pos,
+                        breadcrumb: c.breadcrumb,
+                    })
+                    .collect()
+            }),
+    };
+    chunks.unwrap_or_else(|_| {
+        vec![PendingChunk {
+            text: content.to_string(),
+            pos: 0,
+            bytes: content.len(),
+            breadcrumb: Vec::new(),
+        }]
+    })
+}
+
+impl Store {
+    /// Embed every document that has no embeddings yet.
+    ///
+    /// `on_progress` is called once before the first chunk and after every
+    /// chunk; returning [`ControlFlow::Break`] stops the pass with
+    /// [`QmdError::Cancelled`]. Documents completed before cancellation stay
+    /// embedded. Chunks that fail to embed are reported and skipped.
+    ///
+    /// Fails with [`QmdError::Config`] when embeddings made with another
+    /// chunker exist, unless [`EmbedOptions::force`] clears them first.
+    pub fn embed_pending(
+        &self,
+        engine: &mut EmbeddingEngine,
+        options: &EmbedOptions,
+        mut on_progress: impl FnMut(&EmbedProgress) -> ControlFlow<()>,
+    ) -> Result<EmbedReport> {
+        let mut report = EmbedReport::default();
+        if options.force {
+            report.cleared = self.clear_embeddings()?;
+        }
+        self.use_vector_chunker(options.chunker)?;
+
+        let documents: Vec<PendingDocument> = self
+            .get_hashes_needing_embedding()?
+            .into_iter()
+            .filter(|(_, _, content)| !content.is_empty())
+            .map(|(hash, path, content)| {
+                let title = Self::extract_title(&content);
+                PendingDocument {
+                    chunks: chunk_for_embedding(engine, &content, &title, options.chunker),
+                    title,
+                    hash,
+                    path,
+                }
+            })
+            .collect();
+
+        let mut progress = EmbedProgress {
+            docs_done: 0,
+            total_docs: documents.len(),
+            chunks_done: 0,
+            total_chunks: documents.iter().map(|d| d.chunks.len()).sum(),
+            bytes_done: 0,
+            total_bytes: documents
+                .iter()
+                .flat_map(|d| &d.chunks)
+                .map(|c| c.bytes)
+                .sum(),
+            elapsed: Duration::ZERO,
+        };
+        if progress.total_chunks == 0 {
+            return Ok(report);
+        }
+        if on_progress(&progress).is_break() {
+            return Err(QmdError::Cancelled);
+        }
+
+        let now = chrono::Utc::now().to_rfc3339();
+        let start = Instant::now();
+        let mut vector_writer = None;
+
+        for doc in &documents {
+            let mut embedded = Vec::with_capacity(doc.chunks.len());
+            for (seq, chunk) in doc.chunks.iter().enumerate() {
+                let text = prefix_breadcrumb(&chunk.text, &chunk.breadcrumb);
+                let formatted = format_doc_for_embedding(&text, Some(&doc.title));
+                match engine.embed(&formatted) {
+                    Ok(result) => {
+                        if vector_writer.is_none() {
+                            self.ensure_vector_table(result.embedding.len())?;
+                            vector_writer = Some(self.vector_writer()?);
+                        }
+                        embedded.push((seq, chunk.pos, result));
+                    }
+                    Err(e) => report.failures.push((doc.path.clone(), e.to_string())),
+                }
+
+                progress.chunks_done += 1;
+                progress.bytes_done += chunk.bytes;
+                progress.elapsed = start.elapsed();
+                if on_progress(&progress).is_break() {
+                    return Err(QmdError::Cancelled);
+                }
+            }
+
+            if let Some(writer) = &vector_writer
+                && !embedded.is_empty()
+            {
+                let tx = self.transaction()?;
+                for (seq, pos, result) in &embedded {
+                    writer.insert(
+                        &doc.hash,
+                        *seq,
+                        *pos,
+                        &result.embedding,
+                        &result.model,
+                        &now,
+                    )?;
+                }
+                tx.commit()?;
+                report.documents += 1;
+                report.chunks += embedded.len();
+            }
+            progress.docs_done += 1;
+        }
+        report.elapsed = start.elapsed();
+
+        if self.optimize_vector_index()? {
+            report.index_lists = Some(self.get_vector_index_lists()?);
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_embed_progress_math() {
+        let progress = EmbedProgress {
+            docs_done: 1,
+            total_docs: 2,
+            chunks_done: 3,
+            total_chunks: 6,
+            bytes_done: 250,
+            total_bytes: 1000,
+            elapsed: Duration::from_secs(5),
+        };
+        assert!((progress.percent() - 25.0).abs() < 1e-9);
+        assert!((progress.bytes_per_sec() - 50.0).abs() < 1e-9);
+        assert_eq!(progress.eta_secs(), Some(15.0));
+
+        let starting = EmbedProgress {
+            bytes_done: 0,
+            elapsed: Duration::ZERO,
+            ..progress
+        };
+        assert_eq!(starting.eta_secs(), None);
+    }
+}
@@ -34,6 +34,25 @@ pub enum QmdError {
     #[error("Invalid path: {0}")]
     InvalidPath(String),
 
+    /// Database was created by a newer version of qmd.
+    #[error(
+        "Database schema version {found} is newer than the supported version {supported}; upgrade qmd"
+    )]
+    SchemaTooNew {
+        /// Schema version found in the database.
+        found: u32,
+        /// Latest schema version this build understands.
+        supported: u32,
+    },
+
+    /// Search query could not be parsed.
+    #[error("Invalid search query: {0}")]
+    InvalidQuery(String),
+
+    /// Operation was cancelled by the caller.
+    #[error("Operation cancelled")]
+    Cancelled,
+
     /// Configuration error.
     #[error("Configuration error: {0}")]
     Config(String),
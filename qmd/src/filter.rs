@@ -0,0 +1,376 @@
+//! Search filters.
+//!
+//! Filters restrict which documents a search may return. They are compiled to
+//! SQL and applied while candidates are collected, before any ranking or
+//! fusion, so result limits are never spent on documents that would be
+//! filtered out afterwards.
+//!
+//! Filters can be written inline in any query:
+//!
+//! - `collection:name` restricts to a collection; repeat for several
+//! - `path:pattern` restricts to paths matching a glob, with the same
+//!   semantics as [`match_files_by_glob`](crate::store::match_files_by_glob);
+//!   repeat to allow several patterns
+//! - `after:YYYY-MM-DD` keeps documents modified on or after the date
+//! - `before:YYYY-MM-DD` keeps documents modified before the date
+//! - `tag:name` keeps documents tagged `name` or a nested tag `name/...`;
+//!   repeat to allow several tags
+//! - `field:value` keeps documents whose frontmatter `field` is `value`, or
+//!   lists it, ignoring case; only for fields that occur in the index (see
+//!   [`SearchFilter::parse_with_fields`]), and never for `title`, which stays
+//!   a full-text column filter
+
+use chrono::NaiveDate;
+use rusqlite::types::Value;
+use std::collections::BTreeMap;
+
+use crate::error::{QmdError, Result};
+use crate::tags::normalize_tag;
+
+/// Restrictions on the documents a search may return.
+///
+/// Values of the same kind are alternatives; different kinds must all match.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchFilter {
+    /// Allowed collections; empty allows all.
+    pub collections: Vec<String>,
+    /// Glob patterns over the collection-relative path; empty allows all.
+    pub paths: Vec<String>,
+    /// Earliest modification date, inclusive.
+    pub after: Option<NaiveDate>,
+    /// Latest modification date, exclusive.
+    pub before: Option<NaiveDate>,
+    /// Allowed tags, normalized; nested tags match too. Empty allows all.
+    pub tags: Vec<String>,
+    /// Allowed values of frontmatter fields, by lowercase field name.
+    pub meta: BTreeMap<String, Vec<String>>,
+}
+
+impl SearchFilter {
+    /// Filter restricted to a single collection, or unrestricted for `None`.
+    #[must_use]
+    pub fn collection(name: Option<&str>) -> Self {
+        Self::default().with_collection(name)
+    }
+
+    /// Also allow the given collection.
+    #[must_use]
+    pub fn with_collection(mut self, collection: Option<&str>) -> Self {
+        if let Some(name) = collection
+            && !self.collections.iter().any(|c| c == name)
+        {
+            self.collections.push(name.to_string());
+        }
+        self
+    }
+
+    /// Whether the filter allows every document.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.collections.is_empty()
+            && self.paths.is_empty()
+            && self.after.is_none()
+            && self.before.is_none()
+            && self.tags.is_empty()
+            && self.meta.is_empty()
+    }
+
+    /// Split inline filters out of a query.
+    ///
+    /// Returns the filters and the remaining query text. Quoted phrases and
+    /// excluded terms are never treated as filters.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`QmdError::InvalidQuery`] for a filter without a value, an
+    /// invalid date or an invalid path pattern.
+    pub fn parse(input: &str) -> Result<(Self, String)> {
+        Self::parse_with_fields(input, &[])
+    }
+
+    /// Split inline filters out of a query, also treating `field:value` as a
+    /// frontmatter filter for each of `fields`, e.g. from
+    /// [`Store::get_metadata_keys`](crate::store::Store::get_metadata_keys).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`QmdError::InvalidQuery`] as [`SearchFilter::parse`] does.
+    pub fn parse_with_fields(input: &str, fields: &[String]) -> Result<(Self, String)> {
+        let mut filter = Self::default();
+        let mut rest: Vec<&str> = Vec::new();
+
+        for token in split_tokens(input) {
+            let Some((key, value)) = token.split_once(':') else {
+                rest.push(token);
+                continue;
+            };
+            let reserved = matches!(key, "collection" | "path" | "after" | "before" | "tag");
+            let is_field = !reserved
+                && !key.eq_ignore_ascii_case("title")
+                && fields.iter().any(|f| f.eq_ignore_ascii_case(key));
+            if !reserved && !is_field {
+                rest.push(token);
+                continue;
+            }
+            if value.is_empty() {
+                return Err(invalid(format!("'{key}:' must be followed by a value")));
+            }
+            match key {
+                _ if is_field => {
+                    filter
+                        .meta
+                        .entry(key.to_lowercase())
+                        .or_default()
+                        .push(value.to_string());
+                }
+                "collection" => filter = filter.with_collection(Some(value)),
+                "path" => {
+                    glob::Pattern::new(value)
+                        .map_err(|e| invalid(format!("invalid path pattern '{value}': {e}")))?;
+                    filter.paths.push(value.to_string());
+                }
+                "tag" => {
+                    let tag = normalize_tag(value)
+                        .ok_or_else(|| invalid(format!("invalid tag '{value}'")))?;
+                    if !filter.tags.contains(&tag) {
+                        filter.tags.push(tag);
+                    }
+                }
+                "after" => {
+                    let date = parse_date(key, value)?;
+                    filter.after = filter.after.max(Some(date));
+                }
+                _ => {
+                    let date = parse_date(key, value)?;
+                    filter.before = Some(filter.before.map_or(date, |d| d.min(date)));
+                }
+            }
+        }
+
+        Ok((filter, rest.join(" ")))
+    }
+
+    /// Compile to SQL conditions over the `documents` table aliased as
+    /// `alias`, numbering parameters from `first_param`.
+    ///
+    /// Each condition is prefixed with ` AND `, so the result can be appended
+    /// to an existing `WHERE` clause; an empty filter yields an empty string.
+    pub(crate) fn to_sql(&self, alias: &str, first_param: usize) -> (String, Vec<Value>) {
+        let mut sql = String::new();
+        let mut params: Vec<Value> = Vec::new();
+        let next_param = |value: String, bound: &mut Vec<Value>| {
+            bound.push(Value::Text(value));
+            format!("?{}", first_param + bound.len() - 1)
+        };
+
+        if !self.collections.is_empty() {
+            let placeholders: Vec<String> = self
+                .collections
+                .iter()
+                .map(|c| next_param(c.clone(), &mut params))
+                .collect();
+            sql.push_str(&format!(
+                " AND {alias}.collection IN ({})",
+                placeholders.join(", ")
+            ));
+        }
+        if !self.paths.is_empty() {
+            let conditions: Vec<String> = self
+                .paths
+                .iter()
+                .map(|p| {
+                    let param = next_param(sqlite_glob(p), &mut params);
+                    format!("{alias}.path GLOB {param}")
+                })
+                .collect();
+            sql.push_str(&format!(" AND ({})", conditions.join(" OR ")));
+        }
+        if let Some(after) = self.after {
+            let param = next_param(after.to_string(), &mut params);
+            sql.push_str(&format!(" AND {alias}.modified_at >= {param}"));
+        }
+        if let Some(before) = self.before {
+            let param = next_param(before.to_string(), &mut params);
+            sql.push_str(&format!(" AND {alias}.modified_at < {param}"));
+        }
+        if !self.tags.is_empty() {
+            let conditions: Vec<String> = self
+                .tags
+                .iter()
+                .map(|tag| {
+                    let exact = next_param(tag.clone(), &mut params);
+                    let nested = next_param(format!("{tag}/*"), &mut params);
+                    format!("dt.tag = {exact} OR dt.tag GLOB {nested}")
+                })
+                .collect();
+            sql.push_str(&format!(
+                " AND EXISTS (SELECT 1 FROM document_tags dt WHERE dt.document_id = {alias}.id \
+                 AND ({}))",
+                conditions.join(" OR ")
+            ));
+        }
+        for (key, values) in &self.meta {
+            let key_param = next_param(key.clone(), &mut params);
+            let value_params: Vec<String> = values
+                .iter()
+                .map(|v| next_param(v.clone(), &mut params))
+                .collect();
+            sql.push_str(&format!(
+                " AND EXISTS (SELECT 1 FROM document_meta dm WHERE dm.hash = {alias}.hash \
+                 AND dm.key = {key_param} COLLATE NOCASE AND dm.value COLLATE NOCASE IN ({}))",
+                value_params.join(", ")
+            ));
+        }
+
+        (sql, params)
+    }
+}
+
+/// Build an invalid query error.
+const fn invalid(message: String) -> QmdError {
+    QmdError::InvalidQuery(message)
+}
+
+/// Parse a `YYYY-MM-DD` date filter value.
+fn parse_date(key: &str, value: &str) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|_| {
+        invalid(format!(
+            "'{key}:' expects a date like 2025-01-31, got '{value}'"
+        ))
+    })
+}
+
+/// Translate a glob pattern to `SQLite` `GLOB` syntax.
+///
+/// Both match `*` across `/`; only the negated class is spelled differently.
+fn sqlite_glob(pattern: &str) -> String {
+    pattern.replace("[!", "[^")
+}
+
+/// Split a query on whitespace, keeping quoted phrases in one token.
+fn split_tokens(input: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    let mut in_quote = false;
+    for (i, c) in input.char_indices() {
+        if c == '"' {
+            in_quote = !in_quote;
+        }
+        if c.is_whitespace() && !in_quote {
+            if let Some(s) = start.take() {
+                tokens.push(&input[s..i]);
+            }
+        } else if start.is_none() {
+            start = Some(i);
+        }
+    }
+    if let Some(s) = start {
+        tokens.push(&input[s..]);
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_inline_filters() {
+        let (filter, rest) = SearchFilter::parse(
+            "collection:notes rust path:2024/* after:2025-01-01 \"path:literal\" -path:x collection:work",
+        )
+        .unwrap();
+        assert_eq!(filter.collections, ["notes", "work"]);
+        assert_eq!(filter.paths, ["2024/*"]);
+        assert_eq!(filter.after, NaiveDate::from_ymd_opt(2025, 1, 1));
+        assert_eq!(filter.before, None);
+        assert_eq!(rest, "rust \"path:literal\" -path:x");
+
+        let (filter, rest) = SearchFilter::parse("title:rust http://x").unwrap();
+        assert!(filter.is_empty());
+        assert_eq!(rest, "title:rust http://x");
+    }
+
+    #[test]
+    fn test_parse_frontmatter_filters() {
+        let fields = ["status".to_string(), "title".to_string()];
+        let (filter, rest) =
+            SearchFilter::parse_with_fields("status:draft Status:review title:x other:y", &fields)
+                .unwrap();
+        assert_eq!(
+            filter.meta,
+            BTreeMap::from([("status".to_string(), vec!["draft".into(), "review".into()])])
+        );
+        assert_eq!(rest, "title:x other:y");
+
+        let (sql, params) = filter.to_sql("d", 1);
+        assert_eq!(
+            sql,
+            " AND EXISTS (SELECT 1 FROM document_meta dm WHERE dm.hash = d.hash \
+             AND dm.key = ?1 COLLATE NOCASE AND dm.value COLLATE NOCASE IN (?2, ?3))"
+        );
+        assert_eq!(params.len(), 3);
+    }
+
+    #[test]
+    fn test_parse_tag_filters() {
+        let (filter, rest) = SearchFilter::parse("tag:#Project tag:project rust").unwrap();
+        assert_eq!(filter.tags, ["project"]);
+        assert_eq!(rest, "rust");
+
+        let (sql, params) = filter.to_sql("d", 1);
+        assert_eq!(
+            sql,
+            " AND EXISTS (SELECT 1 FROM document_tags dt WHERE dt.document_id = d.id \
+             AND (dt.tag = ?1 OR dt.tag GLOB ?2))"
+        );
+        assert_eq!(
+            params,
+            [
+                Value::Text("project".into()),
+                Value::Text("project/*".into())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_filter_errors() {
+        for input in [
+            "collection:",
+            "tag:a.b",
+            "after:yesterday",
+            "before:2025-13-01",
+            "path:[a",
+        ] {
+            assert!(
+                matches!(SearchFilter::parse(input), Err(QmdError::InvalidQuery(_))),
+                "{input:?} should not parse"
+            );
+        }
+    }
+
+    #[test]
+    fn test_to_sql() {
+        assert_eq!(
+            SearchFilter::default().to_sql("d", 1),
+            (String::new(), vec![])
+        );
+
+        let (filter, _) =
+            SearchFilter::parse("collection:a collection:b path:[!x]* before:2025-02-01").unwrap();
+        let (sql, params) = filter.to_sql("d", 3);
+        assert_eq!(
+            sql,
+            " AND d.collection IN (?3, ?4) AND (d.path GLOB ?5) AND d.modified_at < ?6"
+        );
+        assert_eq!(
+            params,
+            [
+                Value::Text("a".into()),
+                Value::Text("b".into()),
+                Value::Text("[^x]*".into()),
+                Value::Text("2025-02-01".into()),
+            ]
+        );
+    }
+}
@@ -1,6 +1,9 @@
 //! Output formatting utilities.
 
-use crate::store::{DocumentResult, SearchResult};
+use crate::llm::QueryType;
+use crate::outline::Heading;
+use crate::snippet::Snippet;
+use crate::store::{DocumentResult, SearchResult, SearchSource};
 use chrono::{Datelike, Timelike};
 use colored::Colorize;
 
@@ -30,15 +33,161 @@ pub fn format_search_results(
     full: bool,
 ) -> String {
     match format {
-        OutputFormat::Json => format_search_json(results, full),
+        OutputFormat::Json => format_search_json(results, full, false),
         OutputFormat::Csv => format_search_csv(results),
-        OutputFormat::Md => format_search_md(results, full),
+        OutputFormat::Md => format_search_md(results, full, false),
         OutputFormat::Xml => format_search_xml(results, full),
         OutputFormat::Files => format_search_files(results),
-        OutputFormat::Cli => format_search_cli(results, full),
+        OutputFormat::Cli => format_search_cli(results, full, false),
     }
 }
 
+/// Format search results with an explanation of every score.
+///
+/// JSON output gains an `explain` object per result and CLI and Markdown
+/// output list the score components under each result. Other formats are
+/// the same as [`format_search_results`].
+#[must_use]
+pub fn format_search_results_explained(
+    results: &[SearchResult],
+    format: &OutputFormat,
+    full: bool,
+) -> String {
+    match format {
+        OutputFormat::Json => format_search_json(results, full, true),
+        OutputFormat::Md => format_search_md(results, full, true),
+        OutputFormat::Cli => format_search_cli(results, full, true),
+        OutputFormat::Csv | OutputFormat::Xml | OutputFormat::Files => {
+            format_search_results(results, format, full)
+        }
+    }
+}
+
+/// Explain how a result's score was computed, one component per line.
+///
+/// Plain searches report the raw BM25 score or vector similarity; hybrid
+/// results list each query variant's RRF contribution, the position bonus,
+/// the rerank score and the final blended score.
+#[must_use]
+pub fn explain_score(r: &SearchResult) -> Vec<String> {
+    let mut lines = Vec::new();
+    match &r.provenance {
+        None => match r.source {
+            SearchSource::Fts => lines.push(format!("bm25 {:.4}", r.score)),
+            SearchSource::Vec => lines.push(format!("similarity {:.4}", r.score)),
+            SearchSource::Hybrid => {}
+        },
+        Some(p) => {
+            for list in &p.lists {
+                let metric = if list.query_type == QueryType::Lex {
+                    "bm25"
+                } else {
+                    "similarity"
+                };
+                lines.push(format!(
+                    "{} \"{}\": rank {}, {metric} {:.4}, weight {} -> +{:.4}",
+                    list.query_type.as_str(),
+                    list.query,
+                    list.rank + 1,
+                    list.score,
+                    list.weight,
+                    list.contribution
+                ));
+            }
+            if p.position_bonus > 0.0 {
+                lines.push(format!("position bonus +{:.2}", p.position_bonus));
+            }
+            let fused_rank = p
+                .fused_rank
+                .map(|rank| format!(" (fused rank {})", rank + 1))
+                .unwrap_or_default();
+            lines.push(format!("rrf {:.4}{fused_rank}", p.rrf_score));
+            if let Some(rerank) = p.rerank_score {
+                let passage = r
+                    .passage
+                    .as_ref()
+                    .map(|passage| format!(" (best passage line {})", passage.line))
+                    .unwrap_or_default();
+                lines.push(format!("rerank {rerank:.4}{passage}"));
+            }
+        }
+    }
+    if let (Some(seq), Some(pos)) = (r.chunk_seq, r.chunk_pos) {
+        lines.push(format!("best chunk #{seq} at byte {pos}"));
+    }
+    lines.push(format!("final {:.4}", r.score));
+    lines
+}
+
+/// Structured score explanation for JSON output.
+///
+/// Ranks are 1-indexed, as in [`explain_score`].
+fn explain_json(r: &SearchResult) -> serde_json::Value {
+    let mut obj = serde_json::json!({
+        "source": r.source.as_str(),
+        "final": r.score,
+    });
+    match &r.provenance {
+        None => match r.source {
+            SearchSource::Fts => obj["bm25"] = serde_json::json!(r.score),
+            SearchSource::Vec => obj["similarity"] = serde_json::json!(r.score),
+            SearchSource::Hybrid => {}
+        },
+        Some(p) => {
+            obj["lists"] = p
+                .lists
+                .iter()
+                .map(|list| {
+                    serde_json::json!({
+                        "type": list.query_type.as_str(),
+                        "query": list.query,
+                        "rank": list.rank + 1,
+                        "score": list.score,
+                        "weight": list.weight,
+                        "contribution": list.contribution,
+                    })
+                })
+                .collect();
+            obj["position_bonus"] = serde_json::json!(p.position_bonus);
+            obj["rrf_score"] = serde_json::json!(p.rrf_score);
+            obj["fused_rank"] = serde_json::json!(p.fused_rank.map(|rank| rank + 1));
+            obj["rerank_score"] = serde_json::json!(p.rerank_score);
+        }
+    }
+    if let (Some(seq), Some(pos)) = (r.chunk_seq, r.chunk_pos) {
+        obj["chunk"] = serde_json::json!({ "seq": seq, "pos": pos });
+    }
+    obj
+}
+
+/// Render a snippet with its matches highlighted for the output format.
+///
+/// CLI output uses ANSI colors, Markdown wraps matches in `**` and XML in
+/// `<mark>` elements, with the rest of the text escaped. Other formats get
+/// the plain text.
+#[must_use]
+pub fn highlight_snippet(snippet: &Snippet, format: &OutputFormat) -> String {
+    let mut out = String::with_capacity(snippet.text.len());
+    for (text, hit) in snippet.parts() {
+        match (format, hit) {
+            (OutputFormat::Cli, true) => out.push_str(&text.yellow().bold().to_string()),
+            (OutputFormat::Md, true) => {
+                out.push_str("**");
+                out.push_str(text);
+                out.push_str("**");
+            }
+            (OutputFormat::Xml, true) => {
+                out.push_str("<mark>");
+                out.push_str(&escape_xml(text));
+                out.push_str("</mark>");
+            }
+            (OutputFormat::Xml, false) => out.push_str(&escape_xml(text)),
+            _ => out.push_str(text),
+        }
+    }
+    out
+}
+
 /// Format documents and return as string.
 #[must_use]
 pub fn format_documents(
@@ -56,7 +205,7 @@ pub fn format_documents(
 }
 
 // JSON output.
-fn format_search_json(results: &[SearchResult], full: bool) -> String {
+fn format_search_json(results: &[SearchResult], full: bool, explain: bool) -> String {
     let output: Vec<serde_json::Value> = results
         .iter()
         .map(|r| {
@@ -69,6 +218,55 @@ fn format_search_json(results: &[SearchResult], full: bool) -> String {
             if let Some(ref ctx) = r.doc.context {
                 obj["context"] = serde_json::Value::String(ctx.clone());
             }
+            if !r.doc.meta.is_empty() {
+                obj["meta"] = serde_json::json!(r.doc.meta);
+            }
+            if let (Some(seq), Some(pos)) = (r.chunk_seq, r.chunk_pos) {
+                obj["chunk"] = serde_json::json!({ "seq": seq, "pos": pos });
+            }
+            if let Some(ref p) = r.provenance {
+                obj["provenance"] = serde_json::json!({
+                    "fts_rank": p.fts_rank,
+                    "fts_score": p.fts_score,
+                    "vec_rank": p.vec_rank,
+                    "vec_score": p.vec_score,
+                    "rrf_score": p.rrf_score,
+                    "rerank_score": p.rerank_score,
+                });
+            }
+            if let Some(ref heading) = r.heading {
+                obj["section"] = serde_json::Value::String(section_ref(r, heading));
+                obj["heading"] = serde_json::Value::String(heading.text.clone());
+            }
+            if let Some(ref passage) = r.passage {
+                obj["passage"] = serde_json::json!({
+                    "pos": passage.pos,
+                    "line": passage.line,
+                    "score": passage.score,
+                    "text": passage.text,
+                });
+            }
+            if !r.snippets.is_empty() {
+                obj["snippets"] = r
+                    .snippets
+                    .iter()
+                    .map(|snippet| {
+                        let highlights: Vec<[usize; 2]> = snippet
+                            .highlights
+                            .iter()
+                            .map(|h| [h.start, h.end])
+                            .collect();
+                        serde_json::json!({
+                            "line": snippet.line,
+                            "text": snippet.text,
+                            "highlights": highlights,
+                        })
+                    })
+                    .collect();
+            }
+            if explain {
+                obj["explain"] = explain_json(r);
+            }
             if full && let Some(ref body) = r.doc.body {
                 obj["body"] = serde_json::Value::String(body.clone());
             }
@@ -96,6 +294,9 @@ fn format_docs_json(docs: &[(DocumentResult, bool, Option<String>)]) -> String {
                 if let Some(ref ctx) = doc.context {
                     obj["context"] = serde_json::Value::String(ctx.clone());
                 }
+                if !doc.meta.is_empty() {
+                    obj["meta"] = serde_json::json!(doc.meta);
+                }
                 if let Some(ref body) = doc.body {
                     obj["body"] = serde_json::Value::String(body.clone());
                 }
@@ -147,7 +348,7 @@ fn format_docs_csv(docs: &[(DocumentResult, bool, Option<String>)]) -> String {
 }
 
 // Markdown output.
-fn format_search_md(results: &[SearchResult], full: bool) -> String {
+fn format_search_md(results: &[SearchResult], full: bool, explain: bool) -> String {
     let mut out = String::new();
     for r in results {
         out.push_str(&format!(
@@ -158,6 +359,37 @@ fn format_search_md(results: &[SearchResult], full: bool) -> String {
         if let Some(ref ctx) = r.doc.context {
             out.push_str(&format!("**Context:** {ctx}\n\n"));
         }
+        if let Some(ref heading) = r.heading {
+            out.push_str(&format!(
+                "**Section:** {} (`{}`)\n\n",
+                heading.text,
+                section_ref(r, heading)
+            ));
+        }
+        if let Some(ref passage) = r.passage {
+            out.push_str(&format!(
+                "**Best passage** (line {}): {}\n\n",
+                passage.line,
+                passage_preview(&passage.text)
+            ));
+        }
+        if !r.snippets.is_empty() {
+            for snippet in &r.snippets {
+                out.push_str(&format!(
+                    "- line {}: {}\n",
+                    snippet.line,
+                    highlight_snippet(snippet, &OutputFormat::Md)
+                ));
+            }
+            out.push('\n');
+        }
+        if explain {
+            out.push_str("**Score:**\n\n");
+            for line in explain_score(r) {
+                out.push_str(&format!("- {line}\n"));
+            }
+            out.push('\n');
+        }
         if full && let Some(ref body) = r.doc.body {
             out.push_str(&format!("```\n{body}\n```\n\n"));
         }
@@ -208,6 +440,20 @@ fn format_search_xml(results: &[SearchResult], full: bool) -> String {
         if let Some(ref ctx) = r.doc.context {
             out.push_str(&format!("    <context>{}</context>\n", escape_xml(ctx)));
         }
+        if let Some(ref heading) = r.heading {
+            out.push_str(&format!(
+                "    <section heading=\"{}\">{}</section>\n",
+                escape_xml(&heading.text),
+                escape_xml(&section_ref(r, heading))
+            ));
+        }
+        for snippet in &r.snippets {
+            out.push_str(&format!(
+                "    <snippet line=\"{}\">{}</snippet>\n",
+                snippet.line,
+                highlight_snippet(snippet, &OutputFormat::Xml)
+            ));
+        }
         if full && let Some(ref body) = r.doc.body {
             out.push_str(&format!("    <body>{}</body>\n", escape_xml(body)));
         }
@@ -267,7 +513,7 @@ fn format_docs_files(docs: &[(DocumentResult, bool, Option<String>)]) -> String
 }
 
 // CLI output (colored, human-friendly).
-fn format_search_cli(results: &[SearchResult], full: bool) -> String {
+fn format_search_cli(results: &[SearchResult], full: bool, explain: bool) -> String {
     if results.is_empty() {
         return "No results found.".dimmed().to_string();
     }
@@ -286,6 +532,30 @@ fn format_search_cli(results: &[SearchResult], full: bool) -> String {
         if let Some(ref ctx) = r.doc.context {
             out.push_str(&format!("  {}\n", format!("Context: {ctx}").dimmed()));
         }
+        if let Some(ref heading) = r.heading {
+            out.push_str(&format!(
+                "  {}\n",
+                format!("Section: {} ({})", heading.text, section_ref(r, heading)).dimmed()
+            ));
+        }
+        if let Some(ref passage) = r.passage {
+            out.push_str(&format!(
+                "  {}\n",
+                format!("L{}: {}", passage.line, passage_preview(&passage.text)).dimmed()
+            ));
+        }
+        for snippet in &r.snippets {
+            out.push_str(&format!(
+                "  {} {}\n",
+                format!("L{}:", snippet.line).dimmed(),
+                highlight_snippet(snippet, &OutputFormat::Cli)
+            ));
+        }
+        if explain {
+            for line in explain_score(r) {
+                out.push_str(&format!("    {}\n", line.dimmed()));
+            }
+        }
         if full && let Some(ref body) = r.doc.body {
             out.push_str(&format!("\n{body}\n"));
         }
@@ -319,6 +589,25 @@ fn format_docs_cli(docs: &[(DocumentResult, bool, Option<String>)]) -> String {
     out
 }
 
+/// `file#slug` reference to the section of a result under `heading`.
+fn section_ref(r: &SearchResult, heading: &Heading) -> String {
+    format!("{}#{}", r.doc.display_path, heading.slug)
+}
+
+/// Maximum characters of a passage shown in search output.
+const PASSAGE_PREVIEW_CHARS: usize = 120;
+
+/// First line-folded characters of a passage, for one-line display.
+fn passage_preview(text: &str) -> String {
+    let folded = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if folded.chars().count() <= PASSAGE_PREVIEW_CHARS {
+        return folded;
+    }
+    let mut preview: String = folded.chars().take(PASSAGE_PREVIEW_CHARS).collect();
+    preview.push_str("...");
+    preview
+}
+
 /// Escape a string for CSV output.
 #[must_use]
 pub fn escape_csv(s: &str) -> String {
@@ -419,3 +708,58 @@ pub fn format_ls_time(timestamp: &str) -> String {
         format!("{month} {day:>2} {:02}:{:02}", dt.hour(), dt.minute())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frontmatter::Metadata;
+    use crate::store::{ListContribution, ScoreProvenance};
+
+    #[test]
+    fn test_explain_ranks_are_one_indexed() {
+        let result = SearchResult {
+            doc: DocumentResult {
+                filepath: "qmd://notes/a.md".to_string(),
+                display_path: "notes/a.md".to_string(),
+                title: "A".to_string(),
+                context: None,
+                hash: "abc123".to_string(),
+                docid: "abc123".to_string(),
+                collection_name: "notes".to_string(),
+                path: "a.md".to_string(),
+                modified_at: String::new(),
+                body_length: 0,
+                body: None,
+                meta: Metadata::new(),
+            },
+            score: 0.5,
+            source: SearchSource::Hybrid,
+            chunk_pos: None,
+            chunk_seq: None,
+            provenance: Some(ScoreProvenance {
+                lists: vec![ListContribution {
+                    query_type: QueryType::Lex,
+                    query: "rust".to_string(),
+                    rank: 0,
+                    score: 3.0,
+                    weight: 1.0,
+                    contribution: 0.0164,
+                }],
+                fused_rank: Some(2),
+                rrf_score: 0.0164,
+                ..ScoreProvenance::default()
+            }),
+            passage: None,
+            snippets: Vec::new(),
+            heading: None,
+        };
+
+        let json = explain_json(&result);
+        assert_eq!(json["lists"][0]["rank"], 1);
+        assert_eq!(json["fused_rank"], 3);
+
+        let text = explain_score(&result);
+        assert!(text[0].contains("rank 1,"));
+        assert!(text.iter().any(|line| line.contains("(fused rank 3)")));
+    }
+}
@@ -0,0 +1,190 @@
+//! YAML frontmatter.
+//!
+//! A document may start with a YAML mapping between `---` lines:
+//!
+//! ```markdown
+//! ---
+//! title: Weekly sync
+//! status: draft
+//! tags: [meeting, team]
+//! ---
+//! # Notes
+//! ```
+//!
+//! Scalar fields become [`MetaValue::Text`] and lists of scalars become
+//! [`MetaValue::List`]; nested mappings and nulls are ignored. Frontmatter that
+//! is not valid YAML or not a mapping is treated as having no fields.
+
+use serde::Serialize;
+use serde_yaml::Value;
+use std::collections::BTreeMap;
+
+/// Value of a frontmatter field.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(untagged)]
+pub enum MetaValue {
+    /// A scalar; numbers, booleans and dates are kept as written.
+    Text(String),
+    /// A list of scalars.
+    List(Vec<String>),
+}
+
+impl MetaValue {
+    /// The value as a slice of strings, one per list item.
+    #[must_use]
+    pub fn values(&self) -> &[String] {
+        match self {
+            Self::Text(text) => std::slice::from_ref(text),
+            Self::List(items) => items,
+        }
+    }
+}
+
+/// Frontmatter fields by name.
+pub type Metadata = BTreeMap<String, MetaValue>;
+
+/// Split a document into its frontmatter YAML and the body after it.
+///
+/// Returns `None` for the YAML if the document has no frontmatter block.
+#[must_use]
+pub fn split_frontmatter(content: &str) -> (Option<&str>, &str) {
+    let text = content.strip_prefix('\u{feff}').unwrap_or(content);
+    let mut lines = text.split_inclusive('\n');
+    if lines.next().map(str::trim_end) != Some("---") {
+        return (None, content);
+    }
+
+    let yaml_start = text.find('\n').map_or(text.len(), |p| p + 1);
+    let mut offset = yaml_start;
+    for line in lines {
+        if matches!(line.trim_end(), "---" | "...") {
+            return (
+                Some(&text[yaml_start..offset]),
+                &text[offset + line.len()..],
+            );
+        }
+        offset += line.len();
+    }
+    (None, content)
+}
+
+/// Parse a document's frontmatter fields; empty if it has none.
+#[must_use]
+pub fn parse_frontmatter(content: &str) -> Metadata {
+    let Some(yaml) = split_frontmatter(content).0 else {
+        return Metadata::new();
+    };
+    let Ok(Value::Mapping(mapping)) = serde_yaml::from_str::<Value>(yaml) else {
+        return Metadata::new();
+    };
+
+    mapping
+        .iter()
+        .filter_map(|(key, value)| {
+            let field = match value {
+                Value::Sequence(seq) => {
+                    let items: Vec<String> = seq.iter().filter_map(scalar).collect();
+                    (!items.is_empty()).then_some(MetaValue::List(items))?
+                }
+                other => MetaValue::Text(scalar(other)?),
+            };
+            Some((scalar(key)?, field))
+        })
+        .collect()
+}
+
+/// Text of a scalar YAML value.
+fn scalar(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        Value::Tagged(tagged) => scalar(&tagged.value),
+        Value::Null | Value::Sequence(_) | Value::Mapping(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::SearchFilter;
+    use crate::query::FtsSearchOptions;
+    use crate::store::Store;
+    use crate::test_support::{TempStore, paths};
+
+    #[test]
+    fn test_parse_frontmatter() {
+        let content = "---\ntitle: Weekly sync\nstatus: draft\ndate: 2025-01-31\n\
+                       tags: [meeting, team]\ndraft: true\nempty:\nnested: {a: 1}\n---\n# Notes\n";
+        let meta = parse_frontmatter(content);
+        assert_eq!(
+            meta.get("title"),
+            Some(&MetaValue::Text("Weekly sync".into()))
+        );
+        assert_eq!(meta["date"].values(), ["2025-01-31"]);
+        assert_eq!(meta["draft"].values(), ["true"]);
+        assert_eq!(
+            meta.get("tags"),
+            Some(&MetaValue::List(vec!["meeting".into(), "team".into()]))
+        );
+        assert!(!meta.contains_key("empty"));
+        assert!(!meta.contains_key("nested"));
+        assert_eq!(split_frontmatter(content).1, "# Notes\n");
+    }
+
+    #[test]
+    fn test_no_frontmatter() {
+        for content in [
+            "# Title\n---\nkey: value\n---\n",
+            "---\nunterminated: true\n",
+            "---\n- a list\n---\n",
+            "---\n: : invalid\n---\n",
+        ] {
+            assert!(parse_frontmatter(content).is_empty(), "{content:?}");
+        }
+        assert_eq!(split_frontmatter("# Title\n"), (None, "# Title\n"));
+        assert_eq!(
+            split_frontmatter("\u{feff}---\r\na: 1\r\n...\r\nbody"),
+            (Some("a: 1\r\n"), "body")
+        );
+    }
+
+    #[test]
+    fn test_frontmatter_metadata() {
+        let draft = "---\ntitle: Plan\nstatus: draft\ntags: [a, b]\n---\n# Heading\nrust plans";
+        let store = TempStore::new("meta");
+        store.add_doc("draft.md", draft);
+        store.add_doc("final.md", "---\nstatus: Final\n---\n# Final\nrust");
+        store.add_doc("plain.md", "# Plain\nrust");
+
+        assert_eq!(
+            Store::extract_title("---\n# not a title\nstatus: x\n---\n# Real\n"),
+            "Real"
+        );
+        assert_eq!(
+            store.get_metadata_keys().unwrap(),
+            ["status", "tags", "title"]
+        );
+
+        let search = |q: &str| {
+            let (filter, text) =
+                SearchFilter::parse_with_fields(q, &store.get_metadata_keys().unwrap()).unwrap();
+            store
+                .search_fts_with(&text, 10, &filter, &FtsSearchOptions::default())
+                .unwrap()
+        };
+        let results = search("rust status:draft");
+        assert_eq!(paths(&results), ["draft.md"]);
+        assert_eq!(results[0].doc.title, "Plan");
+        assert_eq!(
+            results[0].doc.meta.get("tags"),
+            Some(&MetaValue::List(vec!["a".into(), "b".into()]))
+        );
+        assert!(search("rust status:final tags:b").is_empty());
+        assert_eq!(
+            paths(&search("rust status:final status:DRAFT")),
+            ["draft.md", "final.md"]
+        );
+        assert_eq!(paths(&search("rust tags:b")), ["draft.md"]);
+    }
+}
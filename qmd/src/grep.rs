@@ -0,0 +1,387 @@
+//! Substring and regex search over document bodies.
+//!
+//! Porter-stemmed full-text search cannot find identifiers, partial words or
+//! code fragments like `fn parse_`. Grep matches a regular expression (or a
+//! literal with [`GrepOptions::fixed_strings`]) against every line of every
+//! document instead.
+//!
+//! When the trigram index is enabled (`qmd db trigram`), the literal runs
+//! every match must contain are looked up in it first, so only documents
+//! containing all of them are verified with the regex. Without the index, or
+//! for patterns without a literal run of at least three characters, every
+//! document in scope is scanned; the results are the same either way.
+
+use crate::error::{QmdError, Result};
+use crate::filter::SearchFilter;
+use crate::store::Store;
+use regex::{Regex, RegexBuilder};
+use std::fmt;
+use std::ops::ControlFlow;
+
+/// Shortest literal the trigram index can look up.
+const TRIGRAM_MIN_CHARS: usize = 3;
+
+/// Options for a grep.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GrepOptions {
+    /// Treat the pattern as a literal string instead of a regex.
+    pub fixed_strings: bool,
+    /// Match case-insensitively.
+    pub ignore_case: bool,
+    /// Maximum number of matching lines.
+    pub limit: usize,
+}
+
+impl Default for GrepOptions {
+    fn default() -> Self {
+        Self {
+            fixed_strings: false,
+            ignore_case: false,
+            limit: 100,
+        }
+    }
+}
+
+/// A line matching a grep pattern.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GrepMatch {
+    /// Collection name.
+    pub collection: String,
+    /// Path within the collection.
+    pub path: String,
+    /// Line number (1-indexed).
+    pub line: usize,
+    /// Text of the line.
+    pub text: String,
+}
+
+impl fmt::Display for GrepMatch {
+    /// Formats as `collection/path:line: text`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}/{}:{}: {}",
+            self.collection, self.path, self.line, self.text
+        )
+    }
+}
+
+/// Compile a grep pattern.
+///
+/// # Errors
+///
+/// Returns [`QmdError::InvalidQuery`] if the pattern is not a valid regex.
+pub fn compile_pattern(pattern: &str, options: &GrepOptions) -> Result<Regex> {
+    let source = if options.fixed_strings {
+        regex::escape(pattern)
+    } else {
+        pattern.to_string()
+    };
+    RegexBuilder::new(&source)
+        .case_insensitive(options.ignore_case)
+        .build()
+        .map_err(|e| QmdError::InvalidQuery(format!("invalid pattern '{pattern}': {e}")))
+}
+
+/// Literal runs that every match of `pattern` contains, in order.
+///
+/// The analysis is conservative: anything optional, repeated zero times,
+/// grouped or in a class ends a run, and `None` is returned when the pattern
+/// has a top-level alternation or a verbose-mode flag, since then no literal
+/// is required.
+fn required_literals(pattern: &str) -> Option<Vec<String>> {
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut runs = Vec::new();
+    let mut run = String::new();
+    let mut i = 0;
+
+    let end_run = |current: &mut String, done: &mut Vec<String>| {
+        if !current.is_empty() {
+            done.push(std::mem::take(current));
+        }
+    };
+
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => {
+                let Some(&next) = chars.get(i + 1) else {
+                    break;
+                };
+                i += 2;
+                if next.is_alphanumeric() {
+                    // Classes, assertions and escape codes.
+                    end_run(&mut run, &mut runs);
+                    let digits = match next {
+                        'x' => 2,
+                        'u' => 4,
+                        'U' => 8,
+                        'p' | 'P' => 1,
+                        _ => 0,
+                    };
+                    if digits > 0 {
+                        i = if chars.get(i) == Some(&'{') {
+                            closing(&chars, i, '}')? + 1
+                        } else {
+                            i + digits
+                        };
+                    }
+                } else {
+                    run.push(next);
+                }
+                continue;
+            }
+            '?' | '*' => {
+                run.pop();
+                end_run(&mut run, &mut runs);
+            }
+            '{' => {
+                run.pop();
+                end_run(&mut run, &mut runs);
+                i = closing(&chars, i, '}')?;
+            }
+            '+' | '.' | '^' | '$' => end_run(&mut run, &mut runs),
+            '|' => return None,
+            '(' => {
+                end_run(&mut run, &mut runs);
+                if chars.get(i + 1) == Some(&'?') {
+                    let flags: String = chars[i + 2..]
+                        .iter()
+                        .take_while(|c| !matches!(c, ')' | ':'))
+                        .collect();
+                    if flags.contains('x') {
+                        return None;
+                    }
+                }
+                i = closing_group(&chars, i)?;
+            }
+            '[' => {
+                end_run(&mut run, &mut runs);
+                i = closing_class(&chars, i)?;
+            }
+            c => run.push(c),
+        }
+        i += 1;
+    }
+    end_run(&mut run, &mut runs);
+    Some(runs)
+}
+
+/// Index of the first `close` at or after `start`.
+fn closing(chars: &[char], start: usize, close: char) -> Option<usize> {
+    chars[start..]
+        .iter()
+        .position(|&c| c == close)
+        .map(|p| start + p)
+}
+
+/// Index of the `)` closing the group opened at `start`.
+fn closing_group(chars: &[char], start: usize) -> Option<usize> {
+    let mut depth = 0;
+    let mut i = start;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 1,
+            '[' => i = closing_class(chars, i)?,
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Index of the `]` closing the class opened at `start`, allowing a leading
+/// literal `]` and nested classes.
+fn closing_class(chars: &[char], start: usize) -> Option<usize> {
+    let mut i = start + 1;
+    if chars.get(i) == Some(&'^') {
+        i += 1;
+    }
+    if chars.get(i) == Some(&']') {
+        i += 1;
+    }
+    let mut depth = 1;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 1,
+            '[' => depth += 1,
+            ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Trigram index expression requiring every literal run long enough to
+/// look up, or `None` if there is none.
+fn trigram_prefilter(pattern: &str) -> Option<String> {
+    let phrases: Vec<String> = required_literals(pattern)?
+        .into_iter()
+        .filter(|literal| literal.chars().count() >= TRIGRAM_MIN_CHARS)
+        .map(|literal| format!("\"{}\"", literal.replace('"', "\"\"")))
+        .collect();
+    (!phrases.is_empty()).then(|| phrases.join(" AND "))
+}
+
+impl Store {
+    /// Find the lines of active documents matching `pattern`, in path order.
+    ///
+    /// Uses the trigram index to skip documents that cannot match when it is
+    /// enabled; every returned line is verified with the regex.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`QmdError::InvalidQuery`] if the pattern is not a valid regex.
+    pub fn grep(
+        &self,
+        pattern: &str,
+        filter: &SearchFilter,
+        options: &GrepOptions,
+    ) -> Result<Vec<GrepMatch>> {
+        let regex = compile_pattern(pattern, options)?;
+        let prefilter = if self.has_trigram_index()? {
+            trigram_prefilter(regex.as_str())
+        } else {
+            None
+        };
+
+        let mut matches = Vec::new();
+        if options.limit == 0 {
+            return Ok(matches);
+        }
+        self.for_each_grep_candidate(prefilter.as_deref(), filter, |collection, path, body| {
+            for (i, line) in body.lines().enumerate() {
+                if regex.is_match(line) {
+                    matches.push(GrepMatch {
+                        collection: collection.to_string(),
+                        path: path.to_string(),
+                        line: i + 1,
+                        text: line.to_string(),
+                    });
+                    if matches.len() >= options.limit {
+                        return ControlFlow::Break(());
+                    }
+                }
+            }
+            ControlFlow::Continue(())
+        })?;
+        Ok(matches)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::QmdError;
+    use crate::filter::SearchFilter;
+    use crate::test_support::TempStore;
+
+    #[test]
+    fn test_required_literals() {
+        assert_eq!(
+            required_literals("fn parse_"),
+            Some(vec!["fn parse_".into()])
+        );
+        assert_eq!(
+            required_literals(r"v1\.2\.\d+ released"),
+            Some(vec!["v1.2.".into(), " released".into()])
+        );
+        assert_eq!(
+            required_literals("colou?r (a|b) [x]yz"),
+            Some(vec!["colo".into(), "r ".into(), " ".into(), "yz".into()])
+        );
+        assert_eq!(
+            required_literals(r"\x41bc\p{Greek}def{2,3}"),
+            Some(vec!["bc".into(), "de".into()])
+        );
+        assert_eq!(required_literals("foo|bar"), None);
+        assert_eq!(required_literals("(?x) fn parse"), None);
+    }
+
+    #[test]
+    fn test_trigram_prefilter() {
+        assert_eq!(
+            trigram_prefilter(r#"say "hi" to.*parse_\w+"#).as_deref(),
+            Some(r#""say ""hi"" to" AND "parse_""#)
+        );
+        assert_eq!(trigram_prefilter(r"ab.cd"), None);
+        assert_eq!(trigram_prefilter("a|bcd"), None);
+    }
+
+    #[test]
+    fn test_grep_with_and_without_trigram_index() {
+        let store = TempStore::new("grep");
+        store.add_doc("parser.md", "# Parser\nfn parse_query() {}\nparsing");
+        store.add_doc("other.md", "# Other\nParse_Query is mentioned\nv1.2.3");
+
+        let grep = |pattern: &str, options: &GrepOptions| {
+            store
+                .grep(pattern, &SearchFilter::default(), options)
+                .unwrap()
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+        };
+        let exact = GrepOptions::default();
+        let literal = GrepOptions {
+            fixed_strings: true,
+            ..exact
+        };
+
+        for enabled in [false, true] {
+            store.set_trigram_index(enabled).unwrap();
+            assert_eq!(store.has_trigram_index().unwrap(), enabled);
+            assert_eq!(
+                grep(r"fn parse_\w+", &exact),
+                ["notes/parser.md:2: fn parse_query() {}"]
+            );
+            assert_eq!(grep("1.2.3", &literal), ["notes/other.md:3: v1.2.3"]);
+            assert_eq!(
+                grep(
+                    "parse_query",
+                    &GrepOptions {
+                        ignore_case: true,
+                        ..exact
+                    }
+                ),
+                [
+                    "notes/other.md:2: Parse_Query is mentioned",
+                    "notes/parser.md:2: fn parse_query() {}",
+                ]
+            );
+        }
+
+        // The triggers keep the enabled index in sync.
+        store.add_doc("new.md", "# New\nlet x = parse_query();");
+        store.deactivate_document("notes", "parser.md").unwrap();
+        assert_eq!(
+            grep("parse_query", &exact),
+            ["notes/new.md:2: let x = parse_query();"]
+        );
+        let indexed: i64 = store
+            .transaction()
+            .unwrap()
+            .query_row("SELECT COUNT(*) FROM documents_trigram", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(indexed, 2);
+
+        assert!(matches!(
+            store.grep("(", &SearchFilter::default(), &exact),
+            Err(QmdError::InvalidQuery(_))
+        ));
+    }
+}
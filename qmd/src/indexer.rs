@@ -0,0 +1,315 @@
+//! Collection indexing.
+//!
+//! Walks a collection's directory, hashes every file matching its glob
+//! pattern, upserts new and changed documents and deactivates documents whose
+//! files have disappeared. This is the single implementation behind
+//! `qmd update`, `qmd collection add` and the MCP `update` tool.
+
+use crate::collections::NamedCollection;
+use crate::error::{QmdError, Result};
+use crate::store::{Store, should_exclude};
+use std::collections::HashSet;
+use std::fs;
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// Options for indexing a collection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndexOptions {
+    /// Follow symbolic links while walking the collection directory.
+    pub follow_links: bool,
+    /// Compute the report without writing to the database.
+    pub dry_run: bool,
+}
+
+impl Default for IndexOptions {
+    fn default() -> Self {
+        Self {
+            follow_links: true,
+            dry_run: false,
+        }
+    }
+}
+
+/// What happened to a single file during indexing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IndexOutcome {
+    /// File was not indexed before.
+    New,
+    /// File content or title changed.
+    Updated,
+    /// File is already up to date.
+    Unchanged,
+    /// File no longer exists; its document was deactivated.
+    Removed,
+    /// File could not be indexed.
+    Failed(String),
+}
+
+/// Indexing outcome for one file.
+#[derive(Debug, Clone)]
+pub struct FileOutcome {
+    /// Document path within the collection.
+    pub path: String,
+    /// What happened to the file.
+    pub outcome: IndexOutcome,
+}
+
+/// Result of indexing a collection.
+#[derive(Debug, Clone, Default)]
+pub struct IndexReport {
+    /// Collection name.
+    pub collection: String,
+    /// Per-file outcomes, in walk order followed by removals.
+    pub files: Vec<FileOutcome>,
+}
+
+impl IndexReport {
+    /// Count files whose outcome matches `pred`.
+    fn count(&self, pred: impl Fn(&IndexOutcome) -> bool) -> usize {
+        self.files.iter().filter(|f| pred(&f.outcome)).count()
+    }
+
+    /// Number of newly indexed files.
+    #[must_use]
+    pub fn new_count(&self) -> usize {
+        self.count(|o| *o == IndexOutcome::New)
+    }
+
+    /// Number of updated files.
+    #[must_use]
+    pub fn updated_count(&self) -> usize {
+        self.count(|o| *o == IndexOutcome::Updated)
+    }
+
+    /// Number of unchanged files.
+    #[must_use]
+    pub fn unchanged_count(&self) -> usize {
+        self.count(|o| *o == IndexOutcome::Unchanged)
+    }
+
+    /// Number of removed documents.
+    #[must_use]
+    pub fn removed_count(&self) -> usize {
+        self.count(|o| *o == IndexOutcome::Removed)
+    }
+
+    /// Files that failed to index, with the reason.
+    pub fn failures(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.files.iter().filter_map(|f| match &f.outcome {
+            IndexOutcome::Failed(reason) => Some((f.path.as_str(), reason.as_str())),
+            _ => None,
+        })
+    }
+
+    /// One-line summary, e.g. `3 new, 1 updated, 10 unchanged, 0 removed`.
+    #[must_use]
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "{} new, {} updated, {} unchanged, {} removed",
+            self.new_count(),
+            self.updated_count(),
+            self.unchanged_count(),
+            self.removed_count()
+        );
+        let failed = self.failures().count();
+        if failed > 0 {
+            summary.push_str(&format!(", {failed} failed"));
+        }
+        summary
+    }
+}
+
+/// Progress of a collection index run, reported after every file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndexProgress<'a> {
+    /// Files processed so far.
+    pub processed: usize,
+    /// Files matching the collection pattern.
+    pub total: usize,
+    /// Path of the file just processed.
+    pub path: &'a str,
+}
+
+/// Collect `(absolute path, relative path)` for files matching the pattern.
+fn collect_files(
+    root: &Path,
+    pattern: &glob::Pattern,
+    follow_links: bool,
+) -> Vec<(PathBuf, String)> {
+    let mut files = Vec::new();
+    for entry in WalkDir::new(root)
+        .follow_links(follow_links)
+        .into_iter()
+        .filter_map(std::result::Result::ok)
+    {
+        let path = entry.path();
+        if !path.is_file() || should_exclude(path) {
+            continue;
+        }
+        let rel_path = path.strip_prefix(root).unwrap_or(path);
+        let rel_path_str = rel_path.to_string_lossy();
+        if pattern.matches(&rel_path_str) {
+            files.push((path.to_path_buf(), rel_path_str.to_string()));
+        }
+    }
+    files
+}
+
+impl Store {
+    /// Index a collection: add new files, update changed ones and deactivate
+    /// documents whose files are gone.
+    ///
+    /// Unreadable files are reported as [`IndexOutcome::Failed`] and do not
+    /// abort the run; database errors do. All writes happen in a single
+    /// transaction.
+    pub fn index_collection(
+        &self,
+        collection: &NamedCollection,
+        options: &IndexOptions,
+    ) -> Result<IndexReport> {
+        self.index_collection_with(collection, options, |_| ControlFlow::Continue(()))
+    }
+
+    /// Index a collection, calling `on_progress` after every file.
+    ///
+    /// Returning [`ControlFlow::Break`] rolls the transaction back and stops
+    /// with [`QmdError::Cancelled`].
+    pub fn index_collection_with(
+        &self,
+        collection: &NamedCollection,
+        options: &IndexOptions,
+        mut on_progress: impl FnMut(&IndexProgress<'_>) -> ControlFlow<()>,
+    ) -> Result<IndexReport> {
+        let pattern = glob::Pattern::new(&collection.pattern)
+            .map_err(|e| QmdError::Config(format!("Invalid pattern: {e}")))?;
+        let root = Path::new(&collection.path);
+        if !root.is_dir() {
+            return Err(QmdError::InvalidPath(collection.path.clone()));
+        }
+
+        let now = chrono::Utc::now().to_rfc3339();
+        let mut report = IndexReport {
+            collection: collection.name.clone(),
+            files: Vec::new(),
+        };
+        let mut seen_paths = HashSet::new();
+
+        let files = collect_files(root, &pattern, options.follow_links);
+        let total = files.len();
+        let tx = self.transaction()?;
+        for (processed, (abs_path, rel_path)) in files.into_iter().enumerate() {
+            let progress = IndexProgress {
+                processed: processed + 1,
+                total,
+                path: &rel_path,
+            };
+            if on_progress(&progress).is_break() {
+                return Err(QmdError::Cancelled);
+            }
+
+            let path = Self::handelize(&rel_path);
+            seen_paths.insert(path.clone());
+
+            let content = match fs::read_to_string(&abs_path) {
+                Ok(c) => c,
+                Err(e) => {
+                    report.files.push(FileOutcome {
+                        path,
+                        outcome: IndexOutcome::Failed(format!("{}: {e}", abs_path.display())),
+                    });
+                    continue;
+                }
+            };
+            let hash = Self::hash_content(&content);
+            let title = Self::extract_title(&content);
+
+            let outcome = match self.find_active_document(&collection.name, &path)? {
+                Some((_, existing_hash, existing_title))
+                    if existing_hash == hash && existing_title == title =>
+                {
+                    IndexOutcome::Unchanged
+                }
+                Some((doc_id, existing_hash, _)) => {
+                    if !options.dry_run {
+                        if existing_hash == hash {
+                            self.update_document_title(doc_id, &title, &now)?;
+                        } else {
+                            self.insert_content(&hash, &content, &now)?;
+                            self.update_document(doc_id, &title, &hash, &now)?;
+                        }
+                    }
+                    IndexOutcome::Updated
+                }
+                None => {
+                    if !options.dry_run {
+                        self.insert_content(&hash, &content, &now)?;
+                        self.insert_document(&collection.name, &path, &title, &hash, &now, &now)?;
+                    }
+                    IndexOutcome::New
+                }
+            };
+            report.files.push(FileOutcome { path, outcome });
+        }
+
+        for path in self.get_active_document_paths(&collection.name)? {
+            if !seen_paths.contains(&path) {
+                if !options.dry_run {
+                    self.deactivate_document(&collection.name, &path)?;
+                }
+                report.files.push(FileOutcome {
+                    path,
+                    outcome: IndexOutcome::Removed,
+                });
+            }
+        }
+        tx.commit()?;
+
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempStore;
+
+    #[test]
+    fn test_index_collection_outcomes() {
+        let store = TempStore::new("indexer");
+        let root = store.dir().join("notes");
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("a.md"), "# Alpha\n").unwrap();
+        fs::write(root.join("b.md"), "# Beta\n").unwrap();
+        fs::write(root.join("skip.txt"), "not markdown").unwrap();
+
+        let collection = NamedCollection {
+            name: "notes".to_string(),
+            path: root.to_string_lossy().to_string(),
+            pattern: "**/*.md".to_string(),
+            context: None,
+            update: None,
+        };
+        let options = IndexOptions::default();
+
+        let report = store.index_collection(&collection, &options).unwrap();
+        assert_eq!(report.new_count(), 2);
+
+        fs::write(root.join("a.md"), "# Alpha v2\n").unwrap();
+        fs::remove_file(root.join("b.md")).unwrap();
+        fs::write(root.join("c.md"), [0xff, 0xfe, 0x00]).unwrap();
+
+        let report = store.index_collection(&collection, &options).unwrap();
+        assert_eq!(report.updated_count(), 1);
+        assert_eq!(report.removed_count(), 1);
+        assert_eq!(report.failures().count(), 1);
+        assert_eq!(
+            store.get_active_document_paths("notes").unwrap(),
+            vec!["a-md".to_string()]
+        );
+
+        let report = store.index_collection(&collection, &options).unwrap();
+        assert_eq!(report.unchanged_count(), 1);
+    }
+}
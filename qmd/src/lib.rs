@@ -34,33 +34,85 @@
 //! }
 //! ```
 
+pub mod chunker;
 pub mod collections;
 pub mod config;
+pub mod embedder;
 pub mod error;
+pub mod filter;
 pub mod formatter;
+pub mod frontmatter;
+pub mod grep;
+pub mod indexer;
+pub mod links;
 pub mod llm;
+pub mod outline;
+pub mod query;
+pub mod search;
+pub mod snippet;
 pub mod store;
+pub mod tags;
+#[cfg(test)]
+mod test_support;
+pub mod vector;
 
 // Re-export core types for convenient access
 pub use error::{QmdError, Result};
 
 // Store and search
 pub use store::{
-    CollectionInfo, DocumentResult, IndexStatus, SearchResult, SearchSource, Store,
-    convert_git_bash_path, find_similar_files, is_absolute_path, is_docid, is_virtual_path,
-    match_files_by_glob, normalize_filesystem_path, normalize_path_separators, parse_virtual_path,
-    should_exclude,
+    CollectionInfo, DocumentResult, IndexStatus, ListContribution, Passage, SCHEMA_VERSION,
+    ScoreProvenance, SearchResult, SearchSource, Store, VectorWriter, convert_git_bash_path,
+    find_similar_files, is_absolute_path, is_docid, is_virtual_path, match_files_by_glob,
+    normalize_filesystem_path, normalize_path_separators, parse_virtual_path, should_exclude,
 };
 
+// Query parsing and filters
+pub use filter::SearchFilter;
+pub use frontmatter::{MetaValue, Metadata, parse_frontmatter};
+pub use query::{Bm25Weights, FtsQuery, FtsSearchOptions, QueryField, QueryTerm};
+
+// Grep
+pub use grep::{GrepMatch, GrepOptions, compile_pattern};
+
+// Tags
+pub use tags::{TagCount, extract_tags, normalize_tag};
+
+// Links
+pub use links::{Link, LinkKind, LinkRef, extract_links};
+
+// Outlines
+pub use outline::{Heading, enclosing_heading, extract_headings, find_heading, slugify};
+
+// Snippets
+pub use snippet::{SNIPPET_WINDOW_CHARS, SNIPPET_WINDOWS, Snippet, build_snippets};
+
+// Hybrid search
+pub use search::{HybridEngines, HybridOptions, HybridSearcher, RerankBlend};
+
+// Indexing
+pub use indexer::{FileOutcome, IndexOptions, IndexOutcome, IndexProgress, IndexReport};
+
+// Chunking
+pub use chunker::{
+    Chunker, MarkdownChunk, chunk_markdown, chunk_markdown_by_tokens, prefix_breadcrumb,
+};
+
+// Embedding
+pub use embedder::{EmbedOptions, EmbedProgress, EmbedReport};
+
+// Vector index
+pub use vector::{ChunkAggregation, Quantization, VecSearchOptions};
+
 // LLM and embeddings
 pub use llm::{
     BatchRerankResult, CHUNK_OVERLAP_TOKENS, CHUNK_SIZE_TOKENS, Chunk, Cursor, EmbeddingEngine,
     EmbeddingResult, GenerationEngine, GenerationResult, IndexHealth, Progress, PullResult,
-    QueryType, Queryable, RerankDocument, RerankEngine, RerankResult, RrfResult, SnippetResult,
-    TokenChunk, chunk_document, chunk_document_by_tokens, cosine_similarity, expand_query_simple,
-    extract_snippet, format_doc_for_embedding, format_eta, format_query_for_embedding,
-    hybrid_search_rrf, pull_model, pull_models, reciprocal_rank_fusion, render_progress_bar,
-    resolve_model,
+    QueryType, Queryable, RerankDocument, RerankEngine, RerankMode, RerankResult, RrfResult,
+    SnippetResult, TokenChunk, chunk_document, chunk_document_by_tokens, cosine_similarity,
+    expand_query_simple, extract_snippet, format_doc_for_embedding, format_eta,
+    format_query_for_embedding, hybrid_search_rrf, pull_model, pull_models, reciprocal_rank_fusion,
+    render_progress_bar, resolve_model, rrf_position_bonus,
 };
 
 // Collections management
@@ -71,6 +123,6 @@
 
 // Formatting utilities
 pub use formatter::{
-    OutputFormat, add_line_numbers, format_bytes, format_documents, format_ls_time,
-    format_search_results, format_time_ago,
+    OutputFormat, add_line_numbers, explain_score, format_bytes, format_documents, format_ls_time,
+    format_search_results, format_search_results_explained, format_time_ago, highlight_snippet,
 };
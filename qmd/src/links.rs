@@ -0,0 +1,527 @@
+//! Links between documents.
+//!
+//! Links are extracted from inline Markdown links `[text](other.md)`,
+//! reference definitions `[label]: other.md` and wiki links `[[Other]]` or
+//! `[[folder/Other#Heading|alias]]`, outside code blocks and code spans.
+//! Only links to Markdown files are kept: Markdown links need a `.md` or
+//! `.markdown` target, and wiki links without an extension get `.md`.
+//!
+//! Each link is stored with the handelized path it points to. Relative
+//! Markdown links are resolved against the linking document's folder and
+//! `qmd://collection/path` links name their collection; other links stay in
+//! the linking document's collection. Targets are looked up when links are
+//! read, so a link resolves as soon as its target is indexed. A wiki link
+//! resolves to the document at its path or, failing that, to the shortest
+//! path ending with it.
+
+use crate::error::Result;
+use crate::frontmatter::split_frontmatter;
+use crate::store::{Store, is_docid, is_virtual_path, parse_virtual_path};
+use std::fmt;
+
+/// Syntax a link was written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkKind {
+    /// `[text](path.md)` or `[label]: path.md`.
+    Markdown,
+    /// `[[Target]]`.
+    Wiki,
+}
+
+impl LinkKind {
+    /// Name stored in the database.
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Markdown => "markdown",
+            Self::Wiki => "wiki",
+        }
+    }
+
+    /// Parse a stored name; anything but `wiki` is a Markdown link.
+    #[must_use]
+    pub fn from_name(name: &str) -> Self {
+        if name == "wiki" {
+            Self::Wiki
+        } else {
+            Self::Markdown
+        }
+    }
+}
+
+/// A link as written in a document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkRef {
+    /// Line number (1-indexed).
+    pub line: usize,
+    /// Link syntax.
+    pub kind: LinkKind,
+    /// Link text, or the target if the link has none.
+    pub text: String,
+    /// Target as written, without a `#heading` fragment.
+    pub target: String,
+}
+
+/// A stored link with its target resolved.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Link {
+    /// Collection of the linking document.
+    pub collection: String,
+    /// Path of the linking document.
+    pub path: String,
+    /// Line number in the linking document (1-indexed).
+    pub line: usize,
+    /// Link syntax.
+    pub kind: LinkKind,
+    /// Link text.
+    pub text: String,
+    /// Target as written.
+    pub target: String,
+    /// Collection and path of the linked document, or `None` if the link is
+    /// broken.
+    pub resolved: Option<(String, String)>,
+}
+
+impl fmt::Display for Link {
+    /// Formats as `collection/path:line: target -> collection/path`, or with
+    /// `(broken)` in place of the linked document.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}/{}:{}: {} -> ",
+            self.collection, self.path, self.line, self.target
+        )?;
+        match &self.resolved {
+            Some((collection, path)) => write!(f, "{collection}/{path}"),
+            None => write!(f, "(broken)"),
+        }
+    }
+}
+
+/// Extract the links of a document in order of appearance.
+#[must_use]
+pub fn extract_links(content: &str) -> Vec<LinkRef> {
+    let body = split_frontmatter(content).1;
+    let first_line = content[..content.len() - body.len()].matches('\n').count() + 1;
+
+    let mut links = Vec::new();
+    let mut in_fence = false;
+    for (i, line) in body.lines().enumerate() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+            continue;
+        }
+        if in_fence {
+            continue;
+        }
+        let number = first_line + i;
+        if let Some(link) = reference_definition(trimmed) {
+            links.extend(markdown_link(number, link.0, link.1));
+            continue;
+        }
+        inline_links(line, number, &mut links);
+    }
+    links
+}
+
+/// Label and destination of a `[label]: destination` line.
+fn reference_definition(line: &str) -> Option<(&str, &str)> {
+    let rest = line.strip_prefix('[')?;
+    let (label, after) = rest.split_once("]:")?;
+    if label.is_empty() || label.starts_with('^') {
+        return None;
+    }
+    Some((label, after.trim()))
+}
+
+/// Inline Markdown and wiki links of a line, outside code spans.
+fn inline_links(line: &str, number: usize, links: &mut Vec<LinkRef>) {
+    let mut in_code = false;
+    let mut i = 0;
+    while i < line.len() {
+        let rest = &line[i..];
+        if rest.starts_with('`') {
+            in_code = !in_code;
+        } else if !in_code && rest.starts_with("[[") {
+            if let Some(end) = rest.find("]]") {
+                links.extend(wiki_link(number, &rest[2..end]));
+                i += end + 2;
+                continue;
+            }
+        } else if !in_code
+            && rest.starts_with('[')
+            && let Some((text, destination, len)) = inline_link(rest)
+        {
+            links.extend(markdown_link(number, text, destination));
+            i += len;
+            continue;
+        }
+        i += rest.chars().next().map_or(1, char::len_utf8);
+    }
+}
+
+/// Text, destination and length of a `[text](destination)` link at the
+/// start of `s`.
+fn inline_link(s: &str) -> Option<(&str, &str, usize)> {
+    let close = s.find(']')?;
+    let after = s[close + 1..].strip_prefix('(')?;
+    let mut depth = 1;
+    for (j, c) in after.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some((&s[1..close], &after[..j], close + 2 + j + 1));
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// A Markdown link to a Markdown file.
+fn markdown_link(line: usize, text: &str, destination: &str) -> Option<LinkRef> {
+    let trimmed = destination.trim();
+    let url = match trimmed.strip_prefix('<') {
+        Some(rest) => rest.split_once('>').map_or(rest, |(inner, _)| inner),
+        // A title may follow the destination.
+        None => trimmed.split_whitespace().next().unwrap_or_default(),
+    };
+    let file = url.split(['#', '?']).next().unwrap_or_default();
+    let is_url = file.contains(':') && !is_virtual_path(file);
+    let is_markdown = matches!(extension(file).as_deref(), Some("md" | "markdown"));
+    if file.is_empty() || is_url || !is_markdown {
+        return None;
+    }
+    let target = percent_decode(file);
+    Some(LinkRef {
+        line,
+        kind: LinkKind::Markdown,
+        text: if text.trim().is_empty() {
+            target.clone()
+        } else {
+            text.trim().to_string()
+        },
+        target,
+    })
+}
+
+/// A `[[target#heading|alias]]` link, given the text between the brackets.
+fn wiki_link(line: usize, inner: &str) -> Option<LinkRef> {
+    let (reference, alias) = inner.split_once('|').unwrap_or((inner, ""));
+    let target = reference.split('#').next().unwrap_or_default().trim();
+    let ext = extension(target);
+    if target.is_empty() || ext.as_deref().is_some_and(|e| e != "md" && e != "markdown") {
+        return None;
+    }
+    Some(LinkRef {
+        line,
+        kind: LinkKind::Wiki,
+        text: if alias.trim().is_empty() {
+            target.to_string()
+        } else {
+            alias.trim().to_string()
+        },
+        target: target.to_string(),
+    })
+}
+
+/// Lowercased extension of the last path segment, if it looks like one:
+/// up to five ASCII letters and digits, not all digits.
+fn extension(target: &str) -> Option<String> {
+    let name = target.rsplit('/').next().unwrap_or(target);
+    let (stem, ext) = name.rsplit_once('.')?;
+    let valid = !stem.is_empty()
+        && (1..=5).contains(&ext.len())
+        && ext.chars().all(|c| c.is_ascii_alphanumeric())
+        && !ext.chars().all(|c| c.is_ascii_digit());
+    valid.then(|| ext.to_ascii_lowercase())
+}
+
+/// Decode `%XX` escapes, keeping invalid ones as written.
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| u8::from_str_radix(digits, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                out.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(out).unwrap_or_else(|_| s.to_string())
+}
+
+/// Handelized target of a link in the document at `source_path`: the
+/// target collection if the link names one, and the path.
+///
+/// Returns `None` for a relative link leaving the collection root.
+pub(crate) fn link_target(source_path: &str, link: &LinkRef) -> Option<(Option<String>, String)> {
+    if link.kind == LinkKind::Wiki {
+        let path = if extension(&link.target).is_some() {
+            Store::handelize(&link.target)
+        } else {
+            Store::handelize(&format!("{}.md", link.target))
+        };
+        return (!path.is_empty()).then_some((None, path));
+    }
+
+    if let Some((collection, path)) = parse_virtual_path(&link.target) {
+        return Some((Some(collection), Store::handelize(&path)));
+    }
+
+    let mut segments: Vec<String> = if link.target.starts_with('/') {
+        Vec::new()
+    } else {
+        let mut dir: Vec<String> = source_path.split('/').map(String::from).collect();
+        dir.pop();
+        dir
+    };
+    for segment in link.target.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop()?;
+            }
+            _ => segments.push(Store::handelize(segment)),
+        }
+    }
+    let path = segments.join("/");
+    (!path.is_empty()).then_some((None, path))
+}
+
+impl Store {
+    /// Find the collection and path of an active document given as a docid
+    /// (`#abc123`), a virtual path (`qmd://collection/path`) or
+    /// `collection/path`, where the path may be a file name before
+    /// handelizing.
+    pub fn locate_document(&self, file: &str) -> Result<Option<(String, String)>> {
+        if is_docid(file) {
+            return self.find_document_by_docid(file);
+        }
+        let location = if is_virtual_path(file) {
+            parse_virtual_path(file)
+        } else {
+            file.split_once('/')
+                .map(|(collection, path)| (collection.to_string(), path.to_string()))
+        };
+        let Some((collection, path)) = location else {
+            return Ok(None);
+        };
+        for candidate in [path.clone(), Self::handelize(&path)] {
+            if self
+                .find_active_document(&collection, &candidate)?
+                .is_some()
+            {
+                return Ok(Some((collection, candidate)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Outgoing links of a document, in order of appearance.
+    pub fn get_links(&self, collection: &str, path: &str) -> Result<Vec<Link>> {
+        self.query_links("d.collection = ?1 AND d.path = ?2", &[&collection, &path])
+    }
+
+    /// Links resolving to a document, by linking document and line.
+    pub fn get_backlinks(&self, collection: &str, path: &str) -> Result<Vec<Link>> {
+        // Candidates by target path; resolution picks the one linked document.
+        let links = self.query_links(
+            r"
+            COALESCE(l.target_collection, d.collection) = ?1
+            AND (l.target_path = ?2 OR (l.kind = 'wiki' AND ?2 LIKE '%/' || l.target_path))
+            ",
+            &[&collection, &path],
+        )?;
+        Ok(links
+            .into_iter()
+            .filter(|link| {
+                link.resolved
+                    .as_ref()
+                    .is_some_and(|(c, p)| c == collection && p == path)
+            })
+            .collect())
+    }
+
+    /// Links that do not resolve to an active document, optionally only from
+    /// one collection.
+    pub fn get_broken_links(&self, collection: Option<&str>) -> Result<Vec<Link>> {
+        let links = self.query_links("?1 IS NULL OR d.collection = ?1", &[&collection])?;
+        Ok(links
+            .into_iter()
+            .filter(|link| link.resolved.is_none())
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempStore;
+
+    fn targets(content: &str) -> Vec<(usize, LinkKind, String, String)> {
+        extract_links(content)
+            .into_iter()
+            .map(|l| (l.line, l.kind, l.text, l.target))
+            .collect()
+    }
+
+    #[test]
+    fn test_extract_links() {
+        let content = "---\ntitle: Links\n---\n\
+                       See [the plan](../plan.md#goals \"Plan\") and [[Weekly Sync|sync]].\n\
+                       [site](https://example.com/a.md), [img](pic.png), `[[code]]`, [[pic.png]]\n\
+                       ```\n[[fenced]]\n```\n\
+                       [ref]: <notes/My%20Note.md>\n\
+                       [[folder/Other#Heading]] and [v1.2](qmd://docs/v1.2.md)\n";
+        assert_eq!(
+            targets(content),
+            [
+                (
+                    4,
+                    LinkKind::Markdown,
+                    "the plan".into(),
+                    "../plan.md".into()
+                ),
+                (4, LinkKind::Wiki, "sync".into(), "Weekly Sync".into()),
+                (
+                    9,
+                    LinkKind::Markdown,
+                    "ref".into(),
+                    "notes/My Note.md".into()
+                ),
+                (
+                    10,
+                    LinkKind::Wiki,
+                    "folder/Other".into(),
+                    "folder/Other".into()
+                ),
+                (
+                    10,
+                    LinkKind::Markdown,
+                    "v1.2".into(),
+                    "qmd://docs/v1.2.md".into()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_link_target() {
+        let target = |kind, target: &str| {
+            let link = LinkRef {
+                line: 1,
+                kind,
+                text: String::new(),
+                target: target.to_string(),
+            };
+            link_target("projects/alpha/readme-md", &link)
+        };
+        assert_eq!(
+            target(LinkKind::Markdown, "../Plan Notes.md"),
+            Some((None, "projects/plan-notes-md".into()))
+        );
+        assert_eq!(
+            target(LinkKind::Markdown, "./specs/API.md"),
+            Some((None, "projects/alpha/specs/api-md".into()))
+        );
+        assert_eq!(
+            target(LinkKind::Markdown, "/index.md"),
+            Some((None, "index-md".into()))
+        );
+        assert_eq!(target(LinkKind::Markdown, "../../../up.md"), None);
+        assert_eq!(
+            target(LinkKind::Markdown, "qmd://docs/Guide.md"),
+            Some((Some("docs".into()), "guide-md".into()))
+        );
+        assert_eq!(
+            target(LinkKind::Wiki, "Weekly Sync"),
+            Some((None, "weekly-sync-md".into()))
+        );
+    }
+
+    #[test]
+    fn test_links_and_backlinks() {
+        let store = TempStore::new("links");
+        store.add_doc(
+            "guides/setup-md",
+            "# Setup\nSee [intro](../Intro.md), [[Glossary]] and [gone](missing.md).",
+        );
+        store.add_doc("intro-md", "# Intro\nBack to [[guides/setup]].");
+
+        let resolved = |links: Vec<Link>| {
+            links
+                .into_iter()
+                .map(|l| {
+                    let resolved = l.resolved.map(|(c, p)| format!("{c}/{p}"));
+                    (l.path, l.line, resolved)
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            resolved(store.get_links("notes", "guides/setup-md").unwrap()),
+            [
+                ("guides/setup-md".into(), 2, Some("notes/intro-md".into())),
+                ("guides/setup-md".into(), 2, None),
+                ("guides/setup-md".into(), 2, None),
+            ]
+        );
+
+        // Wiki links resolve by path suffix once the target is indexed.
+        store.add_doc("ref/glossary-md", "# Glossary");
+        assert_eq!(
+            resolved(store.get_backlinks("notes", "ref/glossary-md").unwrap()),
+            [(
+                "guides/setup-md".into(),
+                2,
+                Some("notes/ref/glossary-md".into())
+            )]
+        );
+        assert_eq!(
+            resolved(store.get_backlinks("notes", "guides/setup-md").unwrap()),
+            [("intro-md".into(), 2, Some("notes/guides/setup-md".into()))]
+        );
+        let broken = store.get_broken_links(Some("notes")).unwrap();
+        assert_eq!(broken.len(), 1);
+        assert_eq!(
+            broken[0].to_string(),
+            "notes/guides/setup-md:2: missing.md -> (broken)"
+        );
+
+        // Links follow document updates and deactivation.
+        store.deactivate_document("notes", "intro-md").unwrap();
+        assert!(
+            store
+                .get_backlinks("notes", "guides/setup-md")
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(store.get_broken_links(None).unwrap().len(), 2);
+
+        assert_eq!(
+            store.locate_document("notes/Guides/Setup.md").unwrap(),
+            Some(("notes".into(), "guides/setup-md".into()))
+        );
+        assert_eq!(
+            store
+                .locate_document("qmd://notes/ref/glossary-md")
+                .unwrap(),
+            Some(("notes".into(), "ref/glossary-md".into()))
+        );
+        assert_eq!(store.locate_document("notes/intro-md").unwrap(), None);
+    }
+}
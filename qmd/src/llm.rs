@@ -12,7 +12,7 @@
 use std::fs::{self, File};
 use std::io::{IsTerminal, Read, Write};
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, OnceLock};
 
 use anyhow::{Context, Result, bail};
 use indicatif::{ProgressBar, ProgressStyle};
@@ -21,6 +21,7 @@
 use llama_cpp_2::llama_batch::LlamaBatch;
 use llama_cpp_2::model::params::LlamaModelParams;
 use llama_cpp_2::model::{AddBos, LlamaModel};
+use llama_cpp_2::token::LlamaToken;
 use regex::Regex;
 
 use crate::config;
@@ -46,6 +47,30 @@
 pub const DEFAULT_GENERATE_MODEL_URI: &str =
     "hf:tobil/qmd-query-expansion-1.7B-gguf/qmd-query-expansion-1.7B-q4_k_m.gguf";
 
+/// Process-wide llama backend, shared by every engine.
+static BACKEND: OnceLock<LlamaBackend> = OnceLock::new();
+
+/// Get the shared llama backend, initializing it on first use.
+///
+/// `LlamaBackend::init` may only be called once per process, so engines that
+/// coexist (embedding, generation and rerank in a hybrid query) must share it.
+fn llama_backend() -> Result<&'static LlamaBackend> {
+    /// Serializes initialization so only one thread calls `init`.
+    static INIT: Mutex<()> = Mutex::new(());
+
+    if let Some(backend) = BACKEND.get() {
+        return Ok(backend);
+    }
+    let _guard = INIT
+        .lock()
+        .map_err(|_| anyhow::anyhow!("llama backend initialization poisoned"))?;
+    if let Some(backend) = BACKEND.get() {
+        return Ok(backend);
+    }
+    let backend = LlamaBackend::init()?;
+    Ok(BACKEND.get_or_init(|| backend))
+}
+
 /// Chunk size in tokens for document splitting
 pub const CHUNK_SIZE_TOKENS: usize = 800;
 
@@ -152,8 +177,8 @@ pub fn render_progress_bar(percent: f64, width: usize) -> String {
 
 /// Embedding engine for generating document vectors.
 pub struct EmbeddingEngine {
-    /// Llama backend instance
-    backend: LlamaBackend,
+    /// Shared llama backend
+    backend: &'static LlamaBackend,
     /// The loaded LLM model
     model: Arc<LlamaModel>,
     /// Model dimensions (set after first embedding)
@@ -195,11 +220,11 @@ impl EmbeddingEngine {
     /// # Errors
     /// Returns an error if the model cannot be loaded.
     pub fn new(model_path: &Path) -> Result<Self> {
-        let backend = LlamaBackend::init()?;
+        let backend = llama_backend()?;
 
         let model_params = LlamaModelParams::default();
 
-        let model = LlamaModel::load_from_file(&backend, model_path, &model_params)
+        let model = LlamaModel::load_from_file(backend, model_path, &model_params)
             .with_context(|| format!("Failed to load model from {}", model_path.display()))?;
 
         Ok(Self {
@@ -218,6 +243,11 @@ pub fn load_default() -> Result<Self> {
         Self::new(&model_path)
     }
 
+    /// Check if embedding model exists.
+    pub fn is_available() -> bool {
+        model_exists(DEFAULT_EMBED_MODEL)
+    }
+
     /// Generate an embedding for the given text.
     ///
     /// # Arguments
@@ -289,7 +319,7 @@ fn embed_raw(&mut self, text: &str) -> Result<EmbeddingResult> {
 
         let mut ctx = self
             .model
-            .new_context(&self.backend, ctx_params)
+            .new_context(self.backend, ctx_params)
             .context("Failed to create context")?;
 
         // Create batch and add tokens
@@ -680,6 +710,18 @@ pub enum QueryType {
     Hyde,
 }
 
+impl QueryType {
+    /// Short lowercase name.
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Lex => "lex",
+            Self::Vec => "vec",
+            Self::Hyde => "hyde",
+        }
+    }
+}
+
 /// A single query with its target backend type.
 #[derive(Debug, Clone)]
 pub struct Queryable {
@@ -729,6 +771,54 @@ pub struct RerankDocument {
     pub title: Option<String>,
 }
 
+/// How [`RerankEngine`] scores documents.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RerankMode {
+    /// Score each query/document pair jointly from the model's yes/no logits.
+    #[default]
+    CrossEncoder,
+    /// Cosine similarity of separately embedded query and document. Only a
+    /// fallback for models that are not yes/no rerankers.
+    Embedding,
+}
+
+/// Maximum tokens in a single cross-encoder prompt; longer documents are truncated.
+pub const RERANK_MAX_TOKENS: usize = 1024;
+
+/// Query/document pairs scored together in one context batch.
+pub const RERANK_BATCH_SIZE: usize = 4;
+
+/// Task instruction given to the reranker.
+const RERANK_INSTRUCTION: &str =
+    "Given a search query, retrieve relevant passages that answer the query";
+
+/// Prompt text after the document, up to where the model answers yes or no.
+const RERANK_PROMPT_SUFFIX: &str = "<|im_end|>\n<|im_start|>assistant\n<think>\n\n</think>\n\n";
+
+/// Prompt text before the document, in the Qwen3-Reranker chat template.
+fn rerank_prompt_prefix(query: &str) -> String {
+    format!(
+        "<|im_start|>system\nJudge whether the Document meets the requirements based on the \
+         Query and the Instruct provided. Note that the answer can only be \"yes\" or \"no\".\
+         <|im_end|>\n<|im_start|>user\n<Instruct>: {RERANK_INSTRUCTION}\n<Query>: {query}\n\
+         <Document>: "
+    )
+}
+
+/// Document text as shown to the reranker.
+fn rerank_document_text(doc: &RerankDocument) -> String {
+    match &doc.title {
+        Some(title) if !title.is_empty() => format!("{title}\n{}", doc.text),
+        _ => doc.text.clone(),
+    }
+}
+
+/// Relevance as the probability of "yes" over "no" given their logits.
+#[must_use]
+pub fn yes_probability(yes_logit: f32, no_logit: f32) -> f32 {
+    1.0 / (1.0 + (no_logit - yes_logit).exp())
+}
+
 /// Rerank result for a single document.
 #[derive(Debug, Clone)]
 pub struct RerankResult {
@@ -1019,6 +1109,19 @@ pub struct RrfResult {
     pub best_rank: usize,
 }
 
+/// Position-aware RRF bonus for a document's best rank (0-indexed).
+///
+/// Protects top retrieval results from reranker disagreement.
+#[must_use]
+pub const fn rrf_position_bonus(best_rank: usize) -> f64 {
+    match best_rank {
+        0..=2 => 0.08,   // Top 3: high protection
+        3..=9 => 0.04,   // Rank 4-10: medium protection
+        10..=19 => 0.01, // Rank 11-20: low protection
+        _ => 0.0,
+    }
+}
+
 /// Combine multiple ranked lists using Reciprocal Rank Fusion with position-aware weighting.
 ///
 /// RRF score = sum(weight / (k + rank + 1)) across all lists where doc appears.
@@ -1072,16 +1175,7 @@ pub fn reciprocal_rank_fusion(
     let mut results: Vec<RrfResult> = scores
         .into_iter()
         .map(|(file, (score, display_path, title, body, best_rank))| {
-            // Position-aware bonus: protect top retrieval results
-            // Rank 1-3: 75% trust retrieval (highest bonus)
-            // Rank 4-10: 60% trust retrieval (medium bonus)
-            // Rank 11+: 40% trust retrieval (low bonus)
-            let bonus = match best_rank {
-                0..=2 => 0.08,   // Top 3: high protection
-                3..=9 => 0.04,   // Rank 4-10: medium protection
-                10..=19 => 0.01, // Rank 11-20: low protection
-                _ => 0.0,
-            };
+            let bonus = rrf_position_bonus(best_rank);
 
             RrfResult {
                 file,
@@ -1115,11 +1209,16 @@ pub struct SnippetResult {
 
 /// Extract a relevant snippet from document body.
 ///
+/// Positions are snapped to `char` boundaries, so any UTF-8 body is safe.
+/// Terms are matched ASCII case-insensitively; use
+/// [`Store::add_snippets`](crate::store::Store::add_snippets) for
+/// tokenizer-aware, highlighted snippets of indexed documents.
+///
 /// # Arguments
 /// * `body` - Full document body
 /// * `query` - Search query for context
 /// * `max_chars` - Maximum snippet length
-/// * `chunk_pos` - Optional character position hint (from vector search)
+/// * `chunk_pos` - Optional byte position hint (from vector search)
 #[must_use]
 pub fn extract_snippet(
     body: &str,
@@ -1137,27 +1236,28 @@ pub fn extract_snippet(
     // Get query terms for matching
     let terms: Vec<&str> = query.split_whitespace().filter(|t| t.len() >= 3).collect();
 
-    let body_lower = body.to_lowercase();
+    // ASCII lowercasing keeps byte offsets aligned with `body`.
+    let body_lower = body.to_ascii_lowercase();
 
     // Find best position based on term matches or chunk_pos
-    let start_pos = if let Some(pos) = chunk_pos {
+    let start_pos = body.floor_char_boundary(if let Some(pos) = chunk_pos {
         pos.min(body.len().saturating_sub(max_chars))
     } else {
         // Find first occurrence of any query term
         let mut best_pos = 0;
         for term in &terms {
-            if let Some(pos) = body_lower.find(&term.to_lowercase()) {
+            if let Some(pos) = body_lower.find(&term.to_ascii_lowercase()) {
                 best_pos = pos.saturating_sub(50); // Start 50 chars before match
                 break;
             }
         }
         best_pos
-    };
+    });
 
     // Extend to line boundaries
     let line_start = body[..start_pos].rfind('\n').map_or(0, |p| p + 1);
 
-    let end_pos = (line_start + max_chars).min(body.len());
+    let end_pos = body.floor_char_boundary(line_start + max_chars);
     let line_end = body[end_pos..]
         .find('\n')
         .map_or(body.len(), |p| end_pos + p);
@@ -1225,8 +1325,8 @@ pub fn warning_message(&self) -> Option<String> {
 
 /// Text generation engine using GGUF models.
 pub struct GenerationEngine {
-    /// Llama backend instance
-    backend: LlamaBackend,
+    /// Shared llama backend
+    backend: &'static LlamaBackend,
     /// The loaded LLM model
     model: Arc<LlamaModel>,
 }
@@ -1251,10 +1351,10 @@ pub struct GenerationResult {
 impl GenerationEngine {
     /// Create a new generation engine with the specified model.
     pub fn new(model_path: &Path) -> Result<Self> {
-        let backend = LlamaBackend::init()?;
+        let backend = llama_backend()?;
         let model_params = LlamaModelParams::default();
 
-        let model = LlamaModel::load_from_file(&backend, model_path, &model_params)
+        let model = LlamaModel::load_from_file(backend, model_path, &model_params)
             .with_context(|| format!("Failed to load model from {}", model_path.display()))?;
 
         Ok(Self {
@@ -1282,7 +1382,7 @@ pub fn generate(&self, prompt: &str, max_tokens: usize) -> Result<GenerationResu
 
         let mut ctx = self
             .model
-            .new_context(&self.backend, ctx_params)
+            .new_context(self.backend, ctx_params)
             .context("Failed to create context")?;
 
         // Tokenize prompt
@@ -1372,15 +1472,19 @@ pub fn expand_query(&self, query: &str, include_lexical: bool) -> Result<Vec<Que
 
 /// Reranking engine using cross-encoder models.
 pub struct RerankEngine {
-    /// Llama backend instance
-    backend: LlamaBackend,
+    /// Shared llama backend
+    backend: &'static LlamaBackend,
     /// The loaded rerank model
     model: Arc<LlamaModel>,
+    /// How documents are scored
+    mode: RerankMode,
 }
 
 impl std::fmt::Debug for RerankEngine {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("RerankEngine").finish()
+        f.debug_struct("RerankEngine")
+            .field("mode", &self.mode)
+            .finish_non_exhaustive()
     }
 }
 
@@ -1396,17 +1500,18 @@ pub struct BatchRerankResult {
 impl RerankEngine {
     /// Create a new rerank engine.
     pub fn new(model_path: &Path) -> Result<Self> {
-        let backend = LlamaBackend::init()?;
+        let backend = llama_backend()?;
         let model_params = LlamaModelParams::default();
 
         let model =
-            LlamaModel::load_from_file(&backend, model_path, &model_params).with_context(|| {
+            LlamaModel::load_from_file(backend, model_path, &model_params).with_context(|| {
                 format!("Failed to load rerank model from {}", model_path.display())
             })?;
 
         Ok(Self {
             backend,
             model: Arc::new(model),
+            mode: RerankMode::default(),
         })
     }
 
@@ -1421,70 +1526,171 @@ pub fn is_available() -> bool {
         model_exists(DEFAULT_RERANK_MODEL)
     }
 
-    /// Rerank documents by relevance to a query using embedding similarity.
+    /// Set how documents are scored.
+    #[must_use]
+    pub const fn with_mode(mut self, mode: RerankMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// How documents are scored.
+    #[must_use]
+    pub const fn mode(&self) -> RerankMode {
+        self.mode
+    }
+
+    /// Rerank documents by relevance to a query.
+    ///
+    /// Results are sorted by score, highest first. Cross-encoder scores are
+    /// probabilities in `[0, 1]`.
     pub fn rerank(
         &mut self,
         query: &str,
         documents: &[RerankDocument],
     ) -> Result<BatchRerankResult> {
-        if documents.is_empty() {
-            return Ok(BatchRerankResult {
-                results: Vec::new(),
-                model: DEFAULT_RERANK_MODEL.to_string(),
-            });
-        }
+        let scores = if documents.is_empty() {
+            Vec::new()
+        } else {
+            match self.mode {
+                RerankMode::CrossEncoder => self.cross_encoder_scores(query, documents)?,
+                RerankMode::Embedding => self.embedding_scores(query, documents)?,
+            }
+        };
 
-        // Use embedding-based scoring as a fallback approach
-        // For true cross-encoder reranking, a dedicated reranker model would be needed
-        let ctx_params = LlamaContextParams::default().with_embeddings(true);
+        let mut results: Vec<RerankResult> = documents
+            .iter()
+            .zip(scores)
+            .enumerate()
+            .map(|(index, (doc, score))| RerankResult {
+                file: doc.file.clone(),
+                score,
+                index,
+            })
+            .collect();
 
-        let mut results: Vec<RerankResult> = Vec::new();
+        // Sort by score descending
+        results.sort_by(|a, b| b.score.total_cmp(&a.score));
 
-        // Get query embedding
-        let query_input = format_query_for_embedding(query);
-        let query_embedding = self.get_embedding(&query_input, &ctx_params)?;
+        Ok(BatchRerankResult {
+            results,
+            model: DEFAULT_RERANK_MODEL.to_string(),
+        })
+    }
 
-        // Score each document by embedding similarity
-        for (index, doc) in documents.iter().enumerate() {
-            let doc_input = format_doc_for_embedding(&doc.text, doc.title.as_deref());
-
-            match self.get_embedding(&doc_input, &ctx_params) {
-                Ok(doc_embedding) => {
-                    let score = cosine_similarity(&query_embedding, &doc_embedding);
-                    results.push(RerankResult {
-                        file: doc.file.clone(),
-                        score,
-                        index,
-                    });
-                }
-                Err(_) => {
-                    results.push(RerankResult {
-                        file: doc.file.clone(),
-                        score: 0.0,
-                        index,
-                    });
+    /// Score documents from the yes/no logits of the reranker prompt.
+    ///
+    /// Up to [`RERANK_BATCH_SIZE`] prompts are decoded together, one sequence
+    /// each, and scored from the logits of their last token.
+    fn cross_encoder_scores(&self, query: &str, documents: &[RerankDocument]) -> Result<Vec<f32>> {
+        let yes = self.single_token("yes")?;
+        let no = self.single_token("no")?;
+
+        let prefix = self
+            .model
+            .str_to_token(&rerank_prompt_prefix(query), AddBos::Never)
+            .context("Failed to tokenize rerank prompt")?;
+        let suffix = self
+            .model
+            .str_to_token(RERANK_PROMPT_SUFFIX, AddBos::Never)
+            .context("Failed to tokenize rerank prompt")?;
+        let doc_budget = RERANK_MAX_TOKENS.saturating_sub(prefix.len() + suffix.len());
+        if doc_budget == 0 {
+            bail!("Query is too long to rerank");
+        }
+
+        let prompts = documents
+            .iter()
+            .map(|doc| {
+                let mut doc_tokens = self
+                    .model
+                    .str_to_token(&rerank_document_text(doc), AddBos::Never)
+                    .with_context(|| format!("Failed to tokenize {}", doc.file))?;
+                doc_tokens.truncate(doc_budget);
+                Ok([prefix.as_slice(), &doc_tokens, &suffix].concat())
+            })
+            .collect::<Result<Vec<Vec<LlamaToken>>>>()?;
+
+        // Each sequence gets its own RERANK_MAX_TOKENS slice of the context.
+        let n_ctx = RERANK_MAX_TOKENS * RERANK_BATCH_SIZE;
+        let ctx_params = LlamaContextParams::default()
+            .with_n_ctx(std::num::NonZero::new(n_ctx as u32))
+            .with_n_batch(n_ctx as u32)
+            .with_n_seq_max(RERANK_BATCH_SIZE as u32);
+        let mut ctx = self
+            .model
+            .new_context(self.backend, ctx_params)
+            .context("Failed to create context")?;
+
+        let mut scores = Vec::with_capacity(prompts.len());
+        for group in prompts.chunks(RERANK_BATCH_SIZE) {
+            ctx.clear_kv_cache();
+            let mut batch = LlamaBatch::new(n_ctx, group.len() as i32);
+            let mut last_indices = Vec::with_capacity(group.len());
+            for (seq, tokens) in group.iter().enumerate() {
+                for (i, token) in tokens.iter().enumerate() {
+                    batch.add(*token, i as i32, &[seq as i32], i == tokens.len() - 1)?;
                 }
+                last_indices.push(batch.n_tokens() - 1);
+            }
+
+            ctx.decode(&mut batch)
+                .context("Failed to decode rerank batch")?;
+
+            for idx in last_indices {
+                let logits = ctx.get_logits_ith(idx);
+                let logit = |token: LlamaToken| {
+                    usize::try_from(token.0)
+                        .ok()
+                        .and_then(|i| logits.get(i).copied())
+                };
+                let (Some(yes_logit), Some(no_logit)) = (logit(yes), logit(no)) else {
+                    bail!("Rerank model produced no logits for yes/no");
+                };
+                scores.push(yes_probability(yes_logit, no_logit));
             }
         }
 
-        // Sort by score descending
-        results.sort_by(|a, b| {
-            b.score
-                .partial_cmp(&a.score)
-                .unwrap_or(std::cmp::Ordering::Equal)
-        });
+        Ok(scores)
+    }
 
-        Ok(BatchRerankResult {
-            results,
-            model: DEFAULT_RERANK_MODEL.to_string(),
-        })
+    /// Look up the token for a word the reranker answers with.
+    fn single_token(&self, word: &str) -> Result<LlamaToken> {
+        let tokens = self
+            .model
+            .str_to_token(word, AddBos::Never)
+            .with_context(|| format!("Failed to tokenize {word:?}"))?;
+        match tokens.as_slice() {
+            [token] => Ok(*token),
+            _ => bail!("Rerank model has no single token for {word:?}; use embedding mode"),
+        }
+    }
+
+    /// Score documents by cosine similarity of query and document embeddings.
+    fn embedding_scores(&self, query: &str, documents: &[RerankDocument]) -> Result<Vec<f32>> {
+        let ctx_params = LlamaContextParams::default().with_embeddings(true);
+
+        // Get query embedding
+        let query_input = format_query_for_embedding(query);
+        let query_embedding = self.get_embedding(&query_input, &ctx_params)?;
+
+        // Score each document by embedding similarity; failures score zero
+        Ok(documents
+            .iter()
+            .map(|doc| {
+                let doc_input = format_doc_for_embedding(&doc.text, doc.title.as_deref());
+                self.get_embedding(&doc_input, &ctx_params)
+                    .map_or(0.0, |doc_embedding| {
+                        cosine_similarity(&query_embedding, &doc_embedding)
+                    })
+            })
+            .collect())
     }
 
     /// Get embedding for text.
     fn get_embedding(&self, text: &str, ctx_params: &LlamaContextParams) -> Result<Vec<f32>> {
         let mut ctx = self
             .model
-            .new_context(&self.backend, ctx_params.clone())
+            .new_context(self.backend, ctx_params.clone())
             .context("Failed to create context")?;
 
         let tokens = self
@@ -1564,4 +1770,44 @@ fn test_cosine_similarity() {
         let c = vec![0.0, 1.0, 0.0];
         assert!(cosine_similarity(&a, &c).abs() < 0.001);
     }
+
+    #[test]
+    fn test_yes_probability() {
+        assert!((yes_probability(2.0, 2.0) - 0.5).abs() < 1e-6);
+        assert!(yes_probability(8.0, -8.0) > 0.99);
+        assert!(yes_probability(-8.0, 8.0) < 0.01);
+        assert!(yes_probability(3.0, 1.0) > yes_probability(2.0, 1.0));
+    }
+
+    #[test]
+    fn test_rerank_prompt() {
+        let prefix = rerank_prompt_prefix("rust errors");
+        assert!(prefix.starts_with("<|im_start|>system\n"));
+        assert!(prefix.contains("<Query>: rust errors\n"));
+        assert!(prefix.ends_with("<Document>: "));
+        assert!(RERANK_PROMPT_SUFFIX.ends_with("</think>\n\n"));
+
+        let doc = RerankDocument {
+            file: "a.md".to_string(),
+            text: "body".to_string(),
+            title: Some("Title".to_string()),
+        };
+        assert_eq!(rerank_document_text(&doc), "Title\nbody");
+    }
+
+    #[test]
+    fn test_extract_snippet_multibyte() {
+        let body = format!(
+            "{}\nthe Straße rust line\n{}",
+            "é".repeat(80),
+            "ü".repeat(200)
+        );
+        for query in ["rust", "zzz"] {
+            let result = extract_snippet(&body, query, 61, None);
+            assert!(result.snippet.len() <= body.len());
+        }
+        let result = extract_snippet(&body, "rust", 300, Some(161));
+        assert_eq!(result.line, 2);
+        assert!(result.snippet.starts_with("the Straße"));
+    }
 }
@@ -0,0 +1,254 @@
+//! Heading outlines and sections.
+//!
+//! A document's outline is its ATX headings (`## Title`) outside code
+//! blocks, each with the line range of its section: from the heading up to
+//! the next heading of the same or a higher level. Sections are addressed
+//! by GitHub-style slugs, so `notes/plan.md#next-steps` names the section
+//! under `## Next Steps`; repeated headings get `-1`, `-2`, ... suffixes.
+
+use crate::error::Result;
+use crate::frontmatter::split_frontmatter;
+use crate::store::Store;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// A heading and the line range of its section.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Heading {
+    /// Heading level, 1 to 6.
+    pub level: usize,
+    /// Heading text without the `#` markers.
+    pub text: String,
+    /// Anchor slug, unique within the document.
+    pub slug: String,
+    /// Line of the heading (1-indexed).
+    pub line: usize,
+    /// Last line of the section, inclusive.
+    pub end_line: usize,
+}
+
+/// Anchor slug of a heading, as GitHub generates it: lowercase, spaces as
+/// `-`, punctuation other than `-` and `_` removed.
+#[must_use]
+pub fn slugify(text: &str) -> String {
+    text.trim()
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            '-' | '_' => Some(c),
+            _ if c.is_alphanumeric() => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Level and text of an ATX heading line.
+pub(crate) fn atx_heading(line: &str) -> Option<(usize, &str)> {
+    let trimmed = line.trim_start_matches(' ');
+    if line.len() - trimmed.len() > 3 {
+        return None;
+    }
+    let level = trimmed.chars().take_while(|&c| c == '#').count();
+    let rest = &trimmed[level..];
+    if !(1..=6).contains(&level) || !(rest.is_empty() || rest.starts_with([' ', '\t'])) {
+        return None;
+    }
+    // An optional closing sequence of `#` is not part of the text.
+    let inner = rest.trim();
+    let unclosed = inner.trim_end_matches('#');
+    let text = if unclosed.is_empty() || unclosed.ends_with([' ', '\t']) {
+        unclosed.trim_end()
+    } else {
+        inner
+    };
+    Some((level, text))
+}
+
+/// Extract the heading outline of a document.
+#[must_use]
+pub fn extract_headings(content: &str) -> Vec<Heading> {
+    let body = split_frontmatter(content).1;
+    let first_line = content[..content.len() - body.len()].matches('\n').count() + 1;
+    let last_line = content.lines().count();
+
+    let mut headings: Vec<Heading> = Vec::new();
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    let mut in_fence = false;
+    for (i, line) in body.lines().enumerate() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+            continue;
+        }
+        if in_fence {
+            continue;
+        }
+        let Some((level, text)) = atx_heading(line) else {
+            continue;
+        };
+
+        let number = first_line + i;
+        for open in headings.iter_mut().rev() {
+            if open.end_line != last_line {
+                continue;
+            }
+            if open.level < level {
+                break;
+            }
+            open.end_line = number - 1;
+        }
+
+        let base = slugify(text);
+        let count = seen.entry(base.clone()).or_insert(0);
+        let slug = if *count == 0 {
+            base
+        } else {
+            format!("{base}-{count}")
+        };
+        *count += 1;
+
+        headings.push(Heading {
+            level,
+            text: text.to_string(),
+            slug,
+            line: number,
+            end_line: last_line,
+        });
+    }
+    headings
+}
+
+/// The heading whose section most closely encloses `line`: the last
+/// heading at or before it.
+#[must_use]
+pub fn enclosing_heading(headings: &[Heading], line: usize) -> Option<&Heading> {
+    headings.iter().rev().find(|h| h.line <= line)
+}
+
+/// The heading a `#fragment` names: by slug, or by text ignoring case.
+#[must_use]
+pub fn find_heading<'a>(headings: &'a [Heading], fragment: &str) -> Option<&'a Heading> {
+    let name = fragment.trim_start_matches('#');
+    let slug = slugify(name);
+    headings
+        .iter()
+        .find(|h| h.slug == name || h.slug == slug)
+        .or_else(|| {
+            headings
+                .iter()
+                .find(|h| h.text.eq_ignore_ascii_case(name.trim()))
+        })
+}
+
+impl Store {
+    /// Find a document's section by `#fragment`, see [`find_heading`].
+    pub fn find_section(
+        &self,
+        collection: &str,
+        path: &str,
+        fragment: &str,
+    ) -> Result<Option<Heading>> {
+        Ok(find_heading(&self.get_outline(collection, path)?, fragment).cloned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempStore;
+
+    #[test]
+    fn test_slugify() {
+        assert_eq!(slugify("Next Steps!"), "next-steps");
+        assert_eq!(slugify(" API: v2 (beta) "), "api-v2-beta");
+        assert_eq!(slugify("snake_case & kebab-case"), "snake_case--kebab-case");
+    }
+
+    #[test]
+    fn test_extract_headings() {
+        let content = "---\ntitle: Plan\n---\n# Plan\nintro\n## Goals ##\n- ship\n\
+                       ```\n# not a heading\n```\n### Details\nmore\n## Goals\n#hashtag\n# Next\nend\n";
+        let outline: Vec<_> = extract_headings(content)
+            .into_iter()
+            .map(|h| (h.level, h.text, h.slug, h.line, h.end_line))
+            .collect();
+        assert_eq!(
+            outline,
+            [
+                (1, "Plan".into(), "plan".into(), 4, 14),
+                (2, "Goals".into(), "goals".into(), 6, 12),
+                (3, "Details".into(), "details".into(), 11, 12),
+                (2, "Goals".into(), "goals-1".into(), 13, 14),
+                (1, "Next".into(), "next".into(), 15, 16),
+            ]
+        );
+
+        let headings = extract_headings(content);
+        assert_eq!(enclosing_heading(&headings, 12).unwrap().text, "Details");
+        assert!(enclosing_heading(&headings, 2).is_none());
+        assert_eq!(find_heading(&headings, "#goals-1").unwrap().line, 13);
+        assert_eq!(find_heading(&headings, "Next").unwrap().line, 15);
+        assert!(find_heading(&headings, "missing").is_none());
+    }
+
+    #[test]
+    fn test_outline_follows_documents() {
+        let store = TempStore::new("outline");
+        store.add_doc(
+            "plan-md",
+            "# Plan\nintro\n## Goals\nship it\n## Risks\nnone",
+        );
+        let outline = || {
+            store
+                .get_outline("notes", "plan-md")
+                .unwrap()
+                .into_iter()
+                .map(|h| (h.slug, h.line, h.end_line))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            outline(),
+            [
+                ("plan".into(), 1, 6),
+                ("goals".into(), 3, 4),
+                ("risks".into(), 5, 6)
+            ]
+        );
+        let section = store.find_section("notes", "plan-md", "Risks").unwrap();
+        assert_eq!(section.map(|h| h.line), Some(5));
+
+        store.update_doc("plan-md", "# Plan\n## Next Steps\nsoon");
+        assert_eq!(
+            outline(),
+            [("plan".into(), 1, 3), ("next-steps".into(), 2, 3)]
+        );
+        store.deactivate_document("notes", "plan-md").unwrap();
+        assert!(outline().is_empty());
+    }
+
+    #[test]
+    fn test_search_results_report_enclosing_heading() {
+        let store = TempStore::new("outline-heading");
+        store.add_doc("guide-md", "intro\n# Guide\ntext\n## Install\nrun cargo\n");
+        store.add_doc("flat-md", "plain cargo notes");
+
+        let mut results = store.search_fts("cargo", 10, None).unwrap();
+        store.add_snippets("cargo", &mut results).unwrap();
+        let heading = |path: &str| {
+            let result = results.iter().find(|r| r.doc.path == path).unwrap();
+            result.heading.as_ref().map(|h| h.slug.as_str())
+        };
+        assert_eq!(heading("guide-md"), Some("install"));
+        assert_eq!(heading("flat-md"), None);
+
+        assert!(
+            store
+                .get_enclosing_heading("notes", "guide-md", 1)
+                .unwrap()
+                .is_none()
+        );
+        let guide = store.get_enclosing_heading("notes", "guide-md", 3).unwrap();
+        assert_eq!(guide.map(|h| h.text).as_deref(), Some("Guide"));
+    }
+}
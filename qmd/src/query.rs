@@ -0,0 +1,531 @@
+//! User-facing full-text query syntax.
+//!
+//! Queries are parsed into a small AST and compiled to an FTS5 `MATCH`
+//! expression. Every term is quoted, so punctuation and FTS5 keywords in user
+//! input are searched for literally instead of being interpreted. The explicit
+//! syntax is:
+//!
+//! - `word` matches the word; whitespace-separated terms must all match
+//! - `"exact phrase"` matches the words in order
+//! - `-term` excludes documents matching the term
+//! - `a OR b` matches either side (uppercase `OR` only)
+//! - `term*` matches words starting with `term`
+//! - `title:term` restricts a term to the document title
+//!
+//! Filters such as `collection:` and `path:` are not part of this syntax; they
+//! are split off beforehand by [`SearchFilter::parse`](crate::filter::SearchFilter::parse).
+//!
+//! Matches are ranked with BM25, weighting each indexed column by
+//! [`Bm25Weights`]. The weights can be set globally and per collection in the
+//! YAML config:
+//!
+//! ```yaml
+//! bm25:
+//!   title: 5.0
+//! collections:
+//!   notes:
+//!     path: ~/notes
+//!     pattern: "**/*.md"
+//!     bm25:
+//!       body: 2.0
+//! ```
+
+use crate::collections::load_config;
+use crate::error::{QmdError, Result};
+use rusqlite::types::Value;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// BM25 weight of each `documents_fts` column.
+///
+/// Omitted fields take their default, which favours title and path matches
+/// over passing mentions in the body.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Bm25Weights {
+    /// Weight of the `collection/path` column.
+    pub path: f64,
+    /// Weight of the title column.
+    pub title: f64,
+    /// Weight of the body column.
+    pub body: f64,
+}
+
+impl Default for Bm25Weights {
+    fn default() -> Self {
+        Self {
+            path: 2.0,
+            title: 4.0,
+            body: 1.0,
+        }
+    }
+}
+
+impl Bm25Weights {
+    /// Weights in `documents_fts` column order.
+    const fn columns(self) -> [f64; 3] {
+        [self.path, self.title, self.body]
+    }
+
+    /// Check that every weight is finite and not negative.
+    fn validate(self, scope: &str) -> Result<Self> {
+        if self.columns().iter().all(|w| w.is_finite() && *w >= 0.0) {
+            Ok(self)
+        } else {
+            Err(QmdError::Config(format!(
+                "BM25 weights for {scope} must be non-negative numbers"
+            )))
+        }
+    }
+}
+
+/// Options for full-text search.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FtsSearchOptions {
+    /// Weights for collections without an override.
+    pub weights: Bm25Weights,
+    /// Per-collection weight overrides.
+    pub collection_weights: BTreeMap<String, Bm25Weights>,
+}
+
+impl FtsSearchOptions {
+    /// Options from the YAML config: the global `bm25` weights and the
+    /// `bm25` overrides of each collection.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the config cannot be loaded or a weight is
+    /// negative or not a number.
+    pub fn from_config() -> Result<Self> {
+        let config = load_config()?;
+        let weights = config
+            .bm25
+            .unwrap_or_default()
+            .validate("all collections")?;
+        let mut collection_weights = BTreeMap::new();
+        for (name, collection) in config.collections {
+            if let Some(overrides) = collection.bm25 {
+                let scope = format!("collection '{name}'");
+                collection_weights.insert(name, overrides.validate(&scope)?);
+            }
+        }
+        Ok(Self {
+            weights,
+            collection_weights,
+        })
+    }
+
+    /// Compile to a `bm25(documents_fts, ...)` call over the `documents`
+    /// table aliased as `alias`, numbering parameters from `first_param`.
+    ///
+    /// Collection overrides become a `CASE` on the collection column, since
+    /// FTS5 reads the weights for every row.
+    pub(crate) fn bm25_sql(&self, alias: &str, first_param: usize) -> (String, Vec<Value>) {
+        let mut params: Vec<Value> = Vec::new();
+        let mut next_param = |value: Value| {
+            params.push(value);
+            format!("?{}", first_param + params.len() - 1)
+        };
+
+        let args: Vec<String> = (0..3)
+            .map(|column| {
+                let default = next_param(Value::Real(self.weights.columns()[column]));
+                if self.collection_weights.is_empty() {
+                    return default;
+                }
+                let mut case = format!("CASE {alias}.collection");
+                for (name, weights) in &self.collection_weights {
+                    let when = next_param(Value::Text(name.clone()));
+                    let then = next_param(Value::Real(weights.columns()[column]));
+                    case.push_str(&format!(" WHEN {when} THEN {then}"));
+                }
+                case.push_str(&format!(" ELSE {default} END"));
+                case
+            })
+            .collect();
+
+        (format!("bm25(documents_fts, {})", args.join(", ")), params)
+    }
+}
+
+/// Column a term is restricted to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueryField {
+    /// Document title.
+    Title,
+}
+
+impl QueryField {
+    /// Parse a field prefix as written by the user.
+    fn from_prefix(prefix: &str) -> Option<Self> {
+        match prefix {
+            "title" => Some(Self::Title),
+            _ => None,
+        }
+    }
+
+    /// FTS5 column name.
+    const fn column(self) -> &'static str {
+        match self {
+            Self::Title => "title",
+        }
+    }
+}
+
+/// A single search term.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryTerm {
+    /// Term text, without quotes or operators.
+    pub text: String,
+    /// Whether the text is a quoted phrase.
+    pub phrase: bool,
+    /// Whether the term matches word prefixes (`term*`).
+    pub prefix: bool,
+    /// Column the term is restricted to.
+    pub field: Option<QueryField>,
+    /// Whether matching documents are excluded (`-term`).
+    pub negated: bool,
+}
+
+impl QueryTerm {
+    /// Compile to an FTS5 phrase, optionally scoped to a column.
+    fn to_fts5(&self) -> String {
+        let mut out = String::new();
+        if let Some(field) = self.field {
+            out.push_str(field.column());
+            out.push_str(" : ");
+        }
+        out.push('"');
+        out.push_str(&self.text.replace('"', "\"\""));
+        out.push('"');
+        if self.prefix {
+            out.push('*');
+        }
+        out
+    }
+}
+
+/// A parsed full-text query: alternatives joined by `OR`, each a list of
+/// terms that must all match.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FtsQuery {
+    /// Alternatives; a document matches if any alternative matches.
+    pub alternatives: Vec<Vec<QueryTerm>>,
+}
+
+impl FtsQuery {
+    /// Parse a user query.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`QmdError::InvalidQuery`] for an empty query, an unterminated
+    /// quote, a dangling `-`, `*` or `OR`, or an alternative made only of
+    /// excluded terms.
+    pub fn parse(input: &str) -> Result<Self> {
+        let mut alternatives: Vec<Vec<QueryTerm>> = vec![Vec::new()];
+        let mut chars = input.chars().peekable();
+
+        loop {
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+            let Some(&first) = chars.peek() else {
+                break;
+            };
+
+            // Leading `-` excludes the term.
+            let negated = first == '-';
+            if negated {
+                chars.next();
+                if chars.peek().is_none_or(|c| c.is_whitespace()) {
+                    return Err(invalid("'-' must be followed by a term"));
+                }
+            }
+
+            let term = if chars.peek() == Some(&'"') {
+                chars.next();
+                let text = read_phrase(&mut chars)?;
+                QueryTerm {
+                    text,
+                    phrase: true,
+                    prefix: false,
+                    field: None,
+                    negated,
+                }
+            } else {
+                let mut word = String::new();
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                    word.push(c);
+                    // A quote after a field prefix starts a scoped phrase.
+                    if c == ':' && chars.peek() == Some(&'"') {
+                        break;
+                    }
+                }
+
+                if word == "OR" && !negated {
+                    if alternatives.last().is_some_and(Vec::is_empty) {
+                        return Err(invalid("'OR' must be between two terms"));
+                    }
+                    alternatives.push(Vec::new());
+                    continue;
+                }
+                parse_word(&word, negated, &mut chars)?
+            };
+
+            if let Some(current) = alternatives.last_mut() {
+                current.push(term);
+            }
+        }
+
+        if alternatives.iter().all(Vec::is_empty) {
+            return Err(invalid("query is empty"));
+        }
+        if alternatives.last().is_some_and(Vec::is_empty) {
+            return Err(invalid("'OR' must be between two terms"));
+        }
+        if alternatives.iter().any(|alt| alt.iter().all(|t| t.negated)) {
+            return Err(invalid(
+                "every alternative needs a term that is not excluded",
+            ));
+        }
+
+        Ok(Self { alternatives })
+    }
+
+    /// Compile to an FTS5 `MATCH` expression.
+    #[must_use]
+    pub fn to_fts5(&self) -> String {
+        let compiled: Vec<String> = self
+            .alternatives
+            .iter()
+            .map(|alternative| {
+                let required: Vec<String> = alternative
+                    .iter()
+                    .filter(|t| !t.negated)
+                    .map(QueryTerm::to_fts5)
+                    .collect();
+                let mut out = required.join(" AND ");
+                if required.len() > 1 && alternative.iter().any(|t| t.negated) {
+                    out = format!("({out})");
+                }
+                for excluded in alternative.iter().filter(|t| t.negated) {
+                    out.push_str(" NOT ");
+                    out.push_str(&excluded.to_fts5());
+                }
+                out
+            })
+            .collect();
+
+        if compiled.len() == 1 {
+            compiled.concat()
+        } else {
+            compiled
+                .iter()
+                .map(|alternative| format!("({alternative})"))
+                .collect::<Vec<_>>()
+                .join(" OR ")
+        }
+    }
+
+    /// Compile to an FTS5 expression matching any term that is not excluded,
+    /// ignoring field scopes. Used to locate matches for highlighting.
+    #[must_use]
+    pub fn to_fts5_any(&self) -> String {
+        self.alternatives
+            .iter()
+            .flatten()
+            .filter(|t| !t.negated)
+            .map(|t| {
+                QueryTerm {
+                    field: None,
+                    ..t.clone()
+                }
+                .to_fts5()
+            })
+            .collect::<Vec<_>>()
+            .join(" OR ")
+    }
+
+    /// Text of every term that is not excluded, in query order.
+    #[must_use]
+    pub fn positive_terms(&self) -> Vec<&str> {
+        self.alternatives
+            .iter()
+            .flatten()
+            .filter(|t| !t.negated)
+            .map(|t| t.text.as_str())
+            .collect()
+    }
+}
+
+/// Build an invalid query error.
+fn invalid(message: &str) -> QmdError {
+    QmdError::InvalidQuery(message.to_string())
+}
+
+/// Read a quoted phrase up to its closing quote; the opening quote has
+/// already been consumed.
+fn read_phrase(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) -> Result<String> {
+    let mut text = String::new();
+    for c in chars.by_ref() {
+        if c == '"' {
+            return Ok(text);
+        }
+        text.push(c);
+    }
+    Err(invalid("unterminated quote"))
+}
+
+/// Parse a bare word, which may carry a field prefix or a trailing `*`.
+///
+/// A word ending in `field:` is followed by a quoted phrase.
+fn parse_word(
+    word: &str,
+    negated: bool,
+    chars: &mut std::iter::Peekable<std::str::Chars<'_>>,
+) -> Result<QueryTerm> {
+    let (field, rest) = match word.split_once(':') {
+        Some((prefix, rest)) => match QueryField::from_prefix(prefix) {
+            Some(field) => (Some(field), rest),
+            None => (None, word),
+        },
+        None => (None, word),
+    };
+
+    if field.is_some() && rest.is_empty() {
+        if chars.next_if_eq(&'"').is_none() {
+            return Err(invalid("field prefix must be followed by a term"));
+        }
+        return Ok(QueryTerm {
+            text: read_phrase(chars)?,
+            phrase: true,
+            prefix: false,
+            field,
+            negated,
+        });
+    }
+
+    let (text, prefix) = rest
+        .strip_suffix('*')
+        .map_or((rest, false), |stripped| (stripped, true));
+    if text.is_empty() {
+        return Err(invalid("'*' must follow a term"));
+    }
+
+    Ok(QueryTerm {
+        text: text.to_string(),
+        phrase: false,
+        prefix,
+        field,
+        negated,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compile(input: &str) -> String {
+        FtsQuery::parse(input).unwrap().to_fts5()
+    }
+
+    #[test]
+    fn test_plain_words_are_quoted() {
+        assert_eq!(compile("rust programming"), r#""rust" AND "programming""#);
+        assert_eq!(compile("foo-bar (baz)"), r#""foo-bar" AND "(baz)""#);
+        assert_eq!(compile("AND NEAR not"), r#""AND" AND "NEAR" AND "not""#);
+        assert_eq!(compile("say \"hi\"x"), r#""say" AND "hi" AND "x""#);
+    }
+
+    #[test]
+    fn test_phrases_prefixes_and_exclusions() {
+        assert_eq!(compile("\"error handling\""), r#""error handling""#);
+        assert_eq!(compile("optim*"), r#""optim"*"#);
+        assert_eq!(compile("rust -async"), r#""rust" NOT "async""#);
+        assert_eq!(
+            compile("rust tokio -\"async await\""),
+            r#"("rust" AND "tokio") NOT "async await""#
+        );
+    }
+
+    #[test]
+    fn test_or_and_fields() {
+        assert_eq!(compile("cats OR dogs"), r#"("cats") OR ("dogs")"#);
+        assert_eq!(compile("or"), r#""or""#);
+        assert_eq!(
+            compile("title:rust title:notes*"),
+            r#"title : "rust" AND title : "notes"*"#
+        );
+        assert_eq!(compile("path:notes"), r#""path:notes""#);
+        assert_eq!(compile("title:\"the book\""), r#"title : "the book""#);
+        assert_eq!(compile("http://x"), r#""http://x""#);
+    }
+
+    #[test]
+    fn test_any_term() {
+        let query = FtsQuery::parse("title:rust async -go OR tokio*").unwrap();
+        assert_eq!(query.to_fts5_any(), r#""rust" OR "async" OR "tokio"*"#);
+    }
+
+    #[test]
+    fn test_quotes_are_escaped() {
+        assert_eq!(compile("it\"s"), r#""it""s""#);
+        let query = FtsQuery::parse("a\"b\"").unwrap();
+        assert_eq!(query.positive_terms(), ["a\"b\""]);
+    }
+
+    #[test]
+    fn test_bm25_sql() {
+        let options = FtsSearchOptions::default();
+        let (sql, params) = options.bm25_sql("d", 2);
+        assert_eq!(sql, "bm25(documents_fts, ?2, ?3, ?4)");
+        assert_eq!(
+            params,
+            [Value::Real(2.0), Value::Real(4.0), Value::Real(1.0)]
+        );
+
+        let mut options = FtsSearchOptions::default();
+        options.collection_weights.insert(
+            "code".to_string(),
+            Bm25Weights {
+                path: 8.0,
+                ..Bm25Weights::default()
+            },
+        );
+        let (sql, params) = options.bm25_sql("d", 1);
+        assert!(
+            sql.starts_with("bm25(documents_fts, CASE d.collection WHEN ?2 THEN ?3 ELSE ?1 END,")
+        );
+        assert_eq!(params.len(), 9);
+        assert_eq!(params[2], Value::Real(8.0));
+    }
+
+    #[test]
+    fn test_bm25_weights_from_yaml() {
+        let weights: Bm25Weights = serde_yaml::from_str("title: 10").unwrap();
+        assert_eq!(
+            weights,
+            Bm25Weights {
+                title: 10.0,
+                ..Bm25Weights::default()
+            }
+        );
+        let negative = Bm25Weights {
+            body: -1.0,
+            ..Bm25Weights::default()
+        };
+        assert!(negative.validate("test").is_err());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for input in [
+            "", "   ", "\"open", "-", "rust -", "*", "OR rust", "rust OR", "-rust",
+        ] {
+            assert!(
+                matches!(FtsQuery::parse(input), Err(QmdError::InvalidQuery(_))),
+                "{input:?} should not parse"
+            );
+        }
+        assert!(FtsQuery::parse("title:").is_err());
+        assert!(FtsQuery::parse("rust OR -go").is_err());
+    }
+}
@@ -0,0 +1,868 @@
+//! Hybrid query pipeline.
+//!
+//! Expands a query into lexical and semantic variants, retrieves candidates
+//! with FTS and vector search, fuses the ranked lists with Reciprocal Rank
+//! Fusion and optionally reranks the top of the fused list. This is the single
+//! implementation behind `qmd qsearch` and the MCP `query` and `qsearch` tools.
+//!
+//! The reranker scores passages rather than whole documents: the chunks that
+//! vector search matched and the window with the most query-term hits. Passage
+//! scores are aggregated back to a document score, and the best passage is
+//! reported on the result. The document's rerank score is then blended with
+//! its fused retrieval rank according to [`RerankBlend`].
+//!
+//! Model failures degrade the pipeline instead of failing it: a failed
+//! expansion falls back to the original query, a missing embedding model
+//! skips vector retrieval, a variant that fails to embed is skipped and a
+//! failed rerank keeps the fused order. Malformed FTS syntax is an error in
+//! the user's query but only drops a generated variant.
+
+use crate::error::{QmdError, Result};
+use crate::filter::SearchFilter;
+use crate::llm::{
+    CHUNK_OVERLAP_CHARS, CHUNK_SIZE_CHARS, EmbeddingEngine, GenerationEngine, QueryType, Queryable,
+    RerankDocument, RerankEngine, rrf_position_bonus,
+};
+use crate::query::FtsSearchOptions;
+use crate::store::{ListContribution, Passage, ScoreProvenance, SearchResult, SearchSource, Store};
+use crate::vector::{ChunkAggregation, VecSearchOptions};
+use std::collections::HashMap;
+
+/// Maximum passage length in bytes, matching the embedding chunk size so a
+/// passage fits the reranker context.
+const PASSAGE_CHARS: usize = CHUNK_SIZE_CHARS;
+
+/// Options for a hybrid query.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HybridOptions {
+    /// Maximum number of results to return.
+    pub limit: usize,
+    /// Expand the query with the generation model.
+    pub expand: bool,
+    /// Rerank the fused candidates with the rerank model.
+    pub rerank: bool,
+    /// RRF weight of lexical result lists.
+    pub lex_weight: f64,
+    /// RRF weight of vector result lists.
+    pub vec_weight: f64,
+    /// Extra weight multiplier for the lists of the original query.
+    pub original_weight: f64,
+    /// RRF `k` parameter.
+    pub k: usize,
+    /// Candidates retrieved per list and passed to the reranker.
+    pub candidates: usize,
+    /// Passages per candidate scored by the reranker.
+    pub rerank_passages: usize,
+    /// How passage scores are combined into a document's rerank score.
+    pub passage_aggregation: ChunkAggregation,
+    /// How rerank scores are combined with the fused retrieval rank.
+    pub blend: RerankBlend,
+    /// Restrict candidates of every list before fusion.
+    pub filter: SearchFilter,
+    /// BM25 weights of the lexical lists.
+    pub fts: FtsSearchOptions,
+    /// Keep document bodies on the returned results.
+    pub include_body: bool,
+}
+
+impl Default for HybridOptions {
+    fn default() -> Self {
+        Self {
+            limit: 10,
+            expand: true,
+            rerank: true,
+            lex_weight: 1.0,
+            vec_weight: 1.0,
+            original_weight: 2.0,
+            k: 60,
+            candidates: 20,
+            rerank_passages: 3,
+            passage_aggregation: ChunkAggregation::Max,
+            blend: RerankBlend::default(),
+            filter: SearchFilter::default(),
+            fts: FtsSearchOptions::default(),
+            include_body: false,
+        }
+    }
+}
+
+impl HybridOptions {
+    /// Effective candidate pool size; never smaller than the result limit.
+    fn pool_size(&self) -> usize {
+        self.candidates.max(self.limit)
+    }
+}
+
+/// How rerank scores are combined with the fused retrieval rank.
+///
+/// The retrieval side is the reciprocal fused rank `1 / (rank + 1)`, which is
+/// on the same `[0, 1]` scale as cross-encoder scores.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum RerankBlend {
+    /// Order by rerank score alone.
+    Replace,
+    /// Interpolate with a fixed weight on the rerank score (`0.0..=1.0`).
+    Weighted(f64),
+    /// Trust retrieval more at the top of the fused list: the rerank score
+    /// weighs 25% for ranks 1-3, 40% for ranks 4-10 and 60% below.
+    #[default]
+    PositionAware,
+}
+
+impl RerankBlend {
+    /// Weight of the rerank score for a candidate at `fused_rank` (0-indexed).
+    #[must_use]
+    pub const fn rerank_weight(self, fused_rank: usize) -> f64 {
+        match self {
+            Self::Replace => 1.0,
+            Self::Weighted(weight) => weight.clamp(0.0, 1.0),
+            Self::PositionAware => match fused_rank {
+                0..3 => 0.25,
+                3..10 => 0.4,
+                _ => 0.6,
+            },
+        }
+    }
+
+    /// Blend a rerank score with the fused retrieval rank (0-indexed).
+    #[must_use]
+    pub fn blend(self, fused_rank: usize, rerank_score: f64) -> f64 {
+        let weight = self.rerank_weight(fused_rank);
+        let retrieval = 1.0 / (fused_rank + 1) as f64;
+        weight.mul_add(rerank_score, (1.0 - weight) * retrieval)
+    }
+}
+
+/// Models used by the hybrid pipeline.
+///
+/// Each engine is optional; the pipeline skips the stages whose model is
+/// missing.
+#[derive(Debug, Default)]
+pub struct HybridEngines {
+    /// Embedding model for vector retrieval.
+    pub embedder: Option<EmbeddingEngine>,
+    /// Generation model for query expansion.
+    pub generator: Option<GenerationEngine>,
+    /// Rerank model.
+    pub reranker: Option<RerankEngine>,
+}
+
+impl HybridEngines {
+    /// Load the default models needed for `options`, skipping any that are
+    /// not downloaded or fail to load.
+    #[must_use]
+    pub fn load(options: &HybridOptions) -> Self {
+        let mut engines = Self::default();
+        engines.ensure(options);
+        engines
+    }
+
+    /// Load whichever models needed for `options` are not loaded yet.
+    ///
+    /// Models already loaded are kept, so long-lived callers can reuse them
+    /// across queries.
+    pub fn ensure(&mut self, options: &HybridOptions) {
+        if self.embedder.is_none() && EmbeddingEngine::is_available() {
+            self.embedder = EmbeddingEngine::load_default().ok();
+        }
+        if options.expand && self.generator.is_none() && GenerationEngine::is_available() {
+            self.generator = GenerationEngine::load_default().ok();
+        }
+        if options.rerank && self.reranker.is_none() && RerankEngine::is_available() {
+            self.reranker = RerankEngine::load_default().ok();
+        }
+    }
+
+    /// Whether no model is loaded.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.embedder.is_none() && self.generator.is_none() && self.reranker.is_none()
+    }
+}
+
+/// A ranked result list produced by one query variant.
+struct RankedList {
+    /// Query variant the list was retrieved with.
+    query: Queryable,
+    /// Retrieval backend that produced the list.
+    source: SearchSource,
+    /// RRF weight of the list.
+    weight: f64,
+    /// Results in rank order.
+    results: Vec<SearchResult>,
+}
+
+/// Runs hybrid queries against a store.
+///
+/// Engines are borrowed so callers can keep models loaded across queries.
+#[derive(Debug)]
+pub struct HybridSearcher<'a> {
+    /// Document store.
+    store: &'a Store,
+    /// Embedding model for vector retrieval.
+    embedder: Option<&'a mut EmbeddingEngine>,
+    /// Generation model for query expansion.
+    generator: Option<&'a GenerationEngine>,
+    /// Rerank model.
+    reranker: Option<&'a mut RerankEngine>,
+}
+
+impl<'a> HybridSearcher<'a> {
+    /// Create a searcher without any models (lexical retrieval only).
+    #[must_use]
+    pub const fn new(store: &'a Store) -> Self {
+        Self {
+            store,
+            embedder: None,
+            generator: None,
+            reranker: None,
+        }
+    }
+
+    /// Use an embedding model for vector retrieval.
+    #[must_use]
+    pub const fn with_embedder(mut self, embedder: &'a mut EmbeddingEngine) -> Self {
+        self.embedder = Some(embedder);
+        self
+    }
+
+    /// Use a generation model for query expansion.
+    #[must_use]
+    pub const fn with_generator(mut self, generator: &'a GenerationEngine) -> Self {
+        self.generator = Some(generator);
+        self
+    }
+
+    /// Use a rerank model.
+    #[must_use]
+    pub const fn with_reranker(mut self, reranker: &'a mut RerankEngine) -> Self {
+        self.reranker = Some(reranker);
+        self
+    }
+
+    /// Use every model loaded in `engines`.
+    #[must_use]
+    pub const fn with_engines(mut self, engines: &'a mut HybridEngines) -> Self {
+        self.embedder = engines.embedder.as_mut();
+        self.generator = engines.generator.as_ref();
+        self.reranker = engines.reranker.as_mut();
+        self
+    }
+
+    /// Expand `query` into the variants to retrieve with.
+    ///
+    /// The original query is always searched both lexically and semantically;
+    /// generated variants are appended without duplicates.
+    #[must_use]
+    pub fn expand(&self, query: &str, options: &HybridOptions) -> Vec<Queryable> {
+        let mut queries = vec![Queryable::lex(query), Queryable::vec(query)];
+        if options.expand
+            && let Some(generator) = self.generator
+        {
+            let expanded = generator
+                .expand_query(query, true)
+                .unwrap_or_else(|_| crate::llm::expand_query_simple(query));
+            for q in expanded {
+                let duplicate = queries
+                    .iter()
+                    .any(|existing| existing.query_type == q.query_type && existing.text == q.text);
+                if !duplicate {
+                    queries.push(q);
+                }
+            }
+        }
+        queries
+    }
+
+    /// Run a hybrid query.
+    ///
+    /// Results carry hash, docid, context and a [`ScoreProvenance`]. The score
+    /// is the rerank score blended with the fused rank when reranking ran and
+    /// the fused RRF score otherwise.
+    pub fn search(&mut self, query: &str, options: &HybridOptions) -> Result<Vec<SearchResult>> {
+        let queries = self.expand(query, options);
+        let lists = self.retrieve(&queries, options)?;
+
+        let mut fused = fuse(&lists, options.k);
+        fused.truncate(options.pool_size());
+
+        for result in &mut fused {
+            if result.doc.body.is_none() {
+                result.doc.body = self.store.get_content(&result.doc.hash)?;
+            }
+        }
+
+        if options.rerank
+            && let Some(reranker) = self.reranker.as_deref_mut()
+        {
+            let passages = candidate_passages(&fused, &lists, &queries, options.rerank_passages);
+            fused = rerank(
+                reranker,
+                query,
+                fused,
+                &passages,
+                options.passage_aggregation,
+                options.blend,
+            );
+        }
+
+        fused.truncate(options.limit);
+        if !options.include_body {
+            for result in &mut fused {
+                result.doc.body = None;
+            }
+        }
+        Ok(fused)
+    }
+
+    /// Retrieve one ranked list per query variant.
+    fn retrieve(
+        &mut self,
+        queries: &[Queryable],
+        options: &HybridOptions,
+    ) -> Result<Vec<RankedList>> {
+        let pool = options.pool_size();
+        let mut lists = Vec::with_capacity(queries.len());
+
+        for (i, q) in queries.iter().enumerate() {
+            // The first two variants are the original query.
+            let boost = if i < 2 { options.original_weight } else { 1.0 };
+            match q.query_type {
+                QueryType::Lex => {
+                    let results = match self.store.search_fts_with(
+                        &q.text,
+                        pool,
+                        &options.filter,
+                        &options.fts,
+                    ) {
+                        Ok(results) => results,
+                        // A generated variant in malformed FTS syntax yields no
+                        // lexical candidates; the user's own query must parse.
+                        Err(QmdError::InvalidQuery(_)) if i >= 2 => Vec::new(),
+                        Err(e) => return Err(e),
+                    };
+                    lists.push(RankedList {
+                        query: q.clone(),
+                        source: SearchSource::Fts,
+                        weight: options.lex_weight * boost,
+                        results,
+                    });
+                }
+                QueryType::Vec | QueryType::Hyde => {
+                    let Some(embedder) = self.embedder.as_deref_mut() else {
+                        continue;
+                    };
+                    let embedding = match embedder.embed_query(&q.text) {
+                        Ok(embedding) => embedding,
+                        Err(e) => {
+                            tracing::warn!("Embedding query {:?} failed, skipping it: {e}", q.text);
+                            continue;
+                        }
+                    };
+                    let results = self.store.search_vec_with(
+                        &embedding.embedding,
+                        pool,
+                        &options.filter,
+                        &VecSearchOptions::default(),
+                    )?;
+                    lists.push(RankedList {
+                        query: q.clone(),
+                        source: SearchSource::Vec,
+                        weight: options.vec_weight * boost,
+                        results,
+                    });
+                }
+            }
+        }
+        Ok(lists)
+    }
+}
+
+/// Fuse ranked lists with Reciprocal Rank Fusion, keyed by document path.
+///
+/// Returns hybrid results sorted by fused score, each with its provenance.
+fn fuse(lists: &[RankedList], k: usize) -> Vec<SearchResult> {
+    let mut fused: HashMap<String, SearchResult> = HashMap::new();
+
+    for list in lists {
+        for (rank, result) in list.results.iter().enumerate() {
+            let entry = fused
+                .entry(result.doc.filepath.clone())
+                .or_insert_with(|| SearchResult {
+                    doc: result.doc.clone(),
+                    score: 0.0,
+                    source: SearchSource::Hybrid,
+                    chunk_pos: None,
+                    chunk_seq: None,
+                    provenance: Some(ScoreProvenance::default()),
+                    passage: None,
+                    snippets: Vec::new(),
+                    heading: None,
+                });
+            let provenance = entry.provenance.get_or_insert_with(Default::default);
+            let contribution = list.weight / (k + rank + 1) as f64;
+            provenance.rrf_score += contribution;
+            provenance.lists.push(ListContribution {
+                query_type: list.query.query_type,
+                query: list.query.text.clone(),
+                rank,
+                score: result.score,
+                weight: list.weight,
+                contribution,
+            });
+
+            match list.source {
+                SearchSource::Vec => {
+                    if provenance.vec_rank.is_none_or(|r| rank < r) {
+                        provenance.vec_rank = Some(rank);
+                        entry.chunk_pos = result.chunk_pos;
+                        entry.chunk_seq = result.chunk_seq;
+                    }
+                    if provenance.vec_score.is_none_or(|s| result.score > s) {
+                        provenance.vec_score = Some(result.score);
+                    }
+                }
+                SearchSource::Fts | SearchSource::Hybrid => {
+                    if provenance.fts_rank.is_none_or(|r| rank < r) {
+                        provenance.fts_rank = Some(rank);
+                    }
+                    if provenance.fts_score.is_none_or(|s| result.score > s) {
+                        provenance.fts_score = Some(result.score);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut results: Vec<SearchResult> = fused
+        .into_values()
+        .map(|mut result| {
+            if let Some(provenance) = result.provenance.as_mut() {
+                let best_rank = provenance
+                    .fts_rank
+                    .into_iter()
+                    .chain(provenance.vec_rank)
+                    .min()
+                    .unwrap_or(usize::MAX);
+                provenance.position_bonus = rrf_position_bonus(best_rank);
+                provenance.rrf_score += provenance.position_bonus;
+                result.score = provenance.rrf_score;
+            }
+            result
+        })
+        .collect();
+
+    results.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| a.doc.filepath.cmp(&b.doc.filepath))
+    });
+    for (rank, result) in results.iter_mut().enumerate() {
+        if let Some(provenance) = result.provenance.as_mut() {
+            provenance.fused_rank = Some(rank);
+        }
+    }
+    results
+}
+
+/// A passage of a candidate to rerank.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct PassageSpan {
+    /// Byte offset of the start.
+    start: usize,
+    /// Byte offset of the end.
+    end: usize,
+}
+
+/// Select the passages to rerank for each candidate.
+fn candidate_passages(
+    candidates: &[SearchResult],
+    lists: &[RankedList],
+    queries: &[Queryable],
+    max: usize,
+) -> Vec<Vec<PassageSpan>> {
+    // Matched chunk offsets per document, best rank first.
+    let mut chunk_hits: HashMap<&str, Vec<(usize, usize)>> = HashMap::new();
+    for list in lists.iter().filter(|l| l.source == SearchSource::Vec) {
+        for (rank, result) in list.results.iter().enumerate() {
+            if let Some(pos) = result.chunk_pos {
+                chunk_hits
+                    .entry(result.doc.filepath.as_str())
+                    .or_default()
+                    .push((rank, pos));
+            }
+        }
+    }
+    let terms = query_terms(queries);
+
+    candidates
+        .iter()
+        .map(|candidate| {
+            let body = candidate.doc.body.as_deref().unwrap_or_default();
+            let mut hits = chunk_hits
+                .get(candidate.doc.filepath.as_str())
+                .cloned()
+                .unwrap_or_default();
+            hits.sort_unstable();
+            let positions: Vec<usize> = hits.into_iter().map(|(_, pos)| pos).collect();
+            select_passages(body, &positions, &terms, max)
+        })
+        .collect()
+}
+
+/// Lowercased words of the lexical query variants.
+fn query_terms(queries: &[Queryable]) -> Vec<String> {
+    let mut terms: Vec<String> = Vec::new();
+    for q in queries.iter().filter(|q| q.query_type == QueryType::Lex) {
+        for word in q.text.split(|c: char| !c.is_alphanumeric()) {
+            if word.len() < 2 || matches!(word, "AND" | "OR" | "NOT" | "NEAR") {
+                continue;
+            }
+            let term = word.to_ascii_lowercase();
+            if !terms.contains(&term) {
+                terms.push(term);
+            }
+        }
+    }
+    terms
+}
+
+/// Pick up to `max` passages of `body`: the best vector chunk, the window
+/// with the most query-term hits, then the remaining vector chunks.
+///
+/// Overlapping passages are skipped; a body that fits in one passage is
+/// returned whole.
+fn select_passages(
+    body: &str,
+    chunk_positions: &[usize],
+    terms: &[String],
+    max: usize,
+) -> Vec<PassageSpan> {
+    if body.len() <= PASSAGE_CHARS {
+        return vec![PassageSpan {
+            start: 0,
+            end: body.len(),
+        }];
+    }
+
+    let mut starts: Vec<usize> = chunk_positions.iter().take(1).copied().collect();
+    starts.extend(best_lexical_window(body, terms));
+    starts.extend(chunk_positions.iter().skip(1));
+    if starts.is_empty() {
+        starts.push(0);
+    }
+
+    let mut spans: Vec<PassageSpan> = Vec::new();
+    for start in starts {
+        if spans.len() >= max.max(1) {
+            break;
+        }
+        let start = body.floor_char_boundary(start.min(body.len()));
+        if spans
+            .iter()
+            .any(|s| s.start.abs_diff(start) < PASSAGE_CHARS / 2)
+        {
+            continue;
+        }
+        let end = body.floor_char_boundary(start + PASSAGE_CHARS);
+        spans.push(PassageSpan { start, end });
+    }
+    spans
+}
+
+/// Start of the passage-sized window with the most query-term hits.
+fn best_lexical_window(body: &str, terms: &[String]) -> Option<usize> {
+    let lower = body.to_ascii_lowercase();
+    let mut hits: Vec<usize> = terms
+        .iter()
+        .flat_map(|term| lower.match_indices(term.as_str()).map(|(i, _)| i))
+        .collect();
+    hits.sort_unstable();
+
+    // Start each window a little before a hit so the hit has some context.
+    let lead = CHUNK_OVERLAP_CHARS / 2;
+    hits.iter()
+        .map(|&hit| {
+            let start = hit.saturating_sub(lead);
+            let count = hits[hits.partition_point(|&h| h < start)..]
+                .partition_point(|&h| h < start + PASSAGE_CHARS);
+            (count, start)
+        })
+        .max_by(|a, b| a.0.cmp(&b.0).then_with(|| b.1.cmp(&a.1)))
+        .map(|(_, start)| start)
+}
+
+/// Reorder candidates by rerank score, keeping the fused order on failure.
+///
+/// Each candidate's passages are scored and combined with `aggregation`; the
+/// best passage is kept on the result. The document score is blended with the
+/// candidate's fused rank, which is its position in `candidates`. Candidates
+/// without a score keep their fused order after the reranked ones.
+fn rerank(
+    reranker: &mut RerankEngine,
+    query: &str,
+    candidates: Vec<SearchResult>,
+    passages: &[Vec<PassageSpan>],
+    aggregation: ChunkAggregation,
+    blend: RerankBlend,
+) -> Vec<SearchResult> {
+    let mut owners: Vec<(usize, PassageSpan)> = Vec::new();
+    let mut documents: Vec<RerankDocument> = Vec::new();
+    for (i, (candidate, spans)) in candidates.iter().zip(passages).enumerate() {
+        let body = candidate.doc.body.as_deref().unwrap_or_default();
+        for span in spans {
+            owners.push((i, *span));
+            documents.push(RerankDocument {
+                file: candidate.doc.filepath.clone(),
+                text: body[span.start..span.end].to_string(),
+                title: Some(candidate.doc.title.clone()),
+            });
+        }
+    }
+    if documents.is_empty() {
+        return candidates;
+    }
+    let reranked = match reranker.rerank(query, &documents) {
+        Ok(reranked) => reranked,
+        Err(e) => {
+            tracing::warn!("Reranking failed, keeping fused order: {e}");
+            return candidates;
+        }
+    };
+
+    let mut scores: Vec<Vec<(f32, PassageSpan)>> = vec![Vec::new(); candidates.len()];
+    for rr in reranked.results {
+        if let Some(&(owner, span)) = owners.get(rr.index) {
+            scores[owner].push((rr.score, span));
+        }
+    }
+
+    let mut results: Vec<(Option<f64>, SearchResult)> = candidates
+        .into_iter()
+        .zip(scores)
+        .enumerate()
+        .map(|(fused_rank, (mut result, passage_scores))| {
+            let values: Vec<f32> = passage_scores.iter().map(|(score, _)| *score).collect();
+            let best = passage_scores
+                .iter()
+                .max_by(|a, b| a.0.total_cmp(&b.0).then_with(|| b.1.start.cmp(&a.1.start)));
+            let Some(&(best_score, span)) = best else {
+                return (None, result);
+            };
+
+            let rerank_score = f64::from(aggregation.aggregate(&values));
+            let score = blend.blend(fused_rank, rerank_score);
+            let body = result.doc.body.as_deref().unwrap_or_default();
+            result.passage = Some(Passage {
+                pos: span.start,
+                line: body[..span.start].matches('\n').count() + 1,
+                text: body[span.start..span.end].to_string(),
+                score: f64::from(best_score),
+            });
+            result.score = score;
+            if let Some(provenance) = result.provenance.as_mut() {
+                provenance.rerank_score = Some(rerank_score);
+            }
+            (Some(score), result)
+        })
+        .collect();
+
+    // Stable sort keeps the fused order among ties and unscored candidates.
+    results.sort_by(|a, b| match (a.0, b.0) {
+        (Some(x), Some(y)) => y.total_cmp(&x),
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => std::cmp::Ordering::Equal,
+    });
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frontmatter::Metadata;
+    use crate::store::DocumentResult;
+    use crate::test_support::TempStore;
+
+    fn result(path: &str, score: f64, source: SearchSource) -> SearchResult {
+        SearchResult {
+            doc: DocumentResult {
+                filepath: format!("qmd://notes/{path}"),
+                display_path: format!("notes/{path}"),
+                title: path.to_string(),
+                context: None,
+                hash: path.to_string(),
+                docid: path.to_string(),
+                collection_name: "notes".to_string(),
+                path: path.to_string(),
+                modified_at: String::new(),
+                body_length: 0,
+                body: None,
+                meta: Metadata::new(),
+            },
+            score,
+            source,
+            chunk_pos: (source == SearchSource::Vec).then_some(42),
+            chunk_seq: (source == SearchSource::Vec).then_some(1),
+            provenance: None,
+            passage: None,
+            snippets: Vec::new(),
+            heading: None,
+        }
+    }
+
+    #[test]
+    fn test_fuse_records_provenance() {
+        let lists = vec![
+            RankedList {
+                query: Queryable::lex("q"),
+                source: SearchSource::Fts,
+                weight: 1.0,
+                results: vec![
+                    result("a", 5.0, SearchSource::Fts),
+                    result("b", 3.0, SearchSource::Fts),
+                ],
+            },
+            RankedList {
+                query: Queryable::vec("q"),
+                source: SearchSource::Vec,
+                weight: 1.0,
+                results: vec![
+                    result("b", 0.9, SearchSource::Vec),
+                    result("c", 0.5, SearchSource::Vec),
+                ],
+            },
+        ];
+
+        let fused = fuse(&lists, 60);
+        let paths: Vec<&str> = fused.iter().map(|r| r.doc.path.as_str()).collect();
+        assert_eq!(paths, ["b", "a", "c"]);
+
+        let b = &fused[0];
+        assert_eq!(b.source, SearchSource::Hybrid);
+        assert_eq!(b.chunk_seq, Some(1));
+        let provenance = b.provenance.as_ref().unwrap();
+        assert_eq!(provenance.fts_rank, Some(1));
+        assert_eq!(provenance.vec_rank, Some(0));
+        assert_eq!(provenance.fts_score, Some(3.0));
+        assert_eq!(provenance.vec_score, Some(0.9));
+        assert!((b.score - (1.0 / 62.0 + 1.0 / 61.0 + 0.08)).abs() < 1e-9);
+        assert!((provenance.position_bonus - 0.08).abs() < 1e-9);
+        assert_eq!(provenance.fused_rank, Some(0));
+        assert_eq!(provenance.lists.len(), 2);
+        assert_eq!(provenance.lists[0].query_type, QueryType::Lex);
+        assert_eq!(provenance.lists[0].rank, 1);
+        assert!((provenance.lists[1].contribution - 1.0 / 61.0).abs() < 1e-9);
+
+        let a = fused[1].provenance.as_ref().unwrap();
+        assert_eq!(a.vec_rank, None);
+        assert_eq!(fused[1].chunk_pos, None);
+    }
+
+    #[test]
+    fn test_fuse_applies_list_weights() {
+        let lists = vec![
+            RankedList {
+                query: Queryable::lex("q"),
+                source: SearchSource::Fts,
+                weight: 1.0,
+                results: vec![result("a", 5.0, SearchSource::Fts)],
+            },
+            RankedList {
+                query: Queryable::vec("q"),
+                source: SearchSource::Vec,
+                weight: 3.0,
+                results: vec![result("b", 0.9, SearchSource::Vec)],
+            },
+        ];
+
+        let fused = fuse(&lists, 60);
+        assert_eq!(fused[0].doc.path, "b");
+    }
+
+    #[test]
+    fn test_select_passages() {
+        let terms = vec!["needle".to_string()];
+        assert_eq!(
+            select_passages("short note", &[], &terms, 3),
+            [PassageSpan { start: 0, end: 10 }]
+        );
+
+        let mut body = "filler text. ".repeat(1000);
+        let needle_at = body.len() / 2;
+        body.insert_str(needle_at, "needle needle ");
+
+        let spans = select_passages(&body, &[100], &terms, 3);
+        assert_eq!(spans.len(), 2);
+        assert_eq!(spans[0].start, 100);
+        assert_eq!(spans[0].end - spans[0].start, PASSAGE_CHARS);
+        assert!(spans[1].start <= needle_at && needle_at < spans[1].end);
+
+        // Overlapping chunks collapse and `max` caps the count.
+        let spans = select_passages(&body, &[100, 200, 9000], &terms, 2);
+        assert_eq!(spans.len(), 2);
+
+        // Without hits or chunks the document start is used.
+        let spans = select_passages(&body, &[], &["absent".to_string()], 3);
+        assert_eq!(spans[0].start, 0);
+    }
+
+    #[test]
+    fn test_select_passages_respects_char_boundaries() {
+        let body = "é".repeat(PASSAGE_CHARS);
+        let spans = select_passages(&body, &[1, PASSAGE_CHARS + 1], &[], 2);
+        for span in spans {
+            assert!(body.is_char_boundary(span.start) && body.is_char_boundary(span.end));
+        }
+    }
+
+    #[test]
+    fn test_query_terms_skip_operators() {
+        let queries = vec![
+            Queryable::lex("rust AND errors"),
+            Queryable::vec("ignored semantic"),
+            Queryable::lex("Rust panics"),
+        ];
+        assert_eq!(query_terms(&queries), ["rust", "errors", "panics"]);
+    }
+
+    #[test]
+    fn test_rerank_blend() {
+        assert!((RerankBlend::Replace.blend(7, 0.3) - 0.3).abs() < 1e-9);
+        assert!((RerankBlend::Weighted(0.5).blend(1, 0.9) - 0.7).abs() < 1e-9);
+        assert!((RerankBlend::Weighted(2.0).rerank_weight(0) - 1.0).abs() < 1e-9);
+
+        let blend = RerankBlend::PositionAware;
+        assert!((blend.rerank_weight(2) - 0.25).abs() < 1e-9);
+        assert!((blend.rerank_weight(3) - 0.4).abs() < 1e-9);
+        assert!((blend.rerank_weight(10) - 0.6).abs() < 1e-9);
+
+        // A confident reranker cannot easily overturn the top retrieval hit.
+        assert!(blend.blend(0, 0.2) > blend.blend(1, 0.9));
+        // Lower in the list the reranker decides.
+        assert!(blend.blend(12, 0.9) > blend.blend(11, 0.1));
+    }
+
+    #[test]
+    fn test_retrieve_rejects_only_malformed_user_queries() {
+        let store = TempStore::new("retrieve");
+        store.add_doc("rust.md", "# Rust\nownership and borrowing");
+        let mut searcher = HybridSearcher::new(&store);
+        let options = HybridOptions::default();
+
+        assert!(matches!(
+            searcher.search("\"ownership", &options),
+            Err(QmdError::InvalidQuery(_))
+        ));
+
+        let queries = [
+            Queryable::lex("ownership"),
+            Queryable::vec("ownership"),
+            Queryable::lex("\"borrowing"),
+        ];
+        let lists = searcher.retrieve(&queries, &options).unwrap();
+        assert_eq!(lists.len(), 2);
+        assert_eq!(lists[0].results.len(), 1);
+        assert!(lists[1].results.is_empty());
+    }
+}
@@ -0,0 +1,278 @@
+//! Highlighted snippets from FTS5 match offsets.
+//!
+//! Matches are located by FTS5's `highlight()` on the body column, so they
+//! follow the index tokenizer: stemmed forms and prefix queries highlight the
+//! same words that made the document match. The marked-up body is split back
+//! into the original text and byte ranges, and the lines with the most
+//! distinct matches become the snippet windows.
+//!
+//! All offsets are byte offsets on `char` boundaries, so snippets can be
+//! sliced safely from any UTF-8 text.
+
+use crate::error::Result;
+use crate::query::FtsQuery;
+use crate::store::{SearchResult, Store};
+use std::collections::HashSet;
+use std::ops::Range;
+
+/// Maximum number of windows in a result's snippet.
+pub const SNIPPET_WINDOWS: usize = 3;
+
+/// Maximum length of a snippet window in bytes.
+pub const SNIPPET_WINDOW_CHARS: usize = 160;
+
+/// Marker FTS5 inserts before a match; a private-use character that does not
+/// occur in ordinary text.
+const MATCH_OPEN: char = '\u{E000}';
+
+/// Marker FTS5 inserts after a match.
+const MATCH_CLOSE: char = '\u{E001}';
+
+/// One window of a document around query matches.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snippet {
+    /// Line number (1-indexed) of the window.
+    pub line: usize,
+    /// Window text, a single line or part of one.
+    pub text: String,
+    /// Byte ranges of the matches within `text`, in order.
+    pub highlights: Vec<Range<usize>>,
+}
+
+impl Snippet {
+    /// Split the text into alternating plain and highlighted parts.
+    ///
+    /// Returns `(text, highlighted)` pairs in order; empty parts are omitted.
+    #[must_use]
+    pub fn parts(&self) -> Vec<(&str, bool)> {
+        let mut parts = Vec::new();
+        let mut pos = 0;
+        for range in &self.highlights {
+            if range.start > pos {
+                parts.push((&self.text[pos..range.start], false));
+            }
+            parts.push((&self.text[range.clone()], true));
+            pos = range.end;
+        }
+        if pos < self.text.len() {
+            parts.push((&self.text[pos..], false));
+        }
+        parts
+    }
+}
+
+/// Split text marked with [`MATCH_OPEN`] and [`MATCH_CLOSE`] into the plain
+/// text and the byte ranges of the marked matches.
+fn parse_highlighted(marked: &str) -> (String, Vec<Range<usize>>) {
+    let mut text = String::with_capacity(marked.len());
+    let mut ranges = Vec::new();
+    let mut open = None;
+    for c in marked.chars() {
+        match c {
+            MATCH_OPEN => open = Some(text.len()),
+            MATCH_CLOSE => {
+                if let Some(start) = open.take()
+                    && start < text.len()
+                {
+                    ranges.push(start..text.len());
+                }
+            }
+            _ => text.push(c),
+        }
+    }
+    (text, ranges)
+}
+
+/// Build up to `max_windows` snippet windows of at most `window_chars` bytes
+/// around the `matches` in `body`.
+///
+/// Each window covers one line, or the part of a long line starting shortly
+/// before its first match. Windows with more distinct matched words are
+/// preferred and returned in document order.
+#[must_use]
+pub fn build_snippets(
+    body: &str,
+    matches: &[Range<usize>],
+    max_windows: usize,
+    window_chars: usize,
+) -> Vec<Snippet> {
+    let mut windows: Vec<(usize, usize, Snippet)> = Vec::new();
+    let mut i = 0;
+    while i < matches.len() {
+        let first = &matches[i];
+        let line_start = body[..first.start].rfind('\n').map_or(0, |p| p + 1);
+        let line_end = body[first.start..]
+            .find('\n')
+            .map_or(body.len(), |p| first.start + p);
+
+        let (start, end) = if line_end - line_start <= window_chars {
+            (line_start, line_end)
+        } else {
+            let lead = window_chars / 4;
+            let start = body.floor_char_boundary(first.start.saturating_sub(lead).max(line_start));
+            let end = body.floor_char_boundary((start + window_chars).min(line_end));
+            (start, end)
+        };
+
+        // Collect the matches of this line that fit in the window.
+        let mut highlights = Vec::new();
+        let mut words = HashSet::new();
+        while i < matches.len() && matches[i].start < line_end {
+            let m = &matches[i];
+            if m.end <= end {
+                highlights.push(m.start - start..m.end - start);
+                words.insert(body[m.clone()].to_lowercase());
+            }
+            i += 1;
+        }
+        if highlights.is_empty() {
+            continue;
+        }
+
+        // Drop leading and trailing whitespace, keeping offsets consistent.
+        let raw = &body[start..end];
+        let trimmed_start = raw.len() - raw.trim_start().len();
+        let text = raw.trim().to_string();
+        for h in &mut highlights {
+            *h = h.start - trimmed_start..h.end - trimmed_start;
+        }
+
+        let line = body[..line_start].matches('\n').count() + 1;
+        windows.push((
+            words.len(),
+            highlights.len(),
+            Snippet {
+                line,
+                text,
+                highlights,
+            },
+        ));
+    }
+
+    // Keep the best windows, then restore document order.
+    windows.sort_by(|a, b| (b.0, b.1).cmp(&(a.0, a.1)).then(a.2.line.cmp(&b.2.line)));
+    windows.truncate(max_windows);
+    let mut snippets: Vec<Snippet> = windows.into_iter().map(|(_, _, s)| s).collect();
+    snippets.sort_by_key(|s| s.line);
+    snippets
+}
+
+impl Store {
+    /// Find the byte ranges in a document's body that match any term of
+    /// `query`, as the FTS index tokenizes them.
+    ///
+    /// Returns the body and the ranges, or `None` if the document is not
+    /// indexed, the query cannot be parsed or nothing matches.
+    pub fn match_ranges(
+        &self,
+        collection: &str,
+        path: &str,
+        query: &str,
+    ) -> Result<Option<(String, Vec<Range<usize>>)>> {
+        let Ok(parsed) = FtsQuery::parse(query) else {
+            return Ok(None);
+        };
+        let marked = self.highlight_body(
+            collection,
+            path,
+            &parsed.to_fts5_any(),
+            MATCH_OPEN,
+            MATCH_CLOSE,
+        )?;
+
+        Ok(marked.and_then(|text| {
+            // Markers already in the body would be ambiguous.
+            let markers = text.matches([MATCH_OPEN, MATCH_CLOSE]).count();
+            let (body, ranges) = parse_highlighted(&text);
+            (!ranges.is_empty() && markers == ranges.len() * 2).then_some((body, ranges))
+        }))
+    }
+
+    /// Attach highlighted snippets of the matches of `query` to every
+    /// result.
+    ///
+    /// Any term of the query counts as a match, so results found by vector
+    /// search get snippets too where they share words with the query.
+    /// Results without a lexical match are left without snippets.
+    ///
+    /// Each result also gets the recorded heading enclosing its rerank
+    /// passage, its best vector chunk or else its first snippet window.
+    pub fn add_snippets(&self, query: &str, results: &mut [SearchResult]) -> Result<()> {
+        for result in results {
+            let doc = &result.doc;
+            let matches = self.match_ranges(&doc.collection_name, &doc.path, query)?;
+            let body = matches.map(|(text, ranges)| {
+                result.snippets =
+                    build_snippets(&text, &ranges, SNIPPET_WINDOWS, SNIPPET_WINDOW_CHARS);
+                text
+            });
+
+            let line = if let Some(passage) = &result.passage {
+                Some(passage.line)
+            } else if let Some(pos) = result.chunk_pos {
+                let content = match body {
+                    Some(text) => Some(text),
+                    None => self.get_content(&doc.hash)?,
+                };
+                content.map(|text| {
+                    let start = text.floor_char_boundary(pos.min(text.len()));
+                    text[..start].matches('\n').count() + 1
+                })
+            } else {
+                result.snippets.first().map(|snippet| snippet.line)
+            };
+            result.heading = match line {
+                Some(at) => self.get_enclosing_heading(&doc.collection_name, &doc.path, at)?,
+                None => None,
+            };
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn marked(text: &str) -> String {
+        text.replace('[', &MATCH_OPEN.to_string())
+            .replace(']', &MATCH_CLOSE.to_string())
+    }
+
+    #[test]
+    fn test_parse_highlighted() {
+        let (text, ranges) = parse_highlighted(&marked("naïve [café] and [crème]"));
+        assert_eq!(text, "naïve café and crème");
+        assert_eq!(&text[ranges[0].clone()], "café");
+        assert_eq!(&text[ranges[1].clone()], "crème");
+    }
+
+    #[test]
+    fn test_build_snippets_picks_best_lines() {
+        let body = "intro\nrust once\nnothing here\nrust and tokio\nrust";
+        let (text, ranges) = parse_highlighted(&marked(
+            "intro\n[rust] once\nnothing here\n[rust] and [tokio]\n[rust]",
+        ));
+        assert_eq!(text, body);
+
+        let snippets = build_snippets(body, &ranges, 2, 160);
+        assert_eq!(snippets.len(), 2);
+        assert_eq!(snippets[0].line, 2);
+        assert_eq!(snippets[1].line, 4);
+        assert_eq!(
+            snippets[1].parts(),
+            [("rust", true), (" and ", false), ("tokio", true)]
+        );
+    }
+
+    #[test]
+    fn test_build_snippets_multibyte_long_line() {
+        let line = format!("{}[ünïcödé] tail", "日本語".repeat(40));
+        let (body, ranges) = parse_highlighted(&marked(&line));
+        let snippets = build_snippets(&body, &ranges, 3, 40);
+        assert_eq!(snippets.len(), 1);
+        let snippet = &snippets[0];
+        assert!(snippet.text.len() <= 40);
+        assert_eq!(&snippet.text[snippet.highlights[0].clone()], "ünïcödé");
+    }
+}
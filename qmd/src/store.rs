@@ -3,12 +3,29 @@
 //! This module provides all database operations, search functions, and document
 //! retrieval for QMD.
 
+use crate::chunker::Chunker;
 use crate::collections::{find_context_for_path, list_collections as yaml_list_collections};
 use crate::config::{EXCLUDE_DIRS, get_default_db_path};
 use crate::error::{QmdError, Result};
-use rusqlite::{Connection, OptionalExtension, params};
+use crate::filter::SearchFilter;
+use crate::frontmatter::{MetaValue, Metadata, parse_frontmatter, split_frontmatter};
+use crate::links::{Link, LinkKind, LinkRef, extract_links, link_target};
+use crate::llm::{QueryType, cosine_similarity};
+use crate::outline::{Heading, extract_headings};
+use crate::query::{FtsQuery, FtsSearchOptions};
+use crate::snippet::Snippet;
+use crate::tags::{TagCount, extract_tags};
+use crate::vector::{
+    IVF_MAX_TRAINING_SAMPLES, IVF_MIN_VECTORS, IVF_TRAIN_ITERATIONS, Quantization,
+    VecSearchOptions, decode_f32, encode_f32, ivf_list_count, nearest_centroid, nearest_centroids,
+    train_centroids,
+};
+use rusqlite::types::Value;
+use rusqlite::{Connection, OptionalExtension, ToSql, Transaction, params, params_from_iter};
 use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
 use std::fs;
+use std::ops::ControlFlow;
 use std::path::{Path, PathBuf};
 
 /// Normalize path separators to forward slashes (Unix-style).
@@ -97,6 +114,8 @@ pub struct DocumentResult {
     pub body_length: usize,
     /// Document body (optional).
     pub body: Option<String>,
+    /// Frontmatter fields.
+    pub meta: Metadata,
 }
 
 /// Search result with score.
@@ -108,8 +127,73 @@ pub struct SearchResult {
     pub score: f64,
     /// Source of the result.
     pub source: SearchSource,
-    /// Chunk position for vector search results (0-indexed).
+    /// Byte offset of the best-matching chunk for vector search results.
     pub chunk_pos: Option<usize>,
+    /// Sequence number of the best-matching chunk for vector search results.
+    pub chunk_seq: Option<usize>,
+    /// How a hybrid search arrived at the score.
+    pub provenance: Option<ScoreProvenance>,
+    /// Passage that decided the rerank score, for reranked hybrid results.
+    pub passage: Option<Passage>,
+    /// Highlighted windows around query matches, once added with
+    /// [`Store::add_snippets`].
+    pub snippets: Vec<Snippet>,
+    /// Heading whose section encloses the passage, chunk or first snippet,
+    /// once added with [`Store::add_snippets`].
+    pub heading: Option<Heading>,
+}
+
+/// Excerpt of a document scored by the reranker.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Passage {
+    /// Byte offset in the document.
+    pub pos: usize,
+    /// Line number (1-indexed) where the passage starts.
+    pub line: usize,
+    /// Passage text.
+    pub text: String,
+    /// Rerank score of the passage.
+    pub score: f64,
+}
+
+/// Score breakdown for a hybrid search result.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScoreProvenance {
+    /// Best rank (0-indexed) across lexical result lists.
+    pub fts_rank: Option<usize>,
+    /// Best BM25 score across lexical result lists.
+    pub fts_score: Option<f64>,
+    /// Best rank (0-indexed) across vector result lists.
+    pub vec_rank: Option<usize>,
+    /// Best cosine similarity across vector result lists.
+    pub vec_score: Option<f64>,
+    /// Fused RRF score, including the position bonus.
+    pub rrf_score: f64,
+    /// Position bonus included in the RRF score.
+    pub position_bonus: f64,
+    /// Contribution of each result list the document appeared in.
+    pub lists: Vec<ListContribution>,
+    /// Rank (0-indexed) in the fused list, before reranking.
+    pub fused_rank: Option<usize>,
+    /// Reranker relevance score, if the result was reranked.
+    pub rerank_score: Option<f64>,
+}
+
+/// RRF contribution of one query variant's result list.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListContribution {
+    /// Backend the list was retrieved with.
+    pub query_type: QueryType,
+    /// Query variant text.
+    pub query: String,
+    /// Rank (0-indexed) of the document in the list.
+    pub rank: usize,
+    /// Raw score in the list (BM25 or cosine similarity).
+    pub score: f64,
+    /// RRF weight of the list.
+    pub weight: f64,
+    /// Amount added to the RRF score.
+    pub contribution: f64,
 }
 
 /// Search source type.
@@ -119,6 +203,20 @@ pub enum SearchSource {
     Fts,
     /// Vector similarity search.
     Vec,
+    /// Fused lexical and vector search.
+    Hybrid,
+}
+
+impl SearchSource {
+    /// Short lowercase name.
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Fts => "fts",
+            Self::Vec => "vec",
+            Self::Hybrid => "hybrid",
+        }
+    }
 }
 
 /// Collection info from database.
@@ -147,6 +245,440 @@ pub struct IndexStatus {
     pub has_vector_index: bool,
     /// Collection information.
     pub collections: Vec<CollectionInfo>,
+    /// Database schema version.
+    pub schema_version: u32,
+}
+
+/// Current schema version, stored in `PRAGMA user_version`.
+pub const SCHEMA_VERSION: u32 = 7;
+
+/// An ordered, transactional schema migration.
+struct Migration {
+    /// Schema version after this migration has run.
+    version: u32,
+    /// Short description used in error messages.
+    description: &'static str,
+    /// Apply the migration inside the given transaction.
+    apply: fn(&Transaction<'_>) -> Result<()>,
+}
+
+/// All migrations, in the order they must be applied.
+///
+/// Databases created before versioning report version 0; the first migration
+/// only uses `IF NOT EXISTS` so it brings them up to date without data loss.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "initial schema",
+        apply: migrate_v1_initial,
+    },
+    Migration {
+        version: 2,
+        description: "vector index tables",
+        apply: migrate_v2_vector_index,
+    },
+    Migration {
+        version: 3,
+        description: "trigram substring index",
+        apply: migrate_v3_trigram_index,
+    },
+    Migration {
+        version: 4,
+        description: "frontmatter metadata",
+        apply: migrate_v4_document_meta,
+    },
+    Migration {
+        version: 5,
+        description: "document tags",
+        apply: migrate_v5_document_tags,
+    },
+    Migration {
+        version: 6,
+        description: "document links",
+        apply: migrate_v6_document_links,
+    },
+    Migration {
+        version: 7,
+        description: "document headings",
+        apply: migrate_v7_document_headings,
+    },
+];
+
+/// v1: content, documents, FTS index and triggers, LLM cache, vector metadata.
+fn migrate_v1_initial(tx: &Transaction<'_>) -> Result<()> {
+    tx.execute_batch(
+        r"
+        -- Content-addressable storage
+        CREATE TABLE IF NOT EXISTS content (
+            hash TEXT PRIMARY KEY,
+            doc TEXT NOT NULL,
+            created_at TEXT NOT NULL
+        );
+
+        -- Documents table
+        CREATE TABLE IF NOT EXISTS documents (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            collection TEXT NOT NULL,
+            path TEXT NOT NULL,
+            title TEXT NOT NULL,
+            hash TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            modified_at TEXT NOT NULL,
+            active INTEGER NOT NULL DEFAULT 1,
+            FOREIGN KEY (hash) REFERENCES content(hash) ON DELETE CASCADE,
+            UNIQUE(collection, path)
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_documents_collection ON documents(collection, active);
+        CREATE INDEX IF NOT EXISTS idx_documents_hash ON documents(hash);
+        CREATE INDEX IF NOT EXISTS idx_documents_path ON documents(path, active);
+
+        -- FTS index
+        CREATE VIRTUAL TABLE IF NOT EXISTS documents_fts USING fts5(
+            filepath, title, body,
+            tokenize='porter unicode61'
+        );
+
+        -- FTS synchronization triggers
+        CREATE TRIGGER IF NOT EXISTS documents_ai AFTER INSERT ON documents
+        WHEN new.active = 1
+        BEGIN
+            INSERT INTO documents_fts(rowid, filepath, title, body)
+            SELECT
+                new.id,
+                new.collection || '/' || new.path,
+                new.title,
+                (SELECT doc FROM content WHERE hash = new.hash)
+            WHERE new.active = 1;
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS documents_ad AFTER DELETE ON documents BEGIN
+            DELETE FROM documents_fts WHERE rowid = old.id;
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS documents_au AFTER UPDATE ON documents
+        BEGIN
+            DELETE FROM documents_fts WHERE rowid = old.id AND new.active = 0;
+            INSERT OR REPLACE INTO documents_fts(rowid, filepath, title, body)
+            SELECT
+                new.id,
+                new.collection || '/' || new.path,
+                new.title,
+                (SELECT doc FROM content WHERE hash = new.hash)
+            WHERE new.active = 1;
+        END;
+
+        -- LLM cache
+        CREATE TABLE IF NOT EXISTS llm_cache (
+            hash TEXT PRIMARY KEY,
+            result TEXT NOT NULL,
+            created_at TEXT NOT NULL
+        );
+
+        -- Content vectors metadata
+        CREATE TABLE IF NOT EXISTS content_vectors (
+            hash TEXT NOT NULL,
+            seq INTEGER NOT NULL DEFAULT 0,
+            pos INTEGER NOT NULL DEFAULT 0,
+            model TEXT NOT NULL,
+            embedded_at TEXT NOT NULL,
+            PRIMARY KEY (hash, seq)
+        );
+        ",
+    )?;
+    Ok(())
+}
+
+/// v2: IVF index over `vectors_vec` and vector storage settings.
+fn migrate_v2_vector_index(tx: &Transaction<'_>) -> Result<()> {
+    tx.execute_batch(
+        r"
+        -- IVF approximate nearest-neighbour index over vectors_vec
+        CREATE TABLE IF NOT EXISTS vectors_ivf_centroids (
+            list_id INTEGER PRIMARY KEY,
+            centroid BLOB NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS vectors_ivf_lists (
+            hash_seq TEXT PRIMARY KEY,
+            list_id INTEGER NOT NULL
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_vectors_ivf_lists ON vectors_ivf_lists(list_id);
+
+        -- Vector storage settings (quantization, dimensions, chunker)
+        CREATE TABLE IF NOT EXISTS vectors_meta (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL
+        );
+        ",
+    )?;
+    Ok(())
+}
+
+/// v3: optional trigram index over document bodies, kept in sync by the FTS
+/// triggers while `index_meta` has a `trigram` entry.
+fn migrate_v3_trigram_index(tx: &Transaction<'_>) -> Result<()> {
+    tx.execute_batch(
+        r"
+        -- Index settings
+        CREATE TABLE IF NOT EXISTS index_meta (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL
+        );
+
+        -- Trigram index for substring and regex search, empty until enabled
+        CREATE VIRTUAL TABLE IF NOT EXISTS documents_trigram USING fts5(
+            body,
+            tokenize='trigram'
+        );
+
+        -- FTS synchronization triggers, now covering the trigram index
+        DROP TRIGGER IF EXISTS documents_ai;
+        DROP TRIGGER IF EXISTS documents_ad;
+        DROP TRIGGER IF EXISTS documents_au;
+
+        CREATE TRIGGER documents_ai AFTER INSERT ON documents
+        WHEN new.active = 1
+        BEGIN
+            INSERT INTO documents_fts(rowid, filepath, title, body)
+            SELECT
+                new.id,
+                new.collection || '/' || new.path,
+                new.title,
+                (SELECT doc FROM content WHERE hash = new.hash)
+            WHERE new.active = 1;
+            INSERT INTO documents_trigram(rowid, body)
+            SELECT new.id, (SELECT doc FROM content WHERE hash = new.hash)
+            WHERE EXISTS (SELECT 1 FROM index_meta WHERE key = 'trigram');
+        END;
+
+        CREATE TRIGGER documents_ad AFTER DELETE ON documents BEGIN
+            DELETE FROM documents_fts WHERE rowid = old.id;
+            DELETE FROM documents_trigram WHERE rowid = old.id;
+        END;
+
+        CREATE TRIGGER documents_au AFTER UPDATE ON documents
+        BEGIN
+            DELETE FROM documents_fts WHERE rowid = old.id AND new.active = 0;
+            INSERT OR REPLACE INTO documents_fts(rowid, filepath, title, body)
+            SELECT
+                new.id,
+                new.collection || '/' || new.path,
+                new.title,
+                (SELECT doc FROM content WHERE hash = new.hash)
+            WHERE new.active = 1;
+            DELETE FROM documents_trigram WHERE rowid = old.id;
+            INSERT INTO documents_trigram(rowid, body)
+            SELECT new.id, (SELECT doc FROM content WHERE hash = new.hash)
+            WHERE new.active = 1
+              AND EXISTS (SELECT 1 FROM index_meta WHERE key = 'trigram');
+        END;
+        ",
+    )?;
+    Ok(())
+}
+
+/// v4: frontmatter fields of every content entry, one row per value.
+fn migrate_v4_document_meta(tx: &Transaction<'_>) -> Result<()> {
+    tx.execute_batch(
+        r"
+        -- Frontmatter fields; list items get one row each, in order
+        CREATE TABLE IF NOT EXISTS document_meta (
+            hash TEXT NOT NULL,
+            key TEXT NOT NULL,
+            seq INTEGER NOT NULL,
+            value TEXT NOT NULL,
+            list INTEGER NOT NULL DEFAULT 0,
+            PRIMARY KEY (hash, key, seq),
+            FOREIGN KEY (hash) REFERENCES content(hash) ON DELETE CASCADE
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_document_meta_key ON document_meta(key, value);
+        ",
+    )?;
+
+    // Backfill content indexed before frontmatter was parsed.
+    let content: Vec<(String, String)> = tx
+        .prepare("SELECT hash, doc FROM content")?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<std::result::Result<_, _>>()?;
+    for (hash, doc) in content {
+        insert_metadata(tx, &hash, &parse_frontmatter(&doc))?;
+    }
+    Ok(())
+}
+
+/// Store the frontmatter fields of a content entry.
+fn insert_metadata(conn: &Connection, hash: &str, meta: &Metadata) -> Result<()> {
+    let mut stmt = conn.prepare_cached(
+        "INSERT OR REPLACE INTO document_meta (hash, key, seq, value, list) VALUES (?1, ?2, ?3, ?4, ?5)",
+    )?;
+    for (key, value) in meta {
+        let list = matches!(value, MetaValue::List(_));
+        for (seq, item) in value.values().iter().enumerate() {
+            stmt.execute(params![hash, key, seq as i64, item, list])?;
+        }
+    }
+    Ok(())
+}
+
+/// v5: normalized tags of active documents.
+fn migrate_v5_document_tags(tx: &Transaction<'_>) -> Result<()> {
+    tx.execute_batch(
+        r"
+        -- Tags per document, from frontmatter and inline #tags
+        CREATE TABLE IF NOT EXISTS document_tags (
+            document_id INTEGER NOT NULL,
+            tag TEXT NOT NULL,
+            PRIMARY KEY (document_id, tag),
+            FOREIGN KEY (document_id) REFERENCES documents(id) ON DELETE CASCADE
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_document_tags_tag ON document_tags(tag);
+        ",
+    )?;
+
+    // Backfill documents indexed before tags were extracted.
+    let documents: Vec<(i64, String)> = tx
+        .prepare(
+            "SELECT d.id, c.doc FROM documents d JOIN content c ON c.hash = d.hash WHERE d.active = 1",
+        )?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<std::result::Result<_, _>>()?;
+    for (id, doc) in documents {
+        insert_tags(tx, id, &extract_tags(&doc))?;
+    }
+    Ok(())
+}
+
+/// v6: outgoing links of active documents.
+fn migrate_v6_document_links(tx: &Transaction<'_>) -> Result<()> {
+    tx.execute_batch(
+        r"
+        -- Links per document; target_path is NULL if the link cannot resolve
+        CREATE TABLE IF NOT EXISTS document_links (
+            document_id INTEGER NOT NULL,
+            seq INTEGER NOT NULL,
+            line INTEGER NOT NULL,
+            kind TEXT NOT NULL,
+            text TEXT NOT NULL,
+            target TEXT NOT NULL,
+            target_collection TEXT,
+            target_path TEXT,
+            PRIMARY KEY (document_id, seq),
+            FOREIGN KEY (document_id) REFERENCES documents(id) ON DELETE CASCADE
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_document_links_target ON document_links(target_path);
+        ",
+    )?;
+
+    // Backfill documents indexed before links were extracted.
+    let documents: Vec<(i64, String, String)> = tx
+        .prepare(
+            r"
+            SELECT d.id, d.path, c.doc
+            FROM documents d
+            JOIN content c ON c.hash = d.hash
+            WHERE d.active = 1
+            ",
+        )?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+        .collect::<std::result::Result<_, _>>()?;
+    for (id, path, doc) in documents {
+        insert_links(tx, id, &path, &extract_links(&doc))?;
+    }
+    Ok(())
+}
+
+/// v7: heading outlines of active documents.
+fn migrate_v7_document_headings(tx: &Transaction<'_>) -> Result<()> {
+    tx.execute_batch(
+        r"
+        -- Headings per document with the line range of their section
+        CREATE TABLE IF NOT EXISTS document_headings (
+            document_id INTEGER NOT NULL,
+            seq INTEGER NOT NULL,
+            level INTEGER NOT NULL,
+            text TEXT NOT NULL,
+            slug TEXT NOT NULL,
+            line INTEGER NOT NULL,
+            end_line INTEGER NOT NULL,
+            PRIMARY KEY (document_id, seq),
+            FOREIGN KEY (document_id) REFERENCES documents(id) ON DELETE CASCADE
+        );
+        ",
+    )?;
+
+    // Backfill documents indexed before headings were recorded.
+    let documents: Vec<(i64, String)> = tx
+        .prepare(
+            "SELECT d.id, c.doc FROM documents d JOIN content c ON c.hash = d.hash WHERE d.active = 1",
+        )?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<std::result::Result<_, _>>()?;
+    for (id, doc) in documents {
+        insert_headings(tx, id, &extract_headings(&doc))?;
+    }
+    Ok(())
+}
+
+/// Store the heading outline of a document.
+fn insert_headings(conn: &Connection, document_id: i64, headings: &[Heading]) -> Result<()> {
+    let mut stmt = conn.prepare_cached(
+        r"
+        INSERT INTO document_headings (document_id, seq, level, text, slug, line, end_line)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+        ",
+    )?;
+    for (seq, heading) in headings.iter().enumerate() {
+        stmt.execute(params![
+            document_id,
+            seq as i64,
+            heading.level as i64,
+            heading.text,
+            heading.slug,
+            heading.line as i64,
+            heading.end_line as i64,
+        ])?;
+    }
+    Ok(())
+}
+
+/// Store the links of the document at `path`.
+fn insert_links(conn: &Connection, document_id: i64, path: &str, links: &[LinkRef]) -> Result<()> {
+    let mut stmt = conn.prepare_cached(
+        r"
+        INSERT INTO document_links
+            (document_id, seq, line, kind, text, target, target_collection, target_path)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+        ",
+    )?;
+    for (seq, link) in links.iter().enumerate() {
+        let (target_collection, target_path) = link_target(path, link).unzip();
+        stmt.execute(params![
+            document_id,
+            seq as i64,
+            link.line as i64,
+            link.kind.as_str(),
+            link.text,
+            link.target,
+            target_collection.flatten(),
+            target_path,
+        ])?;
+    }
+    Ok(())
+}
+
+/// Store the tags of a document.
+fn insert_tags(conn: &Connection, document_id: i64, tags: &[String]) -> Result<()> {
+    let mut stmt = conn
+        .prepare_cached("INSERT OR IGNORE INTO document_tags (document_id, tag) VALUES (?1, ?2)")?;
+    for tag in tags {
+        stmt.execute(params![document_id, tag])?;
+    }
+    Ok(())
 }
 
 /// The database store.
@@ -158,6 +690,53 @@ pub struct Store {
     db_path: PathBuf,
 }
 
+/// Inserts embeddings with the vector format and IVF centroids loaded once,
+/// see [`Store::vector_writer`].
+#[derive(Debug)]
+pub struct VectorWriter<'a> {
+    store: &'a Store,
+    quantization: Quantization,
+    centroids: Vec<Vec<f32>>,
+}
+
+impl VectorWriter<'_> {
+    /// Insert an embedding for a content hash.
+    pub fn insert(
+        &self,
+        hash: &str,
+        seq: usize,
+        pos: usize,
+        embedding: &[f32],
+        model: &str,
+        embedded_at: &str,
+    ) -> Result<()> {
+        let conn = &self.store.conn;
+        conn.execute(
+            r"
+            INSERT OR REPLACE INTO content_vectors (hash, seq, pos, model, embedded_at)
+            VALUES (?1, ?2, ?3, ?4, ?5)
+            ",
+            params![hash, seq as i64, pos as i64, model, embedded_at],
+        )?;
+
+        let hash_seq = format!("{hash}_{seq}");
+        conn.execute(
+            "INSERT OR REPLACE INTO vectors_vec (hash_seq, embedding) VALUES (?1, ?2)",
+            params![hash_seq, self.quantization.encode(embedding)],
+        )?;
+
+        // Keep the IVF index current once it has been trained.
+        if !self.centroids.is_empty() {
+            let list_id = nearest_centroid(&self.centroids, embedding);
+            conn.execute(
+                "INSERT OR REPLACE INTO vectors_ivf_lists (hash_seq, list_id) VALUES (?1, ?2)",
+                params![hash_seq, list_id as i64],
+            )?;
+        }
+        Ok(())
+    }
+}
+
 impl Store {
     /// Create a new store with default database path.
     pub fn new() -> Result<Self> {
@@ -188,113 +767,50 @@ pub fn db_path(&self) -> &Path {
         &self.db_path
     }
 
-    /// Initialize database schema.
+    /// Begin a transaction on the shared connection.
+    pub(crate) fn transaction(&self) -> Result<Transaction<'_>> {
+        Ok(self.conn.unchecked_transaction()?)
+    }
+
+    /// Initialize the connection and bring the schema up to date.
     fn initialize(&mut self) -> Result<()> {
         self.conn.execute_batch(
             r"
             PRAGMA journal_mode = WAL;
             PRAGMA foreign_keys = ON;
-
-            -- Content-addressable storage
-            CREATE TABLE IF NOT EXISTS content (
-                hash TEXT PRIMARY KEY,
-                doc TEXT NOT NULL,
-                created_at TEXT NOT NULL
-            );
-
-            -- Documents table
-            CREATE TABLE IF NOT EXISTS documents (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                collection TEXT NOT NULL,
-                path TEXT NOT NULL,
-                title TEXT NOT NULL,
-                hash TEXT NOT NULL,
-                created_at TEXT NOT NULL,
-                modified_at TEXT NOT NULL,
-                active INTEGER NOT NULL DEFAULT 1,
-                FOREIGN KEY (hash) REFERENCES content(hash) ON DELETE CASCADE,
-                UNIQUE(collection, path)
-            );
-
-            CREATE INDEX IF NOT EXISTS idx_documents_collection ON documents(collection, active);
-            CREATE INDEX IF NOT EXISTS idx_documents_hash ON documents(hash);
-            CREATE INDEX IF NOT EXISTS idx_documents_path ON documents(path, active);
-
-            -- FTS index
-            CREATE VIRTUAL TABLE IF NOT EXISTS documents_fts USING fts5(
-                filepath, title, body,
-                tokenize='porter unicode61'
-            );
-
-            -- LLM cache
-            CREATE TABLE IF NOT EXISTS llm_cache (
-                hash TEXT PRIMARY KEY,
-                result TEXT NOT NULL,
-                created_at TEXT NOT NULL
-            );
-
-            -- Content vectors metadata
-            CREATE TABLE IF NOT EXISTS content_vectors (
-                hash TEXT NOT NULL,
-                seq INTEGER NOT NULL DEFAULT 0,
-                pos INTEGER NOT NULL DEFAULT 0,
-                model TEXT NOT NULL,
-                embedded_at TEXT NOT NULL,
-                PRIMARY KEY (hash, seq)
-            );
             ",
         )?;
-
-        // Create FTS triggers.
-        self.create_fts_triggers()?;
-
-        Ok(())
+        self.migrate()
     }
 
-    /// Create FTS synchronization triggers.
-    fn create_fts_triggers(&self) -> Result<()> {
-        // Check if triggers exist.
-        let trigger_exists: bool = self
+    /// Get the schema version of the open database.
+    pub fn schema_version(&self) -> Result<u32> {
+        let version: u32 = self
             .conn
-            .query_row(
-                "SELECT 1 FROM sqlite_master WHERE type='trigger' AND name='documents_ai'",
-                [],
-                |_| Ok(true),
-            )
-            .unwrap_or(false);
+            .query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        Ok(version)
+    }
 
-        if !trigger_exists {
-            self.conn.execute_batch(
-                r"
-                CREATE TRIGGER IF NOT EXISTS documents_ai AFTER INSERT ON documents
-                WHEN new.active = 1
-                BEGIN
-                    INSERT INTO documents_fts(rowid, filepath, title, body)
-                    SELECT
-                        new.id,
-                        new.collection || '/' || new.path,
-                        new.title,
-                        (SELECT doc FROM content WHERE hash = new.hash)
-                    WHERE new.active = 1;
-                END;
-
-                CREATE TRIGGER IF NOT EXISTS documents_ad AFTER DELETE ON documents BEGIN
-                    DELETE FROM documents_fts WHERE rowid = old.id;
-                END;
-
-                CREATE TRIGGER IF NOT EXISTS documents_au AFTER UPDATE ON documents
-                BEGIN
-                    DELETE FROM documents_fts WHERE rowid = old.id AND new.active = 0;
-                    INSERT OR REPLACE INTO documents_fts(rowid, filepath, title, body)
-                    SELECT
-                        new.id,
-                        new.collection || '/' || new.path,
-                        new.title,
-                        (SELECT doc FROM content WHERE hash = new.hash)
-                    WHERE new.active = 1;
-                END;
-                ",
-            )?;
+    /// Apply all pending migrations, each in its own transaction.
+    fn migrate(&mut self) -> Result<()> {
+        let current = self.schema_version()?;
+        if current > SCHEMA_VERSION {
+            return Err(QmdError::SchemaTooNew {
+                found: current,
+                supported: SCHEMA_VERSION,
+            });
+        }
+
+        for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+            let tx = self.conn.transaction()?;
+            (migration.apply)(&tx).map_err(|e| {
+                QmdError::General(format!(
+                    "Migration to schema v{} ({}) failed: {e}",
+                    migration.version, migration.description
+                ))
+            })?;
+            tx.pragma_update(None, "user_version", migration.version)?;
+            tx.commit()?;
         }
 
         Ok(())
@@ -315,9 +831,16 @@ pub fn get_docid(hash: &str) -> String {
     }
 
     /// Extract title from markdown content.
+    ///
+    /// A frontmatter `title` takes precedence over the first heading.
     #[must_use]
     pub fn extract_title(content: &str) -> String {
-        for line in content.lines() {
+        if let Some(MetaValue::Text(title)) = parse_frontmatter(content).get("title")
+            && !title.trim().is_empty()
+        {
+            return title.trim().to_string();
+        }
+        for line in split_frontmatter(content).1.lines() {
             let trimmed = line.trim();
             if trimmed.starts_with("# ") {
                 return trimmed[2..].trim().to_string();
@@ -348,15 +871,63 @@ pub fn handelize(path: &str) -> String {
             .join("/")
     }
 
-    /// Insert content into content-addressable storage.
+    /// Insert content into content-addressable storage, along with its
+    /// frontmatter fields.
     pub fn insert_content(&self, hash: &str, content: &str, created_at: &str) -> Result<()> {
-        self.conn.execute(
+        let inserted = self.conn.execute(
             "INSERT OR IGNORE INTO content (hash, doc, created_at) VALUES (?1, ?2, ?3)",
             params![hash, content, created_at],
         )?;
+        if inserted > 0 {
+            insert_metadata(&self.conn, hash, &parse_frontmatter(content))?;
+        }
         Ok(())
     }
 
+    /// Get the frontmatter fields of a content entry.
+    pub fn get_metadata(&self, hash: &str) -> Result<Metadata> {
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT key, value, list FROM document_meta WHERE hash = ?1 ORDER BY key, seq",
+        )?;
+        let rows = stmt.query_map(params![hash], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, bool>(2)?,
+            ))
+        })?;
+
+        let mut meta = Metadata::new();
+        for row in rows {
+            let (key, value, list) = row?;
+            if !list {
+                meta.insert(key, MetaValue::Text(value));
+            } else if let Some(MetaValue::List(items)) = meta.get_mut(&key) {
+                items.push(value);
+            } else {
+                meta.insert(key, MetaValue::List(vec![value]));
+            }
+        }
+        Ok(meta)
+    }
+
+    /// Names of the frontmatter fields of active documents.
+    pub fn get_metadata_keys(&self) -> Result<Vec<String>> {
+        let mut stmt = self.conn.prepare(
+            r"
+            SELECT DISTINCT m.key
+            FROM document_meta m
+            JOIN documents d ON d.hash = m.hash
+            WHERE d.active = 1
+            ORDER BY m.key
+            ",
+        )?;
+        let keys = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(keys)
+    }
+
     /// Insert a document record.
     pub fn insert_document(
         &self,
@@ -379,7 +950,7 @@ pub fn insert_document(
             ",
             params![collection, path, title, hash, created_at, modified_at],
         )?;
-        Ok(())
+        self.sync_extracted_at(collection, path)
     }
 
     /// Find an active document by collection and path.
@@ -425,7 +996,7 @@ pub fn update_document(
             "UPDATE documents SET title = ?1, hash = ?2, modified_at = ?3 WHERE id = ?4",
             params![title, hash, modified_at, document_id],
         )?;
-        Ok(())
+        self.sync_extracted(document_id)
     }
 
     /// Deactivate a document.
@@ -434,9 +1005,267 @@ pub fn deactivate_document(&self, collection: &str, path: &str) -> Result<()> {
             "UPDATE documents SET active = 0 WHERE collection = ?1 AND path = ?2",
             params![collection, path],
         )?;
+        self.sync_extracted_at(collection, path)
+    }
+
+    /// Replace a document's tags, links and headings with those of its
+    /// current content, or drop them if it is inactive.
+    fn sync_extracted(&self, document_id: i64) -> Result<()> {
+        self.conn.execute(
+            "DELETE FROM document_tags WHERE document_id = ?1",
+            params![document_id],
+        )?;
+        self.conn.execute(
+            "DELETE FROM document_links WHERE document_id = ?1",
+            params![document_id],
+        )?;
+        self.conn.execute(
+            "DELETE FROM document_headings WHERE document_id = ?1",
+            params![document_id],
+        )?;
+        let content: Option<(String, String)> = self
+            .conn
+            .query_row(
+                r"
+                SELECT d.path, c.doc
+                FROM documents d
+                JOIN content c ON c.hash = d.hash
+                WHERE d.id = ?1 AND d.active = 1
+                ",
+                params![document_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+        if let Some((path, doc)) = content {
+            insert_tags(&self.conn, document_id, &extract_tags(&doc))?;
+            insert_links(&self.conn, document_id, &path, &extract_links(&doc))?;
+            insert_headings(&self.conn, document_id, &extract_headings(&doc))?;
+        }
         Ok(())
     }
 
+    /// [`Store::sync_extracted`] for the document at a collection path.
+    fn sync_extracted_at(&self, collection: &str, path: &str) -> Result<()> {
+        let id: Option<i64> = self
+            .conn
+            .query_row(
+                "SELECT id FROM documents WHERE collection = ?1 AND path = ?2",
+                params![collection, path],
+                |row| row.get(0),
+            )
+            .optional()?;
+        match id {
+            Some(document_id) => self.sync_extracted(document_id),
+            None => Ok(()),
+        }
+    }
+
+    /// Heading outline of an active document, in document order.
+    pub fn get_outline(&self, collection: &str, path: &str) -> Result<Vec<Heading>> {
+        let mut stmt = self.conn.prepare_cached(
+            r"
+            SELECT h.level, h.text, h.slug, h.line, h.end_line
+            FROM document_headings h
+            JOIN documents d ON d.id = h.document_id
+            WHERE d.collection = ?1 AND d.path = ?2 AND d.active = 1
+            ORDER BY h.seq
+            ",
+        )?;
+        let headings = stmt
+            .query_map(params![collection, path], |row| {
+                let level: i64 = row.get(0)?;
+                let line: i64 = row.get(3)?;
+                let end_line: i64 = row.get(4)?;
+                Ok(Heading {
+                    level: level as usize,
+                    text: row.get(1)?,
+                    slug: row.get(2)?,
+                    line: line as usize,
+                    end_line: end_line as usize,
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(headings)
+    }
+
+    /// The recorded heading whose section most closely encloses `line` of
+    /// an active document: the last heading at or before it.
+    pub fn get_enclosing_heading(
+        &self,
+        collection: &str,
+        path: &str,
+        line: usize,
+    ) -> Result<Option<Heading>> {
+        let mut stmt = self.conn.prepare_cached(
+            r"
+            SELECT h.level, h.text, h.slug, h.line, h.end_line
+            FROM document_headings h
+            JOIN documents d ON d.id = h.document_id
+            WHERE d.collection = ?1 AND d.path = ?2 AND d.active = 1 AND h.line <= ?3
+            ORDER BY h.line DESC
+            LIMIT 1
+            ",
+        )?;
+        let heading = stmt
+            .query_row(params![collection, path, line as i64], |row| {
+                let level: i64 = row.get(0)?;
+                let start: i64 = row.get(3)?;
+                let end_line: i64 = row.get(4)?;
+                Ok(Heading {
+                    level: level as usize,
+                    text: row.get(1)?,
+                    slug: row.get(2)?,
+                    line: start as usize,
+                    end_line: end_line as usize,
+                })
+            })
+            .optional()?;
+        Ok(heading)
+    }
+
+    /// Links of active documents matching an SQL condition on `documents d`
+    /// and `document_links l`, resolved, by linking document and position.
+    pub(crate) fn query_links(&self, condition: &str, params: &[&dyn ToSql]) -> Result<Vec<Link>> {
+        let mut stmt = self.conn.prepare(&format!(
+            r"
+            SELECT d.collection, d.path, l.line, l.kind, l.text, l.target,
+                   COALESCE(l.target_collection, d.collection), l.target_path
+            FROM document_links l
+            JOIN documents d ON d.id = l.document_id
+            WHERE d.active = 1 AND ({condition})
+            ORDER BY d.collection, d.path, l.seq
+            "
+        ))?;
+        let rows = stmt
+            .query_map(params, |row| {
+                let line: i64 = row.get(2)?;
+                let kind: String = row.get(3)?;
+                Ok((
+                    Link {
+                        collection: row.get(0)?,
+                        path: row.get(1)?,
+                        line: line as usize,
+                        kind: LinkKind::from_name(&kind),
+                        text: row.get(4)?,
+                        target: row.get(5)?,
+                        resolved: None,
+                    },
+                    row.get::<_, String>(6)?,
+                    row.get::<_, Option<String>>(7)?,
+                ))
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        let mut links = Vec::with_capacity(rows.len());
+        for (mut link, target_collection, target_path) in rows {
+            if let Some(target) = target_path {
+                link.resolved = self
+                    .resolve_link(&target_collection, &target, link.kind)?
+                    .map(|resolved| (target_collection, resolved));
+            }
+            links.push(link);
+        }
+        Ok(links)
+    }
+
+    /// Path of the active document a link target resolves to: the exact
+    /// path or, for wiki links, the shortest path ending with it.
+    fn resolve_link(
+        &self,
+        collection: &str,
+        target: &str,
+        kind: LinkKind,
+    ) -> Result<Option<String>> {
+        let mut stmt = self.conn.prepare_cached(
+            r"
+            SELECT path
+            FROM documents
+            WHERE collection = ?1 AND active = 1
+              AND (path = ?2 OR (?3 AND path LIKE '%/' || ?2))
+            ORDER BY path != ?2, LENGTH(path), path
+            LIMIT 1
+            ",
+        )?;
+        Ok(stmt
+            .query_row(params![collection, target, kind == LinkKind::Wiki], |row| {
+                row.get(0)
+            })
+            .optional()?)
+    }
+
+    /// Count the tags of active documents matching `filter` per collection,
+    /// sorted by collection, then by descending count and tag.
+    pub(crate) fn tag_counts(&self, filter: &SearchFilter) -> Result<Vec<TagCount>> {
+        let (filter_sql, filter_params) = filter.to_sql("d", 1);
+        let mut stmt = self.conn.prepare(&format!(
+            r"
+            SELECT d.collection, t.tag, COUNT(*) AS n
+            FROM document_tags t
+            JOIN documents d ON d.id = t.document_id
+            WHERE d.active = 1{filter_sql}
+            GROUP BY d.collection, t.tag
+            ORDER BY d.collection, n DESC, t.tag
+            "
+        ))?;
+        let counts = stmt
+            .query_map(params_from_iter(filter_params), |row| {
+                let count: i64 = row.get(2)?;
+                Ok(TagCount {
+                    collection: row.get(0)?,
+                    tag: row.get(1)?,
+                    count: count as usize,
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(counts)
+    }
+
+    /// Active documents matching `filter`, in path order, without bodies.
+    pub(crate) fn list_documents(&self, filter: &SearchFilter) -> Result<Vec<DocumentResult>> {
+        let (filter_sql, filter_params) = filter.to_sql("d", 1);
+        let mut stmt = self.conn.prepare(&format!(
+            r"
+            SELECT d.collection, d.path, d.title, d.hash, d.modified_at, LENGTH(c.doc)
+            FROM documents d
+            JOIN content c ON c.hash = d.hash
+            WHERE d.active = 1{filter_sql}
+            ORDER BY d.collection, d.path
+            "
+        ))?;
+        let docs = stmt
+            .query_map(params_from_iter(filter_params), |row| {
+                let collection_name: String = row.get(0)?;
+                let path: String = row.get(1)?;
+                let hash: String = row.get(3)?;
+                let body_length: i64 = row.get(5)?;
+                Ok(DocumentResult {
+                    filepath: format!("qmd://{collection_name}/{path}"),
+                    display_path: format!("{collection_name}/{path}"),
+                    title: row.get(2)?,
+                    context: None,
+                    docid: Self::get_docid(&hash),
+                    hash,
+                    collection_name,
+                    path,
+                    modified_at: row.get(4)?,
+                    body_length: body_length as usize,
+                    body: None,
+                    meta: Metadata::new(),
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(docs
+            .into_iter()
+            .map(|mut doc| {
+                doc.context =
+                    find_context_for_path(&doc.collection_name, &doc.path).unwrap_or(None);
+                doc.meta = self.get_metadata(&doc.hash).unwrap_or_default();
+                doc
+            })
+            .collect())
+    }
+
     /// Get all active document paths for a collection.
     pub fn get_active_document_paths(&self, collection: &str) -> Result<Vec<String>> {
         let mut stmt = self
@@ -449,32 +1278,45 @@ pub fn get_active_document_paths(&self, collection: &str) -> Result<Vec<String>>
     }
 
     /// Full-text search using FTS5.
+    ///
+    /// The query uses the syntax of [`FtsQuery`], plus the inline filters of
+    /// [`SearchFilter::parse`], such as `path:notes/*`; malformed queries
+    /// return [`QmdError::InvalidQuery`]. Matches are ranked with the BM25
+    /// weights from the config; an unreadable config or invalid weights are
+    /// an error, as in [`FtsSearchOptions::from_config`].
     pub fn search_fts(
         &self,
         query: &str,
         limit: usize,
         collection: Option<&str>,
     ) -> Result<Vec<SearchResult>> {
-        let sql = if collection.is_some() {
-            r"
-            SELECT
-                d.collection,
-                d.path,
-                d.title,
-                d.hash,
-                d.modified_at,
-                bm25(documents_fts) as score,
-                LENGTH(c.doc) as body_length
-            FROM documents_fts fts
-            JOIN documents d ON d.id = fts.rowid
-            JOIN content c ON c.hash = d.hash
-            WHERE documents_fts MATCH ?1
-              AND d.collection = ?2
-              AND d.active = 1
-            ORDER BY score
-            LIMIT ?3
-            "
-        } else {
+        let (filter, text) = SearchFilter::parse(query)?;
+        self.search_fts_with(
+            &text,
+            limit,
+            &filter.with_collection(collection),
+            &FtsSearchOptions::from_config()?,
+        )
+    }
+
+    /// Full-text search restricted by a [`SearchFilter`] and ranked with
+    /// explicit BM25 weights.
+    ///
+    /// The filter is applied in SQL, so up to `limit` matching documents are
+    /// returned even when most hits are filtered out. Inline filters are not
+    /// split from `query` here; callers pass them through [`SearchFilter::parse`]
+    /// first, as [`Store::search_fts`] does.
+    pub fn search_fts_with(
+        &self,
+        query: &str,
+        limit: usize,
+        filter: &SearchFilter,
+        options: &FtsSearchOptions,
+    ) -> Result<Vec<SearchResult>> {
+        let fts_query = FtsQuery::parse(query)?.to_fts5();
+        let (bm25_sql, bm25_params) = options.bm25_sql("d", 2);
+        let (filter_sql, filter_params) = filter.to_sql("d", 2 + bm25_params.len());
+        let sql = format!(
             r"
             SELECT
                 d.collection,
@@ -482,22 +1324,28 @@ pub fn search_fts(
                 d.title,
                 d.hash,
                 d.modified_at,
-                bm25(documents_fts) as score,
+                {bm25_sql} as score,
                 LENGTH(c.doc) as body_length
             FROM documents_fts fts
             JOIN documents d ON d.id = fts.rowid
             JOIN content c ON c.hash = d.hash
             WHERE documents_fts MATCH ?1
-              AND d.active = 1
+              AND d.active = 1{filter_sql}
             ORDER BY score
-            LIMIT ?2
-            "
-        };
+            LIMIT ?{}
+            ",
+            bm25_params.len() + filter_params.len() + 2
+        );
 
-        let mut stmt = self.conn.prepare(sql)?;
+        let mut params = Vec::with_capacity(bm25_params.len() + filter_params.len() + 2);
+        params.push(Value::Text(fts_query));
+        params.extend(bm25_params);
+        params.extend(filter_params);
+        params.push(Value::Integer(limit as i64));
 
-        let results: Vec<SearchResult> = if let Some(coll) = collection {
-            stmt.query_map(params![query, coll, limit as i64], |row| {
+        let mut stmt = self.conn.prepare(&sql)?;
+        let results: Vec<SearchResult> = stmt
+            .query_map(params_from_iter(params), |row| {
                 let collection_name: String = row.get(0)?;
                 let path: String = row.get(1)?;
                 let title: String = row.get(2)?;
@@ -520,45 +1368,19 @@ pub fn search_fts(
                         modified_at,
                         body_length,
                         body: None,
+                        meta: Metadata::new(),
                     },
                     score: -score, // BM25 returns negative scores, higher is better.
                     source: SearchSource::Fts,
                     chunk_pos: None,
+                    chunk_seq: None,
+                    provenance: None,
+                    passage: None,
+                    snippets: Vec::new(),
+                    heading: None,
                 })
             })?
-            .collect::<std::result::Result<Vec<_>, _>>()?
-        } else {
-            stmt.query_map(params![query, limit as i64], |row| {
-                let collection_name: String = row.get(0)?;
-                let path: String = row.get(1)?;
-                let title: String = row.get(2)?;
-                let hash: String = row.get(3)?;
-                let modified_at: String = row.get(4)?;
-                let score: f64 = row.get(5)?;
-                let body_length: i64 = row.get(6)?;
-                let body_length = body_length as usize;
-
-                Ok(SearchResult {
-                    doc: DocumentResult {
-                        filepath: format!("qmd://{collection_name}/{path}"),
-                        display_path: format!("{collection_name}/{path}"),
-                        title,
-                        context: None,
-                        hash: hash.clone(),
-                        docid: Self::get_docid(&hash),
-                        collection_name,
-                        path,
-                        modified_at,
-                        body_length,
-                        body: None,
-                    },
-                    score: -score,
-                    source: SearchSource::Fts,
-                    chunk_pos: None,
-                })
-            })?
-            .collect::<std::result::Result<Vec<_>, _>>()?
-        };
+            .collect::<std::result::Result<Vec<_>, _>>()?;
 
         // Add context to results.
         let results_with_context: Vec<SearchResult> = results
@@ -566,6 +1388,7 @@ pub fn search_fts(
             .map(|mut r| {
                 r.doc.context =
                     find_context_for_path(&r.doc.collection_name, &r.doc.path).unwrap_or(None);
+                r.doc.meta = self.get_metadata(&r.doc.hash).unwrap_or_default();
                 r
             })
             .collect();
@@ -573,6 +1396,126 @@ pub fn search_fts(
         Ok(results_with_context)
     }
 
+    /// Body of an active document with the matches of an FTS5 expression
+    /// wrapped in `open` and `close`, or `None` if it does not match.
+    pub(crate) fn highlight_body(
+        &self,
+        collection: &str,
+        path: &str,
+        fts_query: &str,
+        open: char,
+        close: char,
+    ) -> Result<Option<String>> {
+        let marked = self
+            .conn
+            .query_row(
+                r"
+                SELECT highlight(documents_fts, 2, ?1, ?2)
+                FROM documents_fts fts
+                JOIN documents d ON d.id = fts.rowid
+                WHERE documents_fts MATCH ?3
+                  AND d.collection = ?4 AND d.path = ?5 AND d.active = 1
+                ",
+                params![
+                    open.to_string(),
+                    close.to_string(),
+                    fts_query,
+                    collection,
+                    path
+                ],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(marked)
+    }
+
+    /// Whether the trigram substring index is enabled.
+    pub fn has_trigram_index(&self) -> Result<bool> {
+        let enabled = self
+            .conn
+            .query_row("SELECT 1 FROM index_meta WHERE key = 'trigram'", [], |_| {
+                Ok(true)
+            })
+            .optional()?;
+        Ok(enabled.unwrap_or(false))
+    }
+
+    /// Build or drop the trigram substring index used by [`Store::grep`].
+    ///
+    /// Once enabled, the FTS triggers keep it in sync with every document
+    /// change. Returns the number of documents indexed.
+    pub fn set_trigram_index(&self, enabled: bool) -> Result<usize> {
+        let tx = self.transaction()?;
+        tx.execute("DELETE FROM documents_trigram", [])?;
+        let indexed = if enabled {
+            tx.execute(
+                "INSERT OR REPLACE INTO index_meta (key, value) VALUES ('trigram', '1')",
+                [],
+            )?;
+            tx.execute(
+                r"
+                INSERT INTO documents_trigram(rowid, body)
+                SELECT d.id, c.doc
+                FROM documents d
+                JOIN content c ON c.hash = d.hash
+                WHERE d.active = 1
+                ",
+                [],
+            )?
+        } else {
+            tx.execute("DELETE FROM index_meta WHERE key = 'trigram'", [])?;
+            0
+        };
+        tx.commit()?;
+        Ok(indexed)
+    }
+
+    /// Visit the active documents to scan for a grep as `(collection, path,
+    /// body)`, in path order, until `visit` breaks.
+    ///
+    /// With a trigram `prefilter` expression only documents matching it are
+    /// visited; callers must only pass one when the index is enabled.
+    pub(crate) fn for_each_grep_candidate(
+        &self,
+        prefilter: Option<&str>,
+        filter: &SearchFilter,
+        mut visit: impl FnMut(&str, &str, &str) -> ControlFlow<()>,
+    ) -> Result<()> {
+        let mut params: Vec<Value> = Vec::new();
+        let (source, condition) = if let Some(expr) = prefilter {
+            params.push(Value::Text(expr.to_string()));
+            (
+                "documents_trigram t JOIN documents d ON d.id = t.rowid",
+                "documents_trigram MATCH ?1 AND ",
+            )
+        } else {
+            ("documents d", "")
+        };
+        let (filter_sql, filter_params) = filter.to_sql("d", params.len() + 1);
+        params.extend(filter_params);
+
+        let sql = format!(
+            r"
+            SELECT d.collection, d.path, c.doc
+            FROM {source}
+            JOIN content c ON c.hash = d.hash
+            WHERE {condition}d.active = 1{filter_sql}
+            ORDER BY d.collection, d.path
+            "
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+        let mut rows = stmt.query(params_from_iter(params))?;
+        while let Some(row) = rows.next()? {
+            let collection: String = row.get(0)?;
+            let path: String = row.get(1)?;
+            let body: String = row.get(2)?;
+            if visit(&collection, &path, &body).is_break() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
     /// Get document by collection and path.
     pub fn get_document(&self, collection: &str, path: &str) -> Result<Option<DocumentResult>> {
         let result = self
@@ -610,20 +1553,34 @@ pub fn get_document(&self, collection: &str, path: &str) -> Result<Option<Docume
                         modified_at,
                         body_length,
                         body: Some(body),
+                        meta: Metadata::new(),
                     })
                 },
             )
             .optional()?;
 
-        // Add context if document found.
+        // Add context and frontmatter if document found.
         let result = result.map(|mut doc| {
             doc.context = find_context_for_path(collection, path).unwrap_or(None);
+            doc.meta = self.get_metadata(&doc.hash).unwrap_or_default();
             doc
         });
 
         Ok(result)
     }
 
+    /// Get the stored content for a hash.
+    pub fn get_content(&self, hash: &str) -> Result<Option<String>> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT doc FROM content WHERE hash = ?1",
+                params![hash],
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+
     /// Get document by docid (first 6 chars of hash).
     pub fn find_document_by_docid(&self, docid: &str) -> Result<Option<(String, String)>> {
         let clean_docid = docid.trim_start_matches('#');
@@ -712,6 +1669,7 @@ pub fn get_status(&self) -> Result<IndexStatus> {
             needs_embedding,
             has_vector_index,
             collections,
+            schema_version: self.schema_version()?,
         })
     }
 
@@ -762,6 +1720,26 @@ pub fn cleanup_orphaned_vectors(&self) -> Result<usize> {
             ",
             [],
         )?;
+
+        self.conn.execute(
+            r"
+            DELETE FROM vectors_ivf_lists
+            WHERE substr(hash_seq, 1, instr(hash_seq, '_') - 1)
+                NOT IN (SELECT DISTINCT hash FROM documents WHERE active = 1)
+            ",
+            [],
+        )?;
+        if self.has_vector_table()? {
+            self.conn.execute(
+                r"
+                DELETE FROM vectors_vec
+                WHERE substr(hash_seq, 1, instr(hash_seq, '_') - 1)
+                    NOT IN (SELECT DISTINCT hash FROM documents WHERE active = 1)
+                ",
+                [],
+            )?;
+        }
+
         Ok(changes)
     }
 
@@ -785,8 +1763,21 @@ pub fn vacuum(&self) -> Result<()> {
         Ok(())
     }
 
+    /// Check whether the `vectors_vec` table exists.
+    fn has_vector_table(&self) -> Result<bool> {
+        let exists = self
+            .conn
+            .query_row(
+                "SELECT 1 FROM sqlite_master WHERE type='table' AND name='vectors_vec'",
+                [],
+                |_| Ok(true),
+            )
+            .optional()?;
+        Ok(exists.unwrap_or(false))
+    }
+
     /// Ensure the vector table exists with the correct dimensions.
-    pub fn ensure_vector_table(&self, _dimensions: usize) -> Result<()> {
+    pub fn ensure_vector_table(&self, dimensions: usize) -> Result<()> {
         // Create vectors_vec table for storing embeddings
         self.conn.execute(
             r"
@@ -797,10 +1788,124 @@ pub fn ensure_vector_table(&self, _dimensions: usize) -> Result<()> {
                 ",
             [],
         )?;
+        self.set_vector_meta("dimensions", &dimensions.to_string())?;
         Ok(())
     }
 
+    /// Read a vector storage setting.
+    fn get_vector_meta(&self, key: &str) -> Result<Option<String>> {
+        let value = self
+            .conn
+            .query_row(
+                "SELECT value FROM vectors_meta WHERE key = ?1",
+                params![key],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(value)
+    }
+
+    /// Write a vector storage setting.
+    fn set_vector_meta(&self, key: &str, value: &str) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO vectors_meta (key, value) VALUES (?1, ?2)",
+            params![key, value],
+        )?;
+        Ok(())
+    }
+
+    /// Get the storage format of the vector index.
+    pub fn get_vector_quantization(&self) -> Result<Quantization> {
+        match self.get_vector_meta("quantization")? {
+            None => Ok(Quantization::F32),
+            Some(name) => Quantization::parse(&name)
+                .ok_or_else(|| QmdError::Config(format!("Unknown vector quantization: {name}"))),
+        }
+    }
+
+    /// Get the chunker the stored embeddings were made with.
+    ///
+    /// Indexes embedded before the chunker was recorded used
+    /// [`Chunker::Tokens`].
+    pub fn get_vector_chunker(&self) -> Result<Chunker> {
+        match self.get_vector_meta("chunker")? {
+            None => Ok(Chunker::Tokens),
+            Some(name) => Chunker::parse(&name)
+                .ok_or_else(|| QmdError::Config(format!("Unknown chunker: {name}"))),
+        }
+    }
+
+    /// Record `chunker` for the embeddings about to be stored.
+    ///
+    /// Fails while embeddings made with another chunker exist, since an
+    /// index mixing both would rank their chunks inconsistently.
+    pub(crate) fn use_vector_chunker(&self, chunker: Chunker) -> Result<()> {
+        let recorded = self.get_vector_chunker()?;
+        if recorded != chunker && self.get_vector_count()? > 0 {
+            return Err(QmdError::Config(format!(
+                "Existing embeddings were chunked with the {} chunker; \
+                 re-embed with 'qmd embed --force --chunker {}' to switch",
+                recorded.as_str(),
+                chunker.as_str()
+            )));
+        }
+        self.set_vector_meta("chunker", chunker.as_str())
+    }
+
+    /// Get the embedding dimensions recorded for the vector index (0 if unknown).
+    fn get_vector_dimensions(&self) -> Result<usize> {
+        Ok(self
+            .get_vector_meta("dimensions")?
+            .and_then(|d| d.parse().ok())
+            .unwrap_or(0))
+    }
+
+    /// Convert every stored vector to a new storage format.
+    ///
+    /// Conversions may only lose precision: `f32 -> int8 -> binary`. Going
+    /// the other way requires re-embedding. Returns the number of vectors
+    /// rewritten.
+    pub fn requantize(&self, target: Quantization) -> Result<usize> {
+        let current = self.get_vector_quantization()?;
+        if current == target {
+            return Ok(0);
+        }
+        if !current.can_convert_to(target) && self.get_vector_count()? > 0 {
+            return Err(QmdError::Config(format!(
+                "Cannot convert {} vectors to {}; re-embed with 'qmd embed --force' instead",
+                current.as_str(),
+                target.as_str()
+            )));
+        }
+
+        let dims = self.get_vector_dimensions()?;
+        let mut converted = 0;
+        let tx = self.conn.unchecked_transaction()?;
+        if self.has_vector_table()? {
+            let mut update =
+                tx.prepare("UPDATE vectors_vec SET embedding = ?1 WHERE hash_seq = ?2")?;
+            let mut stmt = tx.prepare("SELECT hash_seq, embedding FROM vectors_vec")?;
+            let mut rows = stmt.query([])?;
+            while let Some(row) = rows.next()? {
+                let hash_seq: String = row.get(0)?;
+                let embedding = current.decode(&row.get::<_, Vec<u8>>(1)?, dims);
+                update.execute(params![target.encode(&embedding), hash_seq])?;
+                converted += 1;
+            }
+        }
+        tx.execute(
+            "INSERT OR REPLACE INTO vectors_meta (key, value) VALUES ('quantization', ?1)",
+            params![target.as_str()],
+        )?;
+        tx.commit()?;
+
+        Ok(converted)
+    }
+
     /// Insert an embedding for a content hash.
+    ///
+    /// Loads the vector format and IVF centroids on every call; use
+    /// [`Store::vector_writer`] to insert many embeddings.
     pub fn insert_embedding(
         &self,
         hash: &str,
@@ -810,25 +1915,125 @@ pub fn insert_embedding(
         model: &str,
         embedded_at: &str,
     ) -> Result<()> {
-        // Insert metadata
-        self.conn.execute(
-            r"
-            INSERT OR REPLACE INTO content_vectors (hash, seq, pos, model, embedded_at)
-            VALUES (?1, ?2, ?3, ?4, ?5)
-            ",
-            params![hash, seq as i64, pos as i64, model, embedded_at],
-        )?;
+        self.vector_writer()?
+            .insert(hash, seq, pos, embedding, model, embedded_at)
+    }
+
+    /// Load the vector format and IVF centroids once for a batch of inserts.
+    ///
+    /// The writer must be reloaded after the vector format or the IVF index
+    /// changes.
+    pub fn vector_writer(&self) -> Result<VectorWriter<'_>> {
+        Ok(VectorWriter {
+            store: self,
+            quantization: self.get_vector_quantization()?,
+            centroids: self.load_ivf_centroids()?,
+        })
+    }
+
+    /// Load the IVF centroids, ordered by list id.
+    fn load_ivf_centroids(&self) -> Result<Vec<Vec<f32>>> {
+        let mut stmt = self
+            .conn
+            .prepare_cached("SELECT centroid FROM vectors_ivf_centroids ORDER BY list_id")?;
+        let centroids = stmt
+            .query_map([], |row| row.get::<_, Vec<u8>>(0))?
+            .map(|bytes| bytes.map(|b| decode_f32(&b)))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(centroids)
+    }
+
+    /// Get the number of stored vectors.
+    pub fn get_vector_count(&self) -> Result<usize> {
+        if !self.has_vector_table()? {
+            return Ok(0);
+        }
+        let count: i64 = self
+            .conn
+            .query_row("SELECT COUNT(*) FROM vectors_vec", [], |row| row.get(0))?;
+        Ok(count as usize)
+    }
 
-        // Insert vector data
-        let hash_seq = format!("{hash}_{seq}");
-        let embedding_bytes: Vec<u8> = embedding.iter().flat_map(|f| f.to_le_bytes()).collect();
+    /// Get the number of IVF lists (0 when no ANN index has been built).
+    pub fn get_vector_index_lists(&self) -> Result<usize> {
+        let count: i64 =
+            self.conn
+                .query_row("SELECT COUNT(*) FROM vectors_ivf_centroids", [], |row| {
+                    row.get(0)
+                })?;
+        Ok(count as usize)
+    }
 
-        self.conn.execute(
-            "INSERT OR REPLACE INTO vectors_vec (hash_seq, embedding) VALUES (?1, ?2)",
-            params![hash_seq, embedding_bytes],
-        )?;
+    /// Rebuild the IVF index from all stored vectors.
+    ///
+    /// Returns the number of lists in the new index.
+    pub fn build_vector_index(&self) -> Result<usize> {
+        let total = self.get_vector_count()?;
+        let nlist = ivf_list_count(total);
+        let quantization = self.get_vector_quantization()?;
+        let dims = self.get_vector_dimensions()?;
+
+        // Train on an evenly strided sample to bound memory and time.
+        let stride = total.div_ceil(IVF_MAX_TRAINING_SAMPLES).max(1);
+        let mut sample = Vec::new();
+        {
+            let mut stmt = self
+                .conn
+                .prepare("SELECT embedding FROM vectors_vec ORDER BY hash_seq")?;
+            let mut rows = stmt.query([])?;
+            let mut i = 0usize;
+            while let Some(row) = rows.next()? {
+                if i.is_multiple_of(stride) {
+                    sample.push(quantization.decode(&row.get::<_, Vec<u8>>(0)?, dims));
+                }
+                i += 1;
+            }
+        }
+        let centroids = train_centroids(&sample, nlist, IVF_TRAIN_ITERATIONS);
+        drop(sample);
 
-        Ok(())
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute("DELETE FROM vectors_ivf_centroids", [])?;
+        tx.execute("DELETE FROM vectors_ivf_lists", [])?;
+        {
+            let mut insert_centroid = tx
+                .prepare("INSERT INTO vectors_ivf_centroids (list_id, centroid) VALUES (?1, ?2)")?;
+            for (list_id, centroid) in centroids.iter().enumerate() {
+                insert_centroid.execute(params![list_id as i64, encode_f32(centroid)])?;
+            }
+
+            let mut insert_list =
+                tx.prepare("INSERT INTO vectors_ivf_lists (hash_seq, list_id) VALUES (?1, ?2)")?;
+            let mut stmt = tx.prepare("SELECT hash_seq, embedding FROM vectors_vec")?;
+            let mut rows = stmt.query([])?;
+            while let Some(row) = rows.next()? {
+                let hash_seq: String = row.get(0)?;
+                let embedding = quantization.decode(&row.get::<_, Vec<u8>>(1)?, dims);
+                let list_id = nearest_centroid(&centroids, &embedding);
+                insert_list.execute(params![hash_seq, list_id as i64])?;
+            }
+        }
+        tx.commit()?;
+
+        Ok(centroids.len())
+    }
+
+    /// Build or rebuild the IVF index when the vector count calls for it.
+    ///
+    /// The index is trained once the store holds `IVF_MIN_VECTORS` vectors and
+    /// retrained whenever it has grown enough to warrant twice as many lists.
+    /// Returns `true` if the index was (re)built.
+    pub fn optimize_vector_index(&self) -> Result<bool> {
+        let total = self.get_vector_count()?;
+        if total < IVF_MIN_VECTORS {
+            return Ok(false);
+        }
+        let lists = self.get_vector_index_lists()?;
+        if lists > 0 && ivf_list_count(total) < lists * 2 {
+            return Ok(false);
+        }
+        self.build_vector_index()?;
+        Ok(true)
     }
 
     /// Get hashes that need embedding.
@@ -851,6 +2056,8 @@ pub fn get_hashes_needing_embedding(&self) -> Result<Vec<(String, String, String
     }
 
     /// Get embedding for a hash.
+    ///
+    /// Quantized vectors are decoded to an `f32` approximation.
     pub fn get_embedding(&self, hash: &str, seq: usize) -> Result<Option<Vec<f32>>> {
         let hash_seq = format!("{hash}_{seq}");
         let result: Option<Vec<u8>> = self
@@ -862,12 +2069,9 @@ pub fn get_embedding(&self, hash: &str, seq: usize) -> Result<Option<Vec<f32>>>
             )
             .optional()?;
 
-        Ok(result.map(|bytes| {
-            bytes
-                .chunks_exact(4)
-                .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
-                .collect()
-        }))
+        let quantization = self.get_vector_quantization()?;
+        let dims = self.get_vector_dimensions()?;
+        Ok(result.map(|bytes| quantization.decode(&bytes, dims)))
     }
 
     /// Vector similarity search.
@@ -877,106 +2081,175 @@ pub fn search_vec(
         limit: usize,
         collection: Option<&str>,
     ) -> Result<Vec<SearchResult>> {
-        // Get all embeddings and compute similarity
-        let sql = if collection.is_some() {
-            r"
-            SELECT DISTINCT
-                d.collection,
-                d.path,
-                d.title,
-                d.hash,
-                d.modified_at,
-                LENGTH(c.doc) as body_length,
-                v.hash_seq
-            FROM documents d
-            JOIN content c ON c.hash = d.hash
-            JOIN vectors_vec v ON v.hash_seq = d.hash || '_0'
-            WHERE d.active = 1 AND d.collection = ?1
-            "
+        self.search_vec_with(
+            query_embedding,
+            limit,
+            &SearchFilter::collection(collection),
+            &VecSearchOptions::default(),
+        )
+    }
+
+    /// Vector similarity search with a [`SearchFilter`] and explicit ANN
+    /// options.
+    ///
+    /// Uses the IVF index when one has been built and the store holds at least
+    /// `options.exact_threshold` vectors; otherwise every vector is scored.
+    /// With a non-empty filter only the vectors of matching documents are
+    /// scored, exactly: probing the nearest IVF lists could miss them all.
+    pub fn search_vec_with(
+        &self,
+        query_embedding: &[f32],
+        limit: usize,
+        filter: &SearchFilter,
+        options: &VecSearchOptions,
+    ) -> Result<Vec<SearchResult>> {
+        let total = self.get_vector_count()?;
+        if total == 0 || limit == 0 {
+            return Ok(Vec::new());
+        }
+
+        // Pick the IVF lists to probe, or None for an exhaustive scan.
+        let probe_lists = if filter.is_empty() && total >= options.exact_threshold {
+            let centroids = self.load_ivf_centroids()?;
+            (options.probes < centroids.len())
+                .then(|| nearest_centroids(&centroids, query_embedding, options.probes.max(1)))
         } else {
-            r"
-            SELECT DISTINCT
-                d.collection,
-                d.path,
-                d.title,
-                d.hash,
-                d.modified_at,
-                LENGTH(c.doc) as body_length,
-                v.hash_seq
-            FROM documents d
-            JOIN content c ON c.hash = d.hash
-            JOIN vectors_vec v ON v.hash_seq = d.hash || '_0'
-            WHERE d.active = 1
-            "
+            None
+        };
+
+        let (filter_sql, filter_params) = filter.to_sql("d", 1);
+        let hashes_sql =
+            format!("SELECT DISTINCT d.hash FROM documents d WHERE d.active = 1{filter_sql}");
+        let active_hashes: HashSet<String> = {
+            let mut stmt = self.conn.prepare(&hashes_sql)?;
+            stmt.query_map(params_from_iter(filter_params.clone()), |row| row.get(0))?
+                .collect::<std::result::Result<_, _>>()?
+        };
+
+        let mut scan_params = Vec::new();
+        let sql = match &probe_lists {
+            None if !filter.is_empty() => {
+                scan_params = filter_params;
+                format!(
+                    r"
+                    SELECT v.hash_seq, v.embedding
+                    FROM content_vectors cv
+                    JOIN vectors_vec v ON v.hash_seq = cv.hash || '_' || cv.seq
+                    WHERE cv.hash IN ({hashes_sql})
+                    "
+                )
+            }
+            Some(lists) => {
+                let ids: Vec<String> = lists.iter().map(ToString::to_string).collect();
+                format!(
+                    r"
+                    SELECT v.hash_seq, v.embedding
+                    FROM vectors_ivf_lists l
+                    JOIN vectors_vec v ON v.hash_seq = l.hash_seq
+                    WHERE l.list_id IN ({})
+                    ",
+                    ids.join(", ")
+                )
+            }
+            None => "SELECT hash_seq, embedding FROM vectors_vec".to_string(),
         };
 
-        let mut stmt = self.conn.prepare(sql)?;
-
-        let rows: Vec<(String, String, String, String, String, usize, String)> =
-            if let Some(coll) = collection {
-                stmt.query_map(params![coll], |row| {
-                    let body_length: i64 = row.get(5)?;
-                    Ok((
-                        row.get(0)?,
-                        row.get(1)?,
-                        row.get(2)?,
-                        row.get(3)?,
-                        row.get(4)?,
-                        body_length as usize,
-                        row.get(6)?,
-                    ))
-                })?
-                .collect::<std::result::Result<Vec<_>, _>>()?
+        let quantization = self.get_vector_quantization()?;
+        let dims = self.get_vector_dimensions()?;
+        let query_code = quantization.encode(query_embedding);
+
+        // Score candidate vectors, grouped by content hash. Quantized vectors
+        // get a cheap first pass on their codes and keep the code for
+        // rescoring.
+        let mut chunk_scores: HashMap<String, Vec<(usize, f32, Vec<u8>)>> = HashMap::new();
+        let mut stmt = self.conn.prepare(&sql)?;
+        let mut rows = stmt.query(params_from_iter(scan_params))?;
+        while let Some(row) = rows.next()? {
+            let hash_seq: String = row.get(0)?;
+            let Some((hash, seq)) = hash_seq.rsplit_once('_') else {
+                continue;
+            };
+            let Ok(seq) = seq.parse::<usize>() else {
+                continue;
+            };
+            if !active_hashes.contains(hash) {
+                continue;
+            }
+            let bytes: Vec<u8> = row.get(1)?;
+            let chunk = if quantization == Quantization::F32 {
+                let similarity = cosine_similarity(query_embedding, &decode_f32(&bytes));
+                (seq, similarity, Vec::new())
             } else {
-                stmt.query_map([], |row| {
-                    let body_length: i64 = row.get(5)?;
-                    Ok((
-                        row.get(0)?,
-                        row.get(1)?,
-                        row.get(2)?,
-                        row.get(3)?,
-                        row.get(4)?,
-                        body_length as usize,
-                        row.get(6)?,
-                    ))
-                })?
-                .collect::<std::result::Result<Vec<_>, _>>()?
+                let similarity = quantization.approx_similarity(&query_code, &bytes);
+                (seq, similarity, bytes)
             };
+            chunk_scores
+                .entry(hash.to_string())
+                .or_default()
+                .push(chunk);
+        }
 
-        // Compute similarities
-        let mut results: Vec<SearchResult> = Vec::new();
+        // Aggregate chunk scores to one score per hash, remembering the best
+        // chunk, best first.
+        let rank = |groups: &HashMap<String, Vec<(usize, f32, Vec<u8>)>>| {
+            let mut ranked: Vec<(String, f32, usize)> = groups
+                .iter()
+                .map(|(hash, chunks)| {
+                    let best_seq = chunks
+                        .iter()
+                        .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+                        .map_or(0, |c| c.0);
+                    let scores: Vec<f32> = chunks.iter().map(|c| c.1).collect();
+                    (
+                        hash.clone(),
+                        options.aggregation.aggregate(&scores),
+                        best_seq,
+                    )
+                })
+                .collect();
+            ranked.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+            ranked
+        };
 
-        for (collection_name, path, title, hash, modified_at, body_length, _hash_seq) in rows {
-            if let Some(doc_embedding) = self.get_embedding(&hash, 0)? {
-                let similarity = crate::llm::cosine_similarity(query_embedding, &doc_embedding);
+        // Rescore every chunk of the best quantized documents against the
+        // full-precision query. The stored side stays quantized, so this is an
+        // asymmetric distance: it refines the query's rounding error, not the
+        // document's.
+        if quantization != Quantization::F32 {
+            let keep = limit.saturating_mul(options.oversample).max(limit);
+            let best: HashSet<String> = rank(&chunk_scores)
+                .into_iter()
+                .take(keep)
+                .map(|(hash, _, _)| hash)
+                .collect();
+            chunk_scores.retain(|hash, _| best.contains(hash));
+            for chunk in chunk_scores.values_mut().flatten() {
+                chunk.1 = cosine_similarity(query_embedding, &quantization.decode(&chunk.2, dims));
+            }
+        }
+        let ranked = rank(&chunk_scores);
 
+        // Resolve hashes to documents, best first.
+        let mut results: Vec<SearchResult> = Vec::new();
+        for (hash, similarity, seq) in ranked {
+            if results.len() >= limit {
+                break;
+            }
+            let pos = self.get_chunk_pos(&hash, seq)?;
+            for doc in self.get_documents_by_hash(&hash, filter)? {
                 results.push(SearchResult {
-                    doc: DocumentResult {
-                        filepath: format!("qmd://{collection_name}/{path}"),
-                        display_path: format!("{collection_name}/{path}"),
-                        title,
-                        context: None,
-                        hash: hash.clone(),
-                        docid: Self::get_docid(&hash),
-                        collection_name: collection_name.clone(),
-                        path: path.clone(),
-                        modified_at,
-                        body_length,
-                        body: None,
-                    },
+                    doc,
                     score: f64::from(similarity),
                     source: SearchSource::Vec,
-                    chunk_pos: Some(0), // First chunk (chunk position tracking)
+                    chunk_pos: Some(pos),
+                    chunk_seq: Some(seq),
+                    provenance: None,
+                    passage: None,
+                    snippets: Vec::new(),
+                    heading: None,
                 });
             }
         }
-
-        // Sort by similarity (descending) and limit
-        results.sort_by(|a, b| {
-            b.score
-                .partial_cmp(&a.score)
-                .unwrap_or(std::cmp::Ordering::Equal)
-        });
         results.truncate(limit);
 
         // Add context
@@ -985,6 +2258,7 @@ pub fn search_vec(
             .map(|mut r| {
                 r.doc.context =
                     find_context_for_path(&r.doc.collection_name, &r.doc.path).unwrap_or(None);
+                r.doc.meta = self.get_metadata(&r.doc.hash).unwrap_or_default();
                 r
             })
             .collect();
@@ -992,10 +2266,66 @@ pub fn search_vec(
         Ok(results_with_context)
     }
 
+    /// Get the byte offset of an embedded chunk.
+    fn get_chunk_pos(&self, hash: &str, seq: usize) -> Result<usize> {
+        let pos: Option<i64> = self
+            .conn
+            .query_row(
+                "SELECT pos FROM content_vectors WHERE hash = ?1 AND seq = ?2",
+                params![hash, seq as i64],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(pos.map_or(0, |p| p as usize))
+    }
+
+    /// Get active documents with the given content hash.
+    fn get_documents_by_hash(
+        &self,
+        hash: &str,
+        filter: &SearchFilter,
+    ) -> Result<Vec<DocumentResult>> {
+        let (filter_sql, filter_params) = filter.to_sql("d", 2);
+        let mut stmt = self.conn.prepare_cached(&format!(
+            r"
+            SELECT d.collection, d.path, d.title, d.modified_at, LENGTH(c.doc)
+            FROM documents d
+            JOIN content c ON c.hash = d.hash
+            WHERE d.hash = ?1 AND d.active = 1{filter_sql}
+            ORDER BY d.collection, d.path
+            "
+        ))?;
+        let params = std::iter::once(Value::Text(hash.to_string())).chain(filter_params);
+        let docs = stmt
+            .query_map(params_from_iter(params), |row| {
+                let collection_name: String = row.get(0)?;
+                let path: String = row.get(1)?;
+                let body_length: i64 = row.get(4)?;
+                Ok(DocumentResult {
+                    filepath: format!("qmd://{collection_name}/{path}"),
+                    display_path: format!("{collection_name}/{path}"),
+                    title: row.get(2)?,
+                    context: None,
+                    hash: hash.to_string(),
+                    docid: Self::get_docid(hash),
+                    collection_name,
+                    path,
+                    modified_at: row.get(3)?,
+                    body_length: body_length as usize,
+                    body: None,
+                    meta: Metadata::new(),
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(docs)
+    }
+
     /// Clear all embeddings.
     pub fn clear_embeddings(&self) -> Result<usize> {
         let changes1 = self.conn.execute("DELETE FROM content_vectors", [])?;
         let _ = self.conn.execute("DELETE FROM vectors_vec", []);
+        self.conn.execute("DELETE FROM vectors_ivf_lists", [])?;
+        self.conn.execute("DELETE FROM vectors_ivf_centroids", [])?;
         Ok(changes1)
     }
 
@@ -1042,6 +2372,62 @@ pub fn list_files(
         Ok(files)
     }
 
+    /// List active files of all configured collections in collection and
+    /// path order, starting after `after` (a collection and path) and
+    /// returning at most `limit`.
+    ///
+    /// Returns `(collection, path, title, size)`, with the size in bytes.
+    pub fn list_files_page(
+        &self,
+        after: Option<(&str, &str)>,
+        limit: usize,
+    ) -> Result<Vec<(String, String, String, usize)>> {
+        let names: Vec<String> = yaml_list_collections()?
+            .into_iter()
+            .map(|c| c.name)
+            .collect();
+        self.list_collection_files_page(&names, after, limit)
+    }
+
+    /// [`Store::list_files_page`] over the given collections.
+    fn list_collection_files_page(
+        &self,
+        names: &[String],
+        after: Option<(&str, &str)>,
+        limit: usize,
+    ) -> Result<Vec<(String, String, String, usize)>> {
+        if names.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let (after_collection, after_path) = after.unwrap_or_default();
+        let placeholders = vec!["?"; names.len()].join(", ");
+        let sql = format!(
+            r"
+            SELECT d.collection, d.path, d.title, LENGTH(CAST(c.doc AS BLOB)) as size
+            FROM documents d
+            JOIN content c ON d.hash = c.hash
+            WHERE d.active = 1 AND d.collection IN ({placeholders})
+              AND (d.collection, d.path) > (?, ?)
+            ORDER BY d.collection, d.path
+            LIMIT ?
+            "
+        );
+        let mut values: Vec<Value> = names.iter().cloned().map(Value::Text).collect();
+        values.push(Value::Text(after_collection.to_string()));
+        values.push(Value::Text(after_path.to_string()));
+        values.push(Value::Integer(i64::try_from(limit).unwrap_or(i64::MAX)));
+
+        let mut stmt = self.conn.prepare(&sql)?;
+        let files = stmt
+            .query_map(params_from_iter(values), |row| {
+                let size: i64 = row.get(3)?;
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, size as usize))
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(files)
+    }
+
     /// Get index health information.
     pub fn get_index_health(&self) -> Result<crate::llm::IndexHealth> {
         // Total documents
@@ -1278,6 +2664,7 @@ pub fn match_files_by_glob(store: &Store, pattern: &str) -> Result<Vec<DocumentR
                 let display_path = build_virtual_path(&collection, &path);
                 let docid = Store::get_docid(&hash);
                 let context = find_context_for_path(&collection, &path).ok().flatten();
+                let meta = store.get_metadata(&hash).unwrap_or_default();
 
                 DocumentResult {
                     filepath: display_path.clone(),
@@ -1291,6 +2678,7 @@ pub fn match_files_by_glob(store: &Store, pattern: &str) -> Result<Vec<DocumentR
                     modified_at,
                     body_length: body_length as usize,
                     body: None,
+                    meta,
                 }
             },
         )
@@ -1340,3 +2728,452 @@ fn test_is_absolute_path() {
         assert!(!is_absolute_path("./local"));
     }
 }
+
+#[cfg(test)]
+mod vec_tests {
+    use super::*;
+    use crate::test_support::TempStore;
+
+    /// Insert a one-chunk document with the given embedding.
+    fn add_doc(store: &TempStore, path: &str, embedding: &[f32]) {
+        let content = format!("# {path}\n");
+        store.add_doc(path, &content);
+        store
+            .insert_embedding(
+                &Store::hash_content(&content),
+                0,
+                0,
+                embedding,
+                "test",
+                "2025-01-01T00:00:00Z",
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn test_search_vec_ivf_matches_exact() {
+        let store = TempStore::new("ivf");
+        store.ensure_vector_table(3).unwrap();
+        for i in 0..40 {
+            let t = i as f32 / 40.0;
+            add_doc(&store, &format!("a{i}.md"), &[1.0, t, 0.0]);
+            add_doc(&store, &format!("b{i}.md"), &[0.0, t, 1.0]);
+        }
+        assert!(store.build_vector_index().unwrap() > 1);
+
+        let query = [0.0, 0.5, 1.0];
+        let options = VecSearchOptions {
+            probes: 1,
+            exact_threshold: 0,
+            ..VecSearchOptions::default()
+        };
+        let approx = store
+            .search_vec_with(&query, 5, &SearchFilter::default(), &options)
+            .unwrap();
+        let exact = store
+            .search_vec_with(
+                &query,
+                5,
+                &SearchFilter::default(),
+                &VecSearchOptions::exact(),
+            )
+            .unwrap();
+        let approx: Vec<_> = approx.iter().map(|r| r.doc.path.clone()).collect();
+        let exact: Vec<_> = exact.iter().map(|r| r.doc.path.clone()).collect();
+        assert_eq!(approx, exact);
+
+        store.clear_embeddings().unwrap();
+        assert_eq!(store.get_vector_index_lists().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_requantize_keeps_ranking() {
+        let store = TempStore::new("quant");
+        store.ensure_vector_table(3).unwrap();
+        add_doc(&store, "x.md", &[1.0, 0.1, 0.0]);
+        add_doc(&store, "z.md", &[0.0, 0.1, 1.0]);
+
+        assert_eq!(store.requantize(Quantization::Int8).unwrap(), 2);
+        assert_eq!(store.get_vector_quantization().unwrap(), Quantization::Int8);
+        let results = store.search_vec(&[0.9, 0.0, 0.1], 2, None).unwrap();
+        assert_eq!(results[0].doc.path, "x.md");
+
+        assert_eq!(store.requantize(Quantization::Binary).unwrap(), 2);
+        let results = store.search_vec(&[0.1, 0.0, 0.9], 2, None).unwrap();
+        assert_eq!(results[0].doc.path, "z.md");
+        assert!(store.requantize(Quantization::F32).is_err());
+    }
+
+    #[test]
+    fn test_vector_chunker_change_requires_clearing() {
+        let store = TempStore::new("chunker");
+        assert_eq!(store.get_vector_chunker().unwrap(), Chunker::Tokens);
+        store.use_vector_chunker(Chunker::Markdown).unwrap();
+        assert_eq!(store.get_vector_chunker().unwrap(), Chunker::Markdown);
+
+        store.ensure_vector_table(2).unwrap();
+        add_doc(&store, "x.md", &[1.0, 0.0]);
+        store.use_vector_chunker(Chunker::Markdown).unwrap();
+        assert!(matches!(
+            store.use_vector_chunker(Chunker::Tokens),
+            Err(QmdError::Config(_))
+        ));
+        assert_eq!(store.get_vector_chunker().unwrap(), Chunker::Markdown);
+
+        store.clear_embeddings().unwrap();
+        store.use_vector_chunker(Chunker::Tokens).unwrap();
+        assert_eq!(store.get_vector_chunker().unwrap(), Chunker::Tokens);
+    }
+
+    #[test]
+    fn test_quantized_search_keeps_documents_with_many_chunks() {
+        let store = TempStore::new("quant-chunks");
+        store.ensure_vector_table(2).unwrap();
+        add_doc(&store, "long.md", &[1.0, 0.0]);
+        add_doc(&store, "short.md", &[0.9, 0.1]);
+        let hash = Store::hash_content("# long.md\n");
+        for seq in 1..5 {
+            store
+                .insert_embedding(
+                    &hash,
+                    seq,
+                    seq * 100,
+                    &[1.0, 0.0],
+                    "test",
+                    "2025-01-01T00:00:00Z",
+                )
+                .unwrap();
+        }
+        store.requantize(Quantization::Int8).unwrap();
+
+        let options = VecSearchOptions {
+            oversample: 1,
+            ..VecSearchOptions::exact()
+        };
+        let results = store
+            .search_vec_with(&[1.0, 0.0], 2, &SearchFilter::default(), &options)
+            .unwrap();
+        let paths: Vec<_> = results.iter().map(|r| r.doc.path.as_str()).collect();
+        assert_eq!(paths, ["long.md", "short.md"]);
+    }
+
+    #[test]
+    fn test_search_vec_reports_best_chunk() {
+        let store = TempStore::new("chunks");
+        store.ensure_vector_table(2).unwrap();
+        add_doc(&store, "long.md", &[1.0, 0.0]);
+        let hash = Store::hash_content("# long.md\n");
+        let now = "2025-01-01T00:00:00Z";
+        store
+            .insert_embedding(&hash, 1, 120, &[0.0, 1.0], "test", now)
+            .unwrap();
+
+        let results = store.search_vec(&[0.1, 1.0], 1, None).unwrap();
+        assert_eq!(results[0].chunk_seq, Some(1));
+        assert_eq!(results[0].chunk_pos, Some(120));
+    }
+
+    #[test]
+    fn test_search_vec_filters_before_limit() {
+        let store = TempStore::new("filter");
+        store.ensure_vector_table(2).unwrap();
+        add_doc(&store, "inbox/near.md", &[1.0, 0.0]);
+        add_doc(&store, "2024/far.md", &[0.0, 1.0]);
+
+        let (filter, _) = SearchFilter::parse("path:2024/*").unwrap();
+        let results = store
+            .search_vec_with(&[1.0, 0.0], 1, &filter, &VecSearchOptions::exact())
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].doc.path, "2024/far.md");
+    }
+
+    #[test]
+    fn test_search_vec_filters_with_ivf_index() {
+        let store = TempStore::new("ivf-filter");
+        store.ensure_vector_table(3).unwrap();
+        for i in 0..40 {
+            let t = i as f32 / 40.0;
+            add_doc(&store, &format!("a/{i}.md"), &[1.0, t, 0.0]);
+            add_doc(&store, &format!("b/{i}.md"), &[0.0, t, 1.0]);
+        }
+        assert!(store.build_vector_index().unwrap() > 1);
+
+        // The nearest list holds only `b/` documents.
+        let options = VecSearchOptions {
+            probes: 1,
+            exact_threshold: 0,
+            ..VecSearchOptions::default()
+        };
+        let (filter, _) = SearchFilter::parse("path:a/*").unwrap();
+        let results = store
+            .search_vec_with(&[0.0, 0.5, 1.0], 5, &filter, &options)
+            .unwrap();
+        assert_eq!(results.len(), 5);
+        assert!(results.iter().all(|r| r.doc.path.starts_with("a/")));
+    }
+}
+
+#[cfg(test)]
+mod schema_tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    #[test]
+    fn test_migrations_are_ordered() {
+        for (i, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(migration.version as usize, i + 1);
+        }
+        assert_eq!(MIGRATIONS.last().map(|m| m.version), Some(SCHEMA_VERSION));
+    }
+
+    #[test]
+    fn test_unversioned_database_is_upgraded() {
+        let dir = TempDir::new("legacy");
+        let path = dir.path().join("index.sqlite");
+        {
+            let conn = Connection::open(&path).unwrap();
+            conn.execute_batch(
+                "CREATE TABLE content (hash TEXT PRIMARY KEY, doc TEXT NOT NULL, created_at TEXT NOT NULL);
+                 INSERT INTO content VALUES ('abc', 'hello', 'now');",
+            )
+            .unwrap();
+        }
+        let store = Store::open(&path).unwrap();
+        assert_eq!(store.schema_version().unwrap(), SCHEMA_VERSION);
+        assert_eq!(store.get_status().unwrap().schema_version, SCHEMA_VERSION);
+        let doc: String = store
+            .conn
+            .query_row("SELECT doc FROM content WHERE hash = 'abc'", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(doc, "hello");
+    }
+
+    #[test]
+    fn test_newer_database_is_refused() {
+        let dir = TempDir::new("newer");
+        let path = dir.path().join("index.sqlite");
+        {
+            let conn = Connection::open(&path).unwrap();
+            conn.pragma_update(None, "user_version", SCHEMA_VERSION + 1)
+                .unwrap();
+        }
+        assert!(matches!(
+            Store::open(&path),
+            Err(QmdError::SchemaTooNew { .. })
+        ));
+    }
+}
+
+#[cfg(test)]
+mod fts_tests {
+    use super::*;
+    use crate::query::Bm25Weights;
+    use crate::test_support::{TempStore, paths};
+
+    #[test]
+    fn test_search_fts_query_syntax() {
+        let store = TempStore::new("syntax");
+        store.add_doc(
+            "rust.md",
+            "# Rust Errors\nerror handling with Result and async",
+        );
+        store.add_doc("go.md", "# Go Errors\nerror handling with panics");
+        store.add_doc(
+            "misc/c.md",
+            "# Notes\nC-style (manual) error codes: AND NEAR",
+        );
+
+        let search = |q: &str| store.search_fts(q, 10, None).unwrap();
+        assert_eq!(paths(&search("error -async")), ["go.md", "misc/c.md"]);
+        assert_eq!(paths(&search("\"error handling\" -panics")), ["rust.md"]);
+        assert_eq!(paths(&search("rust OR panics")), ["go.md", "rust.md"]);
+        assert_eq!(paths(&search("hand*")), ["go.md", "rust.md"]);
+        assert_eq!(paths(&search("title:errors")), ["go.md", "rust.md"]);
+        assert_eq!(paths(&search("title:notes")), ["misc/c.md"]);
+
+        // Punctuation and FTS5 keywords are searched literally.
+        assert_eq!(
+            paths(&search("C-style (manual) codes: AND NEAR")),
+            ["misc/c.md"]
+        );
+
+        assert!(matches!(
+            store.search_fts("\"unterminated", 10, None),
+            Err(QmdError::InvalidQuery(_))
+        ));
+    }
+
+    #[test]
+    fn test_search_fts_splits_inline_filters() {
+        let store = TempStore::new("filters");
+        store.add_doc("notes/rust.md", "# Rust\nownership and borrowing");
+        store.add_doc("journal/rust.md", "# Rust\nlearning ownership today");
+
+        let search = |q: &str| store.search_fts(q, 10, None).unwrap();
+        assert_eq!(paths(&search("rust path:notes/*")), ["notes/rust.md"]);
+        assert_eq!(paths(&search("ownership")).len(), 2);
+        assert!(search("rust collection:elsewhere").is_empty());
+    }
+
+    #[test]
+    fn test_add_snippets_follow_stemming() {
+        let store = TempStore::new("snippets");
+        store.add_doc(
+            "café.md",
+            "# Café notes\nNothing here.\nWe were running to the café.\nRuns daily.",
+        );
+
+        let mut results = store.search_fts("run café", 10, None).unwrap();
+        store.add_snippets("run café", &mut results).unwrap();
+        let snippets = &results[0].snippets;
+        assert_eq!(
+            snippets.iter().map(|s| s.line).collect::<Vec<_>>(),
+            [1, 3, 4]
+        );
+        let highlighted: Vec<&str> = snippets[1]
+            .parts()
+            .into_iter()
+            .filter_map(|(text, hit)| hit.then_some(text))
+            .collect();
+        assert_eq!(highlighted, ["running", "café"]);
+    }
+
+    #[test]
+    fn test_search_fts_bm25_weights() {
+        let store = TempStore::new("weights");
+        store.add_doc("title.md", "# Tokio\nA runtime.");
+        store.add_doc("body.md", "# Runtimes\ntokio tokio tokio and more tokio");
+
+        let search = |options: &FtsSearchOptions| {
+            store
+                .search_fts_with("tokio", 10, &SearchFilter::default(), options)
+                .unwrap()
+        };
+        let boosted = search(&FtsSearchOptions::default());
+        assert_eq!(boosted[0].doc.path, "title.md");
+
+        let flat = Bm25Weights {
+            path: 1.0,
+            title: 1.0,
+            body: 1.0,
+        };
+        let mut options = FtsSearchOptions {
+            weights: flat,
+            ..FtsSearchOptions::default()
+        };
+        assert_eq!(search(&options)[0].doc.path, "body.md");
+
+        // A collection override takes precedence over the global weights.
+        options
+            .collection_weights
+            .insert("notes".to_string(), Bm25Weights::default());
+        assert_eq!(search(&options)[0].doc.path, "title.md");
+    }
+
+    #[test]
+    fn test_search_fts_filters_before_limit() {
+        let store = TempStore::new("filter");
+        for i in 0..5 {
+            store.add_doc(&format!("inbox/{i}.md"), "rust rust rust");
+        }
+        store.add_doc("2024/a.md", "rust");
+        store.add_doc("2024/b.md", "python");
+        store.add_doc("archive/2024/c.md", "rust");
+
+        let search = |q: &str, limit| {
+            let (filter, text) = SearchFilter::parse(q).unwrap();
+            store
+                .search_fts_with(&text, limit, &filter, &FtsSearchOptions::default())
+                .unwrap()
+        };
+        // The better-scoring inbox hits must not use up the limit.
+        assert_eq!(paths(&search("rust path:2024/*", 1)), ["2024/a.md"]);
+        assert_eq!(
+            paths(&search("rust path:2024/* path:archive/*", 10)),
+            ["2024/a.md", "archive/2024/c.md"]
+        );
+        assert_eq!(
+            paths(&search("rust collection:other", 10)),
+            Vec::<&str>::new()
+        );
+        assert_eq!(
+            search("rust collection:notes collection:other", 10).len(),
+            7
+        );
+        assert_eq!(
+            search("rust after:2024-12-31 before:2025-01-02", 10).len(),
+            7
+        );
+        assert!(search("rust before:2025-01-01", 10).is_empty());
+    }
+}
+
+#[cfg(test)]
+mod listing_tests {
+    use super::*;
+    use crate::test_support::TempStore;
+
+    #[test]
+    fn test_list_files_page_crosses_collections() {
+        let store = TempStore::new("pages");
+        store.add_doc("a.md", "# Café\nau lait");
+        store.add_doc("b.md", "# B");
+        store.add_doc("sub/c.md", "# C");
+        let hash = Store::hash_content("# Old");
+        let now = "2025-01-01T00:00:00Z";
+        store.insert_content(&hash, "# Old", now).unwrap();
+        store
+            .insert_document("archive", "old.md", "Old", &hash, now, now)
+            .unwrap();
+        store
+            .insert_document("hidden", "x.md", "Old", &hash, now, now)
+            .unwrap();
+
+        // Page as the MCP resource listing does: one extra file tells whether
+        // there is a next page, whose cursor is the last file's virtual path.
+        let names = ["archive".to_string(), "notes".to_string()];
+        let page_size = 2;
+        let mut pages = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let after = cursor.as_deref().map(|c| parse_virtual_path(c).unwrap());
+            let mut files = store
+                .list_collection_files_page(
+                    &names,
+                    after.as_ref().map(|(c, p)| (c.as_str(), p.as_str())),
+                    page_size + 1,
+                )
+                .unwrap();
+            let more = files.len() > page_size;
+            files.truncate(page_size);
+            cursor = files
+                .last()
+                .filter(|_| more)
+                .map(|(collection, path, _, _)| build_virtual_path(collection, path));
+            pages.push(
+                files
+                    .iter()
+                    .map(|(c, p, _, _)| format!("{c}/{p}"))
+                    .collect::<Vec<_>>(),
+            );
+            if cursor.is_none() {
+                break;
+            }
+        }
+        assert_eq!(
+            pages,
+            [
+                vec!["archive/old.md", "notes/a.md"],
+                vec!["notes/b.md", "notes/sub/c.md"],
+            ]
+        );
+
+        // Sizes are in bytes, not characters.
+        let first = store.list_collection_files_page(&names, None, 2).unwrap();
+        assert_eq!(first[1].3, "# Café\nau lait".len());
+        assert_eq!(first[1].2, "Café");
+    }
+}
@@ -0,0 +1,209 @@
+//! Tags.
+//!
+//! Tags come from the frontmatter `tags` field, as a list or a comma or
+//! space separated string, and from inline `#tags` in the body. Inline tags
+//! must start a word and contain at least one non-digit; heading markers,
+//! code blocks and code spans are skipped. Tags are normalized to lowercase
+//! without the leading `#`; `/` separates nested tags, so `#project/alpha`
+//! is also found under `project`.
+//!
+//! Tags are stored per document in `document_tags` and kept in sync by the
+//! same store methods that insert, update and deactivate documents.
+
+use crate::error::Result;
+use crate::filter::SearchFilter;
+use crate::frontmatter::{parse_frontmatter, split_frontmatter};
+use crate::store::{DocumentResult, Store};
+use std::collections::BTreeSet;
+
+/// Number of active documents with a tag in a collection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagCount {
+    /// Collection name.
+    pub collection: String,
+    /// Normalized tag.
+    pub tag: String,
+    /// Number of documents.
+    pub count: usize,
+}
+
+/// Whether `c` may appear in a tag.
+fn is_tag_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '-' | '/')
+}
+
+/// Normalize a tag: strip a leading `#` and surrounding `/`, lowercase.
+///
+/// Returns `None` if the result is empty, all digits or contains characters
+/// other than letters, digits, `_`, `-` and `/`.
+#[must_use]
+pub fn normalize_tag(tag: &str) -> Option<String> {
+    let name = tag.trim().trim_start_matches('#').trim_matches('/');
+    let valid = !name.is_empty()
+        && name.chars().all(is_tag_char)
+        && !name.chars().all(|c| c.is_ascii_digit() || c == '/');
+    valid.then(|| name.to_lowercase())
+}
+
+/// Extract the normalized tags of a document, sorted and deduplicated.
+#[must_use]
+pub fn extract_tags(content: &str) -> Vec<String> {
+    let mut tags = BTreeSet::new();
+
+    if let Some(field) = parse_frontmatter(content).get("tags") {
+        for value in field.values() {
+            tags.extend(
+                value
+                    .split(|c: char| c == ',' || c.is_whitespace())
+                    .filter_map(normalize_tag),
+            );
+        }
+    }
+
+    let mut in_fence = false;
+    for line in split_frontmatter(content).1.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+            continue;
+        }
+        if in_fence {
+            continue;
+        }
+        tags.extend(inline_tags(line));
+    }
+
+    tags.into_iter().collect()
+}
+
+/// Inline `#tags` of a line, outside code spans.
+fn inline_tags(line: &str) -> Vec<String> {
+    let mut tags = Vec::new();
+    let mut in_code = false;
+    let mut prev = ' ';
+    for (i, c) in line.char_indices() {
+        if c == '`' {
+            in_code = !in_code;
+        } else if c == '#' && !in_code && (prev.is_whitespace() || matches!(prev, '(' | '[')) {
+            let rest = &line[i + 1..];
+            let end = rest.find(|ch: char| !is_tag_char(ch)).unwrap_or(rest.len());
+            tags.extend(normalize_tag(&rest[..end]));
+        }
+        prev = c;
+    }
+    tags
+}
+
+impl Store {
+    /// Count the tags of active documents per collection, optionally only in
+    /// one collection.
+    ///
+    /// Sorted by collection, then by descending count and tag.
+    pub fn list_tags(&self, collection: Option<&str>) -> Result<Vec<TagCount>> {
+        self.tag_counts(&SearchFilter::collection(collection))
+    }
+
+    /// Active documents with a tag or one of its nested tags, in path order.
+    pub fn get_tagged_documents(
+        &self,
+        tag: &str,
+        collection: Option<&str>,
+    ) -> Result<Vec<DocumentResult>> {
+        let Some(normalized) = normalize_tag(tag) else {
+            return Ok(Vec::new());
+        };
+        let mut filter = SearchFilter::collection(collection);
+        filter.tags.push(normalized);
+        self.list_documents(&filter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query::FtsSearchOptions;
+    use crate::test_support::{TempStore, paths};
+
+    #[test]
+    fn test_normalize_tag() {
+        assert_eq!(
+            normalize_tag("#Project/Alpha/").as_deref(),
+            Some("project/alpha")
+        );
+        assert_eq!(normalize_tag("to-do_2").as_deref(), Some("to-do_2"));
+        assert_eq!(normalize_tag("#123"), None);
+        assert_eq!(normalize_tag("a.b"), None);
+        assert_eq!(normalize_tag("#"), None);
+    }
+
+    #[test]
+    fn test_extract_tags() {
+        let content = "---\ntags: [Rust, \"cli tools\"]\n---\n\
+                       # Heading #heading-tag\n\
+                       Notes on #async and (#Tokio), not a#b or #1 or `#code`.\n\
+                       ```\n#fenced\n```\n\
+                       See https://example.com/#anchor and #project/alpha.\n";
+        assert_eq!(
+            extract_tags(content),
+            [
+                "async",
+                "cli",
+                "heading-tag",
+                "project/alpha",
+                "rust",
+                "tokio",
+                "tools"
+            ]
+        );
+        assert_eq!(
+            extract_tags("---\ntags: a, b\n---\nbody"),
+            ["a".to_string(), "b".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_tags_follow_documents() {
+        let store = TempStore::new("tags");
+        store.add_doc("a.md", "---\ntags: [plan]\n---\n# A\nrust #project/alpha");
+        store.add_doc("b.md", "# B\nrust #project");
+        store.add_doc("c.md", "# C\nrust #plan");
+
+        let counts = || {
+            store
+                .list_tags(None)
+                .unwrap()
+                .into_iter()
+                .map(|t| format!("{}:{}", t.tag, t.count))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(counts(), ["plan:2", "project:1", "project/alpha:1"]);
+
+        let tagged = |tag: &str| {
+            store
+                .get_tagged_documents(tag, Some("notes"))
+                .unwrap()
+                .into_iter()
+                .map(|d| d.path)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(tagged("#Project"), ["a.md", "b.md"]);
+        assert_eq!(tagged("project/alpha"), ["a.md"]);
+        assert!(tagged("proj").is_empty());
+
+        let (filter, text) = SearchFilter::parse("rust tag:plan").unwrap();
+        let results = store
+            .search_fts_with(&text, 10, &filter, &FtsSearchOptions::default())
+            .unwrap();
+        assert_eq!(paths(&results), ["a.md", "c.md"]);
+
+        // Updates replace tags; deactivated and deleted documents lose them.
+        store.update_doc("c.md", "# C\nrust #done");
+        store.deactivate_document("notes", "b.md").unwrap();
+        assert_eq!(counts(), ["done:1", "plan:1", "project/alpha:1"]);
+
+        store.add_doc("b.md", "# B\nrust #project");
+        assert_eq!(tagged("project"), ["a.md", "b.md"]);
+        store.remove_collection_documents("notes").unwrap();
+        assert!(counts().is_empty());
+    }
+}
@@ -0,0 +1,101 @@
+//! Fixtures shared by the unit tests.
+
+use crate::store::{SearchResult, Store};
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A fresh directory in the system temp directory, removed with everything
+/// in it when dropped.
+#[derive(Debug)]
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// Create an empty directory; `name` only makes it easier to recognize.
+    pub fn new(name: &str) -> Self {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "qmd-test-{}-{}-{name}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+
+    /// Path of the directory.
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// A store on a database in its own [`TempDir`].
+#[derive(Debug)]
+pub struct TempStore {
+    // Declared before `dir` so the connection is closed before the database
+    // files are removed.
+    store: Store,
+    dir: TempDir,
+}
+
+impl TempStore {
+    /// Open a store on a new database.
+    pub fn new(name: &str) -> Self {
+        let dir = TempDir::new(name);
+        let store = Store::open(&dir.path().join("index.sqlite")).unwrap();
+        Self { store, dir }
+    }
+
+    /// Directory holding the database, for test files.
+    pub fn dir(&self) -> &Path {
+        self.dir.path()
+    }
+
+    /// Insert a document into the `notes` collection.
+    pub fn add_doc(&self, path: &str, content: &str) {
+        let hash = Store::hash_content(content);
+        let title = Store::extract_title(content);
+        let now = "2025-01-01T00:00:00Z";
+        self.store.insert_content(&hash, content, now).unwrap();
+        self.store
+            .insert_document("notes", path, &title, &hash, now, now)
+            .unwrap();
+    }
+
+    /// Replace the content of an active document in the `notes` collection.
+    pub fn update_doc(&self, path: &str, content: &str) {
+        let hash = Store::hash_content(content);
+        let title = Store::extract_title(content);
+        let now = "2025-01-02T00:00:00Z";
+        self.store.insert_content(&hash, content, now).unwrap();
+        let id = self
+            .store
+            .find_active_document("notes", path)
+            .unwrap()
+            .unwrap()
+            .0;
+        self.store.update_document(id, &title, &hash, now).unwrap();
+    }
+}
+
+impl Deref for TempStore {
+    type Target = Store;
+
+    fn deref(&self) -> &Store {
+        &self.store
+    }
+}
+
+/// Paths of search results, sorted.
+pub fn paths(results: &[SearchResult]) -> Vec<&str> {
+    let mut paths: Vec<&str> = results.iter().map(|r| r.doc.path.as_str()).collect();
+    paths.sort_unstable();
+    paths
+}
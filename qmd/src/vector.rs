@@ -0,0 +1,446 @@
+//! Vector storage helpers and the IVF approximate nearest-neighbour index.
+//!
+//! Embeddings are stored as BLOBs in `vectors_vec`, encoded in the index's
+//! [`Quantization`] (`f32` by default). On top of that table the store keeps
+//! an inverted-file (IVF) index: a set of k-means centroids and an assignment
+//! of every vector to its nearest centroid. A query only scores the vectors in the `probes` lists whose
+//! centroids are closest to it, trading a little recall for a large cut in
+//! the number of vectors touched.
+//!
+//! Vectors may also be stored quantized (int8 scalar or 1-bit binary). A
+//! quantized search scores candidates on the compact codes first and then
+//! rescores the best documents with the full-precision query against their
+//! decoded codes. The full-precision vectors are not kept, so the rescore is
+//! asymmetric: it removes the query's quantization error but not the
+//! document's.
+
+use crate::llm::cosine_similarity;
+
+/// Minimum number of vectors before an IVF index is built.
+pub const IVF_MIN_VECTORS: usize = 1024;
+
+/// Maximum number of IVF lists.
+pub const IVF_MAX_LISTS: usize = 1024;
+
+/// Number of k-means iterations used when training centroids.
+pub const IVF_TRAIN_ITERATIONS: usize = 10;
+
+/// Maximum number of vectors sampled for k-means training.
+pub const IVF_MAX_TRAINING_SAMPLES: usize = 64 * 1024;
+
+/// Storage format of the vectors in `vectors_vec`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Quantization {
+    /// Raw little-endian `f32` values.
+    #[default]
+    F32,
+    /// One signed byte per dimension plus a per-vector `f32` scale.
+    Int8,
+    /// One sign bit per dimension.
+    Binary,
+}
+
+impl Quantization {
+    /// Name stored in the database.
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::F32 => "f32",
+            Self::Int8 => "int8",
+            Self::Binary => "binary",
+        }
+    }
+
+    /// Parse a stored or user-supplied name.
+    #[must_use]
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "f32" => Some(Self::F32),
+            "int8" => Some(Self::Int8),
+            "binary" => Some(Self::Binary),
+            _ => None,
+        }
+    }
+
+    /// Relative precision, higher is more precise.
+    const fn precision(self) -> u8 {
+        match self {
+            Self::F32 => 2,
+            Self::Int8 => 1,
+            Self::Binary => 0,
+        }
+    }
+
+    /// Whether vectors stored as `self` can be converted to `target`
+    /// without pretending to recover lost precision.
+    #[must_use]
+    pub const fn can_convert_to(self, target: Self) -> bool {
+        target.precision() <= self.precision()
+    }
+
+    /// Encode an embedding into this storage format.
+    #[must_use]
+    pub fn encode(self, embedding: &[f32]) -> Vec<u8> {
+        match self {
+            Self::F32 => encode_f32(embedding),
+            Self::Int8 => {
+                let max = embedding.iter().fold(0.0f32, |m, x| m.max(x.abs()));
+                let scale = if max > 0.0 { max / 127.0 } else { 1.0 };
+                let mut out = Vec::with_capacity(4 + embedding.len());
+                out.extend_from_slice(&scale.to_le_bytes());
+                out.extend(
+                    embedding
+                        .iter()
+                        .map(|x| (x / scale).round().clamp(-127.0, 127.0) as i8 as u8),
+                );
+                out
+            }
+            Self::Binary => {
+                let mut out = vec![0u8; embedding.len().div_ceil(8)];
+                for (i, x) in embedding.iter().enumerate() {
+                    if *x > 0.0 {
+                        out[i / 8] |= 1 << (i % 8);
+                    }
+                }
+                out
+            }
+        }
+    }
+
+    /// Decode a stored vector back to `f32`.
+    ///
+    /// Int8 and binary vectors are approximations; binary vectors decode to
+    /// `±1.0` per dimension. `dims` is needed to strip binary padding bits and
+    /// may be 0 when unknown.
+    #[must_use]
+    pub fn decode(self, bytes: &[u8], dims: usize) -> Vec<f32> {
+        match self {
+            Self::F32 => decode_f32(bytes),
+            Self::Int8 => {
+                let Some((scale, codes)) = bytes.split_first_chunk::<4>() else {
+                    return Vec::new();
+                };
+                let scale = f32::from_le_bytes(*scale);
+                codes
+                    .iter()
+                    .map(|&c| f32::from(c.cast_signed()) * scale)
+                    .collect()
+            }
+            Self::Binary => {
+                let bits = bytes.len() * 8;
+                let n = if dims == 0 { bits } else { dims.min(bits) };
+                (0..n)
+                    .map(|i| {
+                        if bytes[i / 8] & (1 << (i % 8)) == 0 {
+                            -1.0
+                        } else {
+                            1.0
+                        }
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// Cheap similarity between two vectors encoded in this format.
+    ///
+    /// Only meaningful for ranking: int8 codes use an integer cosine and
+    /// binary codes use `1 - 2 * hamming / bits`.
+    #[must_use]
+    pub fn approx_similarity(self, a: &[u8], b: &[u8]) -> f32 {
+        match self {
+            Self::F32 => cosine_similarity(&decode_f32(a), &decode_f32(b)),
+            Self::Int8 => {
+                let (a, b) = (
+                    a.get(4..).unwrap_or_default(),
+                    b.get(4..).unwrap_or_default(),
+                );
+                if a.len() != b.len() || a.is_empty() {
+                    return 0.0;
+                }
+                let (mut dot, mut na, mut nb) = (0i64, 0i64, 0i64);
+                for (&x, &y) in a.iter().zip(b) {
+                    let (x, y) = (i64::from(x.cast_signed()), i64::from(y.cast_signed()));
+                    dot += x * y;
+                    na += x * x;
+                    nb += y * y;
+                }
+                if na == 0 || nb == 0 {
+                    return 0.0;
+                }
+                (dot as f64 / ((na as f64).sqrt() * (nb as f64).sqrt())) as f32
+            }
+            Self::Binary => {
+                if a.len() != b.len() || a.is_empty() {
+                    return 0.0;
+                }
+                let hamming: u32 = a.iter().zip(b).map(|(x, y)| (x ^ y).count_ones()).sum();
+                1.0 - 2.0 * hamming as f32 / (a.len() * 8) as f32
+            }
+        }
+    }
+}
+
+/// How chunk similarities are combined into a document score.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChunkAggregation {
+    /// Score of the best-matching chunk.
+    #[default]
+    Max,
+    /// Mean score over the document's scored chunks.
+    Mean,
+    /// Average of `Max` and `Mean`, rewarding consistently relevant documents.
+    MaxMean,
+}
+
+impl ChunkAggregation {
+    /// Combine chunk similarities into one document score.
+    #[must_use]
+    pub fn aggregate(self, scores: &[f32]) -> f32 {
+        if scores.is_empty() {
+            return 0.0;
+        }
+        let max = scores.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        let mean = scores.iter().sum::<f32>() / scores.len() as f32;
+        match self {
+            Self::Max => max,
+            Self::Mean => mean,
+            Self::MaxMean => f32::midpoint(max, mean),
+        }
+    }
+}
+
+/// Options for vector similarity search.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VecSearchOptions {
+    /// Number of IVF lists to scan. Higher values improve recall at the
+    /// cost of latency; a value >= the number of lists is an exact search.
+    pub probes: usize,
+    /// Indexes with fewer vectors than this are always searched exactly.
+    pub exact_threshold: usize,
+    /// How chunk scores are combined per document. With an IVF index only
+    /// the chunks in probed lists contribute.
+    pub aggregation: ChunkAggregation,
+    /// For quantized indexes, the number of documents rescored with the
+    /// full-precision query per requested result.
+    pub oversample: usize,
+}
+
+impl Default for VecSearchOptions {
+    fn default() -> Self {
+        Self {
+            probes: 8,
+            exact_threshold: IVF_MIN_VECTORS,
+            aggregation: ChunkAggregation::Max,
+            oversample: 10,
+        }
+    }
+}
+
+impl VecSearchOptions {
+    /// Options that always perform an exhaustive scan.
+    #[must_use]
+    pub const fn exact() -> Self {
+        Self {
+            probes: usize::MAX,
+            exact_threshold: usize::MAX,
+            aggregation: ChunkAggregation::Max,
+            oversample: 10,
+        }
+    }
+}
+
+/// Encode an embedding as a little-endian `f32` BLOB.
+#[must_use]
+pub fn encode_f32(embedding: &[f32]) -> Vec<u8> {
+    embedding.iter().flat_map(|f| f.to_le_bytes()).collect()
+}
+
+/// Decode a little-endian `f32` BLOB.
+#[must_use]
+pub fn decode_f32(bytes: &[u8]) -> Vec<f32> {
+    bytes
+        .chunks_exact(4)
+        .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+        .collect()
+}
+
+/// Number of IVF lists to use for an index of `n` vectors.
+#[must_use]
+pub fn ivf_list_count(n: usize) -> usize {
+    n.isqrt().clamp(1, IVF_MAX_LISTS)
+}
+
+/// Scale a vector to unit length in place.
+fn normalize(v: &mut [f32]) {
+    let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        for x in v.iter_mut() {
+            *x /= norm;
+        }
+    }
+}
+
+/// Train `nlist` centroids over `vectors` with spherical k-means.
+///
+/// Initial centroids are picked at evenly spaced positions so training is
+/// deterministic for a given input.
+#[must_use]
+pub fn train_centroids(vectors: &[Vec<f32>], nlist: usize, iterations: usize) -> Vec<Vec<f32>> {
+    let k = nlist.min(vectors.len());
+    if k == 0 {
+        return Vec::new();
+    }
+    let dims = vectors[0].len();
+
+    let stride = vectors.len() / k;
+    let mut centroids: Vec<Vec<f32>> = (0..k)
+        .map(|i| {
+            let mut c = vectors[i * stride].clone();
+            normalize(&mut c);
+            c
+        })
+        .collect();
+
+    let mut assignments = vec![usize::MAX; vectors.len()];
+    for _ in 0..iterations {
+        let mut changed = false;
+        for (v, slot) in vectors.iter().zip(assignments.iter_mut()) {
+            let nearest = nearest_centroid(&centroids, v);
+            if *slot != nearest {
+                *slot = nearest;
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+
+        let mut sums = vec![vec![0.0f32; dims]; k];
+        let mut counts = vec![0usize; k];
+        for (v, &list) in vectors.iter().zip(&assignments) {
+            counts[list] += 1;
+            for (s, x) in sums[list].iter_mut().zip(v) {
+                *s += x;
+            }
+        }
+        for ((centroid, mut sum), count) in centroids.iter_mut().zip(sums).zip(counts) {
+            // Empty lists keep their previous centroid.
+            if count > 0 {
+                normalize(&mut sum);
+                *centroid = sum;
+            }
+        }
+    }
+
+    centroids
+}
+
+/// Index of the centroid most similar to `v`.
+#[must_use]
+pub fn nearest_centroid(centroids: &[Vec<f32>], v: &[f32]) -> usize {
+    nearest_centroids(centroids, v, 1)
+        .first()
+        .copied()
+        .unwrap_or(0)
+}
+
+/// Indices of the `n` centroids most similar to `v`, best first.
+#[must_use]
+pub fn nearest_centroids(centroids: &[Vec<f32>], v: &[f32], n: usize) -> Vec<usize> {
+    let mut scored: Vec<(usize, f32)> = centroids
+        .iter()
+        .enumerate()
+        .map(|(i, c)| (i, cosine_similarity(c, v)))
+        .collect();
+    scored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+    scored.into_iter().take(n).map(|(i, _)| i).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_f32_roundtrip() {
+        let v = vec![0.5, -1.25, 3.0];
+        assert_eq!(decode_f32(&encode_f32(&v)), v);
+    }
+
+    #[test]
+    fn test_quantization_roundtrip() {
+        let v = vec![0.5, -1.0, 0.25, 0.0, 0.75];
+        assert_eq!(
+            Quantization::F32.decode(&Quantization::F32.encode(&v), 0),
+            v
+        );
+
+        let int8 = Quantization::Int8.decode(&Quantization::Int8.encode(&v), 0);
+        for (a, b) in v.iter().zip(&int8) {
+            assert!((a - b).abs() < 0.01);
+        }
+
+        let binary = Quantization::Binary.encode(&v);
+        assert_eq!(binary.len(), 1);
+        assert_eq!(
+            Quantization::Binary.decode(&binary, v.len()),
+            vec![1.0, -1.0, 1.0, -1.0, 1.0]
+        );
+    }
+
+    #[test]
+    fn test_quantized_approx_similarity_ranks() {
+        let q = [1.0, 0.2, -0.3, 0.5];
+        let near = [0.9, 0.1, -0.2, 0.6];
+        let far = [-0.8, 0.3, 0.4, -0.5];
+        for mode in [Quantization::F32, Quantization::Int8, Quantization::Binary] {
+            let (qe, ne, fe) = (mode.encode(&q), mode.encode(&near), mode.encode(&far));
+            assert!(mode.approx_similarity(&qe, &ne) > mode.approx_similarity(&qe, &fe));
+        }
+    }
+
+    #[test]
+    fn test_quantization_conversions() {
+        assert!(Quantization::F32.can_convert_to(Quantization::Int8));
+        assert!(Quantization::Int8.can_convert_to(Quantization::Binary));
+        assert!(!Quantization::Binary.can_convert_to(Quantization::F32));
+        assert_eq!(Quantization::parse("int8"), Some(Quantization::Int8));
+        assert_eq!(Quantization::parse("f16"), None);
+    }
+
+    #[test]
+    fn test_chunk_aggregation() {
+        let scores = [0.2, 0.8, 0.5];
+        assert!((ChunkAggregation::Max.aggregate(&scores) - 0.8).abs() < 1e-6);
+        assert!((ChunkAggregation::Mean.aggregate(&scores) - 0.5).abs() < 1e-6);
+        assert!((ChunkAggregation::MaxMean.aggregate(&scores) - 0.65).abs() < 1e-6);
+        assert!(ChunkAggregation::Max.aggregate(&[]).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn test_ivf_list_count() {
+        assert_eq!(ivf_list_count(0), 1);
+        assert_eq!(ivf_list_count(10_000), 100);
+        assert_eq!(ivf_list_count(usize::MAX), IVF_MAX_LISTS);
+    }
+
+    #[test]
+    fn test_train_centroids_separates_clusters() {
+        let mut vectors = Vec::new();
+        for i in 0..20 {
+            let jitter = i as f32 * 0.001;
+            vectors.push(vec![1.0, jitter, 0.0]);
+            vectors.push(vec![0.0, jitter, 1.0]);
+        }
+        let centroids = train_centroids(&vectors, 2, IVF_TRAIN_ITERATIONS);
+        assert_eq!(centroids.len(), 2);
+
+        let a = nearest_centroid(&centroids, &[1.0, 0.0, 0.0]);
+        let b = nearest_centroid(&centroids, &[0.0, 0.0, 1.0]);
+        assert_ne!(a, b);
+        assert_eq!(
+            nearest_centroids(&centroids, &[1.0, 0.0, 0.1], 2),
+            vec![a, b]
+        );
+    }
+}